@@ -1,10 +1,28 @@
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
-use std::io::Read;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use clap::{Parser, Subcommand};
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use llvm_cov_easy::analysis::{AnalysisOptions, AnalysisResult};
+use llvm_cov_easy::badge::{Badge, ColorThreshold, Metric};
+use llvm_cov_easy::comment::{CommentOptions, GITHUB_COMMENT_LIMIT};
+use llvm_cov_easy::format::template::{Template, Templates, format_templated};
+use llvm_cov_easy::format::{FormatOptions, GapOrder};
+use llvm_cov_easy::history::{HistoryEntry, parse_history};
+use llvm_cov_easy::lsp::{self, FrameError};
+use llvm_cov_easy::per_test::{TestId, TestIndex, parse_nextest_list};
+use llvm_cov_easy::score::{ScoreContext, parse_git_churn};
+use llvm_cov_easy::source::SourceCache;
+use llvm_cov_easy::totals;
+use llvm_cov_easy::workspace::Workspace;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
 
 /// Cargo wrapper for compact LLVM coverage output.
 #[derive(Parser)]
@@ -39,6 +57,15 @@ enum Commands {
     /// Reads a JSON file (or stdin if no path given) produced by
     /// `cargo llvm-cov --json` and outputs compact coverage gap information.
     Analyze {
+        /// Analysis options.
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+        /// Output options.
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Workspace options.
+        #[command(flatten)]
+        workspace: WorkspaceArgs,
         /// Path to the coverage JSON file. Reads from stdin if not provided.
         path: Option<PathBuf>,
     },
@@ -48,6 +75,15 @@ enum Commands {
     /// Use `+toolchain` (e.g. `+nightly`) as the first argument to select
     /// a Rust toolchain.
     Run {
+        /// Analysis options.
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+        /// Output options.
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Workspace options.
+        #[command(flatten)]
+        workspace: WorkspaceArgs,
         /// Arguments forwarded to `cargo llvm-cov run`.
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -58,10 +94,596 @@ enum Commands {
     /// Use `+toolchain` (e.g. `+nightly`) as the first argument to select
     /// a Rust toolchain.
     Nextest {
+        /// Analysis options.
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+        /// Output options.
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Workspace options.
+        #[command(flatten)]
+        workspace: WorkspaceArgs,
+        /// Run each test alone with its own profile data and save what it
+        /// covers to `target/llvm-cov-easy/per-test.json` for `who-covers`,
+        /// then list the gaps each test closes, redundant tests, and a
+        /// minimal set preserving total coverage.
+        ///
+        /// The forwarded arguments select the tests and are passed to
+        /// `cargo nextest list` and `cargo nextest run`, so they must be
+        /// `cargo nextest` options. No report options apply.
+        #[arg(long, conflicts_with_all = ["AnalysisArgs", "OutputArgs", "WorkspaceArgs"])]
+        per_test: bool,
         /// Arguments forwarded to `cargo llvm-cov nextest`.
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
+    /// Re-run coverage whenever a `.rs` file changes and print the gaps
+    /// closed or opened since the previous run.
+    ///
+    /// The first run prints the full report. In formats other than the
+    /// markdown agent format, every run prints the full report in that
+    /// format. All trailing arguments are
+    /// forwarded to `cargo llvm-cov run` or `cargo llvm-cov nextest`.
+    Watch {
+        /// Analysis options.
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+        /// Output options.
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Workspace options.
+        #[command(flatten)]
+        workspace: WorkspaceArgs,
+        /// `cargo llvm-cov` subcommand to run.
+        #[arg(long, value_enum, default_value_t)]
+        runner: Runner,
+        /// Arguments forwarded to `cargo llvm-cov`.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Show coverage over recent `run` and `nextest` runs and flag files
+    /// whose coverage has been dropping.
+    ///
+    /// Every `run` and `nextest` appends its results to
+    /// `target/llvm-cov-easy/history.jsonl`.
+    Trend {
+        /// Number of recent runs to show.
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        runs: usize,
+    },
+    /// List the tests that cover a line, from the index saved by
+    /// `nextest --per-test`.
+    WhoCovers {
+        /// Location as `file:line`, e.g. `src/lib.rs:42`. The file may be a
+        /// suffix of the indexed path.
+        location: String,
+    },
+    /// Serve coverage gaps to editors as Language Server Protocol
+    /// diagnostics.
+    ///
+    /// Speaks LSP on stdin and stdout and marks the gaps in open documents
+    /// as informational diagnostics. Coverage is read from the newest
+    /// `.json` file in `target/llvm-cov-target`, and reloaded whenever one
+    /// is written there, e.g. by
+    /// `cargo llvm-cov --json --output-path target/llvm-cov-target/coverage.json`.
+    Lsp {
+        /// Analysis options.
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+        /// Workspace options.
+        #[command(flatten)]
+        workspace: WorkspaceArgs,
+    },
+    /// Serve coverage to coding agents over the Model Context Protocol.
+    ///
+    /// Speaks MCP on stdin and stdout with tools to run coverage, list
+    /// gaps by file and kind, list the gaps in a function, and show the
+    /// summary. Results are cached between calls until the next run. The
+    /// trailing arguments are forwarded to `cargo llvm-cov` unless a
+    /// `run_coverage` call passes its own.
+    Mcp {
+        /// Analysis options.
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+        /// Workspace options.
+        #[command(flatten)]
+        workspace: WorkspaceArgs,
+        /// `cargo llvm-cov` subcommand to run.
+        #[arg(long, value_enum, default_value_t)]
+        runner: Runner,
+        /// Arguments forwarded to `cargo llvm-cov`.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Write a coverage badge as an SVG image.
+    ///
+    /// Reads a JSON file (or stdin if no path given) produced by
+    /// `cargo llvm-cov --json` and renders one summary metric in the
+    /// color of the highest threshold it reaches, or red below all of them.
+    Badge {
+        /// Analysis options.
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+        /// Metric shown on the badge.
+        #[arg(long, value_enum, default_value_t)]
+        metric: BadgeMetric,
+        /// Text on the left of the badge. Defaults to `coverage` for lines
+        /// and e.g. `branch coverage` for other metrics.
+        #[arg(long)]
+        label: Option<String>,
+        /// Lowest percentage shown in a color, e.g. `80=yellow` or
+        /// `95=#4c1`; repeat for several. Defaults to 90=brightgreen,
+        /// 75=green, 60=yellowgreen, 40=yellow, and 20=orange.
+        #[arg(long = "threshold", value_name = "PERCENT=COLOR")]
+        thresholds: Vec<ColorThreshold>,
+        /// Path to write the SVG badge to.
+        #[arg(short, long, value_name = "PATH", default_value = "coverage.svg")]
+        output: PathBuf,
+        /// Also write a shields.io endpoint JSON to this path.
+        #[arg(long, value_name = "PATH")]
+        endpoint: Option<PathBuf>,
+        /// Path to the coverage JSON file. Reads from stdin if not provided.
+        path: Option<PathBuf>,
+    },
+}
+
+/// Options shared by every subcommand that analyzes coverage.
+///
+/// These must appear before any arguments forwarded to `cargo llvm-cov`.
+#[derive(Args, Debug, Default)]
+struct AnalysisArgs {
+    /// Report generic functions with unexecuted instantiations.
+    #[arg(long)]
+    instantiations: bool,
+    /// Report lines, regions, and branch directions executed fewer than N
+    /// times as weakly covered.
+    #[arg(long, value_name = "N")]
+    weak_threshold: Option<u64>,
+    /// List the N most-executed lines.
+    #[arg(long, value_name = "N")]
+    hot_lines: Option<usize>,
+    /// Do not read source files; branch gaps show raw counts instead of
+    /// the condition text, and `--exclude-tests` only finds test files.
+    #[arg(long)]
+    no_source: bool,
+    /// Leave test code out of gaps and totals: files under a package's
+    /// `tests` directory and `#[cfg(test)]` and `#[test]` items.
+    #[arg(long)]
+    exclude_tests: bool,
+    /// List items excluded with `coverage(off)` and flag any that are still
+    /// instrumented. Reads the package sources, so not with `--no-source`.
+    #[arg(long, conflicts_with = "no_source")]
+    audit_exclusions: bool,
+}
+
+impl AnalysisArgs {
+    /// Converts the command-line flags into library analysis options.
+    const fn to_options(&self) -> AnalysisOptions {
+        AnalysisOptions {
+            instantiations: self.instantiations,
+            weak_threshold: self.weak_threshold,
+            hot_lines: self.hot_lines,
+            read_sources: !self.no_source,
+            exclude_tests: self.exclude_tests,
+            audit_exclusions: self.audit_exclusions,
+            public_api: Vec::new(),
+        }
+    }
+}
+
+/// Output options shared by every subcommand that analyzes coverage.
+#[derive(Args, Debug, Default)]
+struct OutputArgs {
+    /// Order in which gaps are listed.
+    #[arg(long, value_enum, default_value_t)]
+    sort: SortOrder,
+    /// Output format. Defaults to `pretty` when stdout is a terminal or
+    /// with `--report-color always`, and to `markdown` otherwise.
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+    /// When to color `pretty` output. `auto` colors it on a terminal
+    /// unless `NO_COLOR` is set.
+    ///
+    /// Not `--color`, which `run`, `nextest`, and `watch` pass on to
+    /// `cargo llvm-cov`.
+    #[arg(long = "report-color", value_enum, default_value_t)]
+    color: ColorChoice,
+    /// Coverage JSON to compare totals with in `pr-comment` output, e.g.
+    /// from the target branch.
+    ///
+    /// Its totals are taken as exported: the sources checked out are not
+    /// the baseline's, so `--exclude-tests` does not apply to it.
+    #[arg(long, value_name = "PATH")]
+    baseline: Option<PathBuf>,
+    /// URL of a source line for `pr-comment` links, with `{commit}`,
+    /// `{path}`, and `{line}` placeholders.
+    #[arg(long, value_name = "URL")]
+    link_template: Option<String>,
+    /// Commit for `pr-comment` links. Defaults to the checked-out commit.
+    #[arg(long, value_name = "SHA")]
+    commit: Option<String>,
+    /// Maximum length of `pr-comment` output in bytes.
+    #[arg(long, value_name = "N", default_value_t = GITHUB_COMMENT_LIMIT)]
+    max_length: usize,
+    /// Print each gap with this template instead of the report, e.g.
+    /// `{file}:{line} {kind} in {function}`, then the summary line.
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "format")]
+    template: Option<String>,
+    /// Print the summary line with this template, e.g.
+    /// `lines={lines}% gaps={gaps}`, after the gap lines.
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "format")]
+    summary_template: Option<String>,
+    /// Read templates from a file of `gap = TEMPLATE` and
+    /// `summary = TEMPLATE` lines. `--template` and `--summary-template`
+    /// take precedence.
+    #[arg(long, value_name = "PATH", conflicts_with = "format")]
+    template_file: Option<PathBuf>,
+}
+
+/// Report formats selectable on the command line.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OutputFormat {
+    /// Compact markdown report for agents.
+    #[default]
+    Markdown,
+    /// Markdown report with each file path printed once, above its gaps
+    /// in short codes explained by a legend line.
+    Compact,
+    /// Report for reading in a terminal, with a header per file, colored
+    /// gap kinds, and summary bars.
+    Pretty,
+    /// One `path:line:col: message` line per gap, for editor quickfix
+    /// lists and problem matchers.
+    Quickfix,
+    /// Self-contained HTML page showing the source of files with gaps.
+    Html,
+    /// Markdown for a pull request comment, with a totals table and
+    /// collapsible gap lists per file.
+    PrComment,
+    /// GitLab Code Quality JSON, one issue per gap.
+    CodeQuality,
+    /// Cobertura XML line and branch coverage, for GitLab's diff view.
+    Cobertura,
+}
+
+impl OutputFormat {
+    /// Returns whether the format is a GitLab CI report, which is followed
+    /// by a coverage line on stderr for GitLab's coverage regex.
+    const fn is_gitlab(self) -> bool {
+        matches!(self, Self::CodeQuality | Self::Cobertura)
+    }
+}
+
+/// Badge metrics selectable on the command line.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum BadgeMetric {
+    /// Line coverage.
+    #[default]
+    Lines,
+    /// Region coverage.
+    Regions,
+    /// Branch coverage; needs `--branch` coverage data.
+    Branches,
+    /// Function coverage.
+    Functions,
+    /// Instantiation coverage; needs `--instantiations`.
+    Instantiations,
+}
+
+impl From<BadgeMetric> for Metric {
+    fn from(metric: BadgeMetric) -> Self {
+        match metric {
+            BadgeMetric::Lines => Self::Lines,
+            BadgeMetric::Regions => Self::Regions,
+            BadgeMetric::Branches => Self::Branches,
+            BadgeMetric::Functions => Self::Functions,
+            BadgeMetric::Instantiations => Self::Instantiations,
+        }
+    }
+}
+
+/// Color choices selectable on the command line.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    /// Always color, even when `NO_COLOR` is set.
+    Always,
+    /// Never color.
+    Never,
+}
+
+/// Where output is going, for picking the default format and colors.
+#[derive(Clone, Copy, Debug, Default)]
+struct Terminal {
+    /// Whether stdout is a terminal.
+    is_tty: bool,
+    /// Whether `NO_COLOR` is set to a non-empty value.
+    no_color: bool,
+}
+
+impl Terminal {
+    /// Inspects stdout and the environment.
+    fn detect() -> Self {
+        Self {
+            is_tty: std::io::stdout().is_terminal(),
+            no_color: std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()),
+        }
+    }
+}
+
+/// Gap ordering selectable on the command line.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum SortOrder {
+    /// Group gaps by file, in location order.
+    #[default]
+    File,
+    /// Most valuable gaps first, scored by size, kind, dead functions,
+    /// public visibility, and git churn.
+    Priority,
+}
+
+/// `cargo llvm-cov` subcommand used by watch mode.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Runner {
+    /// `cargo llvm-cov run`.
+    Run,
+    /// `cargo llvm-cov nextest`.
+    #[default]
+    Nextest,
+}
+
+impl Runner {
+    /// Returns the `cargo llvm-cov` subcommand name.
+    const fn subcommand(self) -> &'static str {
+        match self {
+            Self::Run => "run",
+            Self::Nextest => "nextest",
+        }
+    }
+}
+
+impl OutputArgs {
+    /// Converts the command-line flags into library format options.
+    ///
+    /// `churn` is only consulted for priority ordering.
+    fn to_options(&self, churn: impl FnOnce() -> BTreeMap<String, u64>) -> FormatOptions {
+        let grouped = self.format == Some(OutputFormat::Compact);
+        match self.sort {
+            SortOrder::File => FormatOptions {
+                grouped,
+                ..FormatOptions::default()
+            },
+            SortOrder::Priority => FormatOptions {
+                order: GapOrder::Priority,
+                score_context: ScoreContext { churn: churn() },
+                grouped,
+            },
+        }
+    }
+
+    /// Returns the selected format, or the default for `terminal`.
+    fn format(&self, terminal: Terminal) -> OutputFormat {
+        self.format
+            .unwrap_or(if terminal.is_tty || self.color == ColorChoice::Always {
+                OutputFormat::Pretty
+            } else {
+                OutputFormat::Markdown
+            })
+    }
+
+    /// Returns whether to color output going to `terminal`.
+    const fn color(&self, terminal: Terminal) -> bool {
+        match self.color {
+            ColorChoice::Auto => terminal.is_tty && !terminal.no_color,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+
+    /// Parses the templates, if any are given.
+    fn templates(&self) -> anyhow::Result<Option<Templates>> {
+        if self.template.is_none()
+            && self.summary_template.is_none()
+            && self.template_file.is_none()
+        {
+            return Ok(None);
+        }
+        let mut templates = match &self.template_file {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read template file {}", path.display()))?;
+                Templates::parse_file(&contents)
+                    .with_context(|| format!("invalid template file {}", path.display()))?
+            }
+            None => Templates::default(),
+        };
+        if let Some(template) = &self.template {
+            templates.gap = Some(Template::gap(template).context("invalid --template")?);
+        }
+        if let Some(template) = &self.summary_template {
+            templates.summary =
+                Some(Template::summary(template).context("invalid --summary-template")?);
+        }
+        Ok(Some(templates))
+    }
+
+    /// Formats `result` in the selected format.
+    ///
+    /// `json` is the coverage export `result` was analyzed from.
+    fn render(
+        &self,
+        json: &str,
+        result: &AnalysisResult,
+        analysis: &AnalysisArgs,
+        terminal: Terminal,
+        churn: impl FnOnce() -> BTreeMap<String, u64>,
+    ) -> anyhow::Result<String> {
+        if let Some(templates) = self.templates()? {
+            return Ok(format_templated(
+                result,
+                &self.to_options(churn),
+                &templates,
+            ));
+        }
+        Ok(match self.format(terminal) {
+            OutputFormat::Markdown | OutputFormat::Compact => {
+                llvm_cov_easy::format::format_result_with(result, &self.to_options(churn))
+            }
+            OutputFormat::Pretty => llvm_cov_easy::pretty::format_pretty(
+                result,
+                &self.to_options(churn),
+                self.color(terminal),
+            ),
+            OutputFormat::Quickfix => llvm_cov_easy::format::format_quickfix(result),
+            OutputFormat::Html => llvm_cov_easy::html::format_html(result, &mut SourceCache::new()),
+            OutputFormat::PrComment => {
+                let options = self.comment_options(git_head)?;
+                llvm_cov_easy::comment::format_pr_comment(result, &options)
+            }
+            OutputFormat::CodeQuality => llvm_cov_easy::gitlab::format_code_quality(result),
+            OutputFormat::Cobertura => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                llvm_cov_easy::gitlab::format_cobertura(
+                    &llvm_cov_easy::parse_json(json)?,
+                    &analysis.to_options(),
+                    &std::env::current_dir()?,
+                    timestamp,
+                )
+            }
+        })
+    }
+
+    /// Builds the `pr-comment` options, reading the baseline if given.
+    ///
+    /// The baseline is analyzed without options, so its totals are the
+    /// exported ones and no sources are read. `head` is only consulted when
+    /// links need a commit and none was given.
+    fn comment_options(
+        &self,
+        head: impl FnOnce() -> Option<String>,
+    ) -> anyhow::Result<CommentOptions> {
+        let baseline = match &self.baseline {
+            Some(path) => {
+                let json = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read baseline {}", path.display()))?;
+                let baseline = llvm_cov_easy::analyze_json(&json)?;
+                Some(totals::sum(&baseline.file_totals))
+            }
+            None => None,
+        };
+        let commit = match (&self.commit, &self.link_template) {
+            (Some(commit), _) => Some(commit.clone()),
+            (None, Some(_)) => head(),
+            (None, None) => None,
+        };
+        Ok(CommentOptions {
+            baseline,
+            link_template: self.link_template.clone(),
+            commit,
+            max_length: self.max_length,
+        })
+    }
+}
+
+/// Workspace options shared by every subcommand that analyzes coverage.
+#[derive(Args, Debug, Default)]
+struct WorkspaceArgs {
+    /// Add a per-crate coverage rollup.
+    #[arg(long)]
+    rollup: bool,
+    /// Add a per-crate rollup broken down by module directory.
+    #[arg(long)]
+    rollup_modules: bool,
+    /// Only list gaps in this workspace package (repeatable).
+    ///
+    /// Not `--package`, which `run`, `nextest`, and `watch` pass on to
+    /// `cargo llvm-cov` to select what to test.
+    #[arg(long = "only-package", value_name = "NAME")]
+    packages: Vec<String>,
+    /// List public functions and methods of library crates that tests
+    /// never executed. Reads the crate sources, so not with `--no-source`.
+    #[arg(long, conflicts_with = "no_source")]
+    public_api: bool,
+}
+
+impl WorkspaceArgs {
+    /// Returns whether any option needs the workspace layout.
+    const fn needs_metadata(&self) -> bool {
+        self.rollup || self.rollup_modules || !self.packages.is_empty() || self.public_api
+    }
+
+    /// Sets the analyses in `options` that need the workspace layout.
+    fn configure(&self, workspace: &Workspace, options: &mut AnalysisOptions) {
+        if self.public_api {
+            options.public_api = workspace.library_roots();
+        }
+    }
+
+    /// Adds rollups to `result` and filters it to the selected packages.
+    ///
+    /// Must run before paths are relativized.
+    fn apply(&self, workspace: &Workspace, result: &mut AnalysisResult) -> anyhow::Result<()> {
+        if let Some(unknown) = self
+            .packages
+            .iter()
+            .find(|name| workspace.package(name).is_none())
+        {
+            let known: Vec<&str> = workspace.packages.iter().map(|p| p.name.as_str()).collect();
+            anyhow::bail!(
+                "package `{unknown}` is not a workspace member (members: {})",
+                known.join(", ")
+            );
+        }
+        if self.rollup || self.rollup_modules {
+            result.rollups = workspace.rollup(&result.file_totals, self.rollup_modules);
+        }
+        if !self.packages.is_empty() {
+            workspace.retain_packages(result, &self.packages);
+        }
+        Ok(())
+    }
+}
+
+/// Reads the workspace layout from `cargo metadata`.
+///
+/// COVERAGE: This function spawns `cargo metadata`, which is tested via E2E
+/// runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn cargo_metadata() -> anyhow::Result<Workspace> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("`cargo metadata` exited with {}", output.status);
+    }
+    Ok(Workspace::from_metadata(&String::from_utf8(
+        output.stdout,
+    )?)?)
+}
+
+/// Counts recent commits touching each file below the current directory.
+///
+/// Returns an empty map when `git` is unavailable or this is not a
+/// repository, so priority ordering degrades gracefully.
+///
+/// COVERAGE: This function spawns `git`, which is tested via E2E runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn git_churn() -> BTreeMap<String, u64> {
+    let output = Command::new("git")
+        .args(["log", "--format=", "--name-only", "--relative", "-n", "200"])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_git_churn(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => BTreeMap::new(),
+    }
 }
 
 /// Splits a `+toolchain` prefix from the user args, if present.
@@ -101,49 +723,656 @@ fn build_cargo_llvm_cov_args<'a>(
 #[cfg_attr(coverage_nightly, coverage(off))]
 fn run_cargo_llvm_cov(subcommand: &str, user_args: &[String]) -> anyhow::Result<String> {
     let (cargo_args, llvm_cov_args) = build_cargo_llvm_cov_args(subcommand, user_args);
+    run_cargo(&cargo_args, &llvm_cov_args, &[])
+}
+
+/// COVERAGE: main is the thin entry point; logic is tested via the library crate.
+#[cfg_attr(coverage_nightly, coverage(off))]
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let Cargo {
+        command: CargoCommand::LlvmCovEasy { command },
+    } = Cargo::parse();
+
+    let (json, analysis, output_args, workspace_args, record) = match command {
+        Commands::Trend { runs } => return show_trend(runs),
+        Commands::Watch {
+            analysis,
+            output,
+            workspace,
+            runner,
+            args,
+        } => return watch(runner, &analysis, &output, &workspace, &args),
+        Commands::WhoCovers { location } => return who_covers(&location),
+        Commands::Lsp {
+            analysis,
+            workspace,
+        } => return serve_lsp(&analysis, &workspace),
+        Commands::Mcp {
+            analysis,
+            workspace,
+            runner,
+            args,
+        } => return serve_mcp(runner, &analysis, &workspace, &args),
+        Commands::Nextest {
+            per_test: true,
+            args,
+            ..
+        } => return run_per_test(&args),
+        Commands::Badge {
+            analysis,
+            metric,
+            label,
+            thresholds,
+            output,
+            endpoint,
+            path,
+        } => {
+            let badge = make_badge(
+                &read_input(path)?,
+                &analysis,
+                metric,
+                label.as_deref(),
+                thresholds,
+            )?;
+            std::fs::write(&output, badge.to_svg())
+                .with_context(|| format!("failed to write {}", output.display()))?;
+            if let Some(endpoint) = endpoint {
+                std::fs::write(&endpoint, badge.to_endpoint_json())
+                    .with_context(|| format!("failed to write {}", endpoint.display()))?;
+            }
+            return Ok(());
+        }
+        Commands::Analyze {
+            analysis,
+            output,
+            workspace,
+            path,
+        } => (read_input(path)?, analysis, output, workspace, false),
+        Commands::Run {
+            analysis,
+            output,
+            workspace,
+            args,
+        } => (
+            run_cargo_llvm_cov("run", &args)?,
+            analysis,
+            output,
+            workspace,
+            true,
+        ),
+        Commands::Nextest {
+            analysis,
+            output,
+            workspace,
+            args,
+            ..
+        } => (
+            run_cargo_llvm_cov("nextest", &args)?,
+            analysis,
+            output,
+            workspace,
+            true,
+        ),
+    };
+
+    let workspace = if workspace_args.needs_metadata() {
+        Some(cargo_metadata()?)
+    } else {
+        None
+    };
+    let result = analyze(&json, &analysis, &workspace_args, workspace.as_ref())?;
+    if record && let Err(err) = record_history(&result, workspace) {
+        tracing::warn!("failed to record coverage history: {err:#}");
+    }
+    let terminal = Terminal::detect();
+    print!(
+        "{}",
+        output_args.render(&json, &result, &analysis, terminal, git_churn)?
+    );
+    if output_args.format(terminal).is_gitlab() {
+        eprintln!(
+            "{}",
+            llvm_cov_easy::gitlab::format_coverage_line(&result.summary)
+        );
+    }
+
+    Ok(())
+}
+
+/// Analyzes coverage JSON and creates the badge for `metric`.
+///
+/// Empty `thresholds` select the default ones.
+fn make_badge(
+    json: &str,
+    analysis: &AnalysisArgs,
+    metric: BadgeMetric,
+    label: Option<&str>,
+    thresholds: Vec<ColorThreshold>,
+) -> anyhow::Result<Badge> {
+    let result = llvm_cov_easy::analyze_json_with_options(json, &analysis.to_options())?;
+    let thresholds = if thresholds.is_empty() {
+        ColorThreshold::defaults()
+    } else {
+        thresholds
+    };
+    Ok(Badge::new(
+        &result.summary,
+        metric.into(),
+        label,
+        &thresholds,
+    ))
+}
+
+/// Analyzes coverage JSON, applies the workspace options, and relativizes
+/// paths to the current directory.
+///
+/// `workspace` must be given when `workspace_args` needs metadata.
+fn analyze(
+    json: &str,
+    analysis: &AnalysisArgs,
+    workspace_args: &WorkspaceArgs,
+    workspace: Option<&Workspace>,
+) -> anyhow::Result<AnalysisResult> {
+    let mut options = analysis.to_options();
+    if let Some(workspace) = workspace {
+        workspace_args.configure(workspace, &mut options);
+    }
+    let mut result = llvm_cov_easy::analyze_json_with_options(json, &options)?;
+    if let Some(workspace) = workspace {
+        workspace_args.apply(workspace, &mut result)?;
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        result.relativize_paths(&cwd);
+    }
+    Ok(result)
+}
+
+/// How long watch mode waits for further changes before re-running, since
+/// editors often write a file several times in a row.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
 
+/// Runs coverage, then again after every source change, printing the full
+/// report first and the gap changes afterwards. Other formats than the
+/// agent format print the full report every time.
+///
+/// Failed runs, such as compile errors, are reported and watching goes on.
+///
+/// COVERAGE: This function watches the file system and spawns
+/// `cargo llvm-cov` in a loop, which is tested via E2E runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn watch(
+    runner: Runner,
+    analysis: &AnalysisArgs,
+    output_args: &OutputArgs,
+    workspace_args: &WorkspaceArgs,
+    args: &[String],
+) -> anyhow::Result<()> {
+    let workspace = cargo_metadata()?;
+    let target_dir = target_dir(&workspace);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&std::env::current_dir()?, RecursiveMode::Recursive)?;
+
+    let terminal = Terminal::detect();
+    let diff_only = output_args.templates()?.is_none()
+        && output_args.format(terminal) == OutputFormat::Markdown;
+    let mut previous: Option<AnalysisResult> = None;
+    loop {
+        let run = run_cargo_llvm_cov(runner.subcommand(), args).and_then(|json| {
+            analyze(&json, analysis, workspace_args, Some(&workspace)).map(|result| (json, result))
+        });
+        match run {
+            Ok((json, result)) => {
+                let output = match &previous {
+                    Some(previous) if diff_only => llvm_cov_easy::format::format_gap_diff(
+                        &result.diff_gaps(previous),
+                        &result.summary,
+                    ),
+                    _ => output_args.render(&json, &result, analysis, terminal, git_churn)?,
+                };
+                println!("{output}");
+                previous = Some(result);
+            }
+            Err(err) => tracing::error!("coverage run failed: {err:#}"),
+        }
+        wait_for_change(&rx, &target_dir)?;
+    }
+}
+
+/// Serves MCP requests from stdin until it closes.
+///
+/// Each `run_coverage` call runs `cargo llvm-cov` with the call's
+/// arguments, or with `default_args` if it gives none.
+///
+/// COVERAGE: This function reads stdin and spawns `cargo llvm-cov`; the
+/// protocol handling is tested in the library crate.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn serve_mcp(
+    runner: Runner,
+    analysis: &AnalysisArgs,
+    workspace_args: &WorkspaceArgs,
+    default_args: &[String],
+) -> anyhow::Result<()> {
+    let workspace = if workspace_args.needs_metadata() {
+        Some(cargo_metadata()?)
+    } else {
+        None
+    };
+    let mut server = llvm_cov_easy::mcp::Server::new(|args: &[String]| {
+        let args = if args.is_empty() { default_args } else { args };
+        run_cargo_llvm_cov(runner.subcommand(), args)
+            .and_then(|json| analyze(&json, analysis, workspace_args, workspace.as_ref()))
+            .map_err(|err| format!("{err:#}"))
+    });
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = server.handle_line(&line) {
+            writeln!(stdout, "{response}")?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Input to the language server loop.
+enum LspInput {
+    /// A message from the client, `None` at end of input.
+    Message(Result<Option<lsp::Message>, FrameError>),
+    /// A coverage file was written.
+    Coverage,
+}
+
+/// Serves LSP on stdio until the client exits, publishing diagnostics from
+/// the newest coverage JSON in the coverage directory.
+///
+/// COVERAGE: This function reads stdin and watches the file system; the
+/// protocol handling is tested in the library crate.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn serve_lsp(analysis: &AnalysisArgs, workspace_args: &WorkspaceArgs) -> anyhow::Result<()> {
+    let workspace = cargo_metadata()?;
+    let coverage_dir = target_dir(&workspace).join("llvm-cov-target");
+    std::fs::create_dir_all(&coverage_dir)?;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let events = tx.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if event.is_ok_and(|event| is_coverage_change(&event)) {
+            // The loop is gone once the client exits.
+            let _ = events.send(LspInput::Coverage);
+        }
+    })?;
+    watcher.watch(&coverage_dir, RecursiveMode::NonRecursive)?;
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin().lock();
+        loop {
+            let message = lsp::read_message(&mut stdin);
+            // A body that is not JSON was read whole; keep reading.
+            let done = !matches!(message, Ok(Some(_)) | Err(FrameError::Json(_)));
+            if tx.send(LspInput::Message(message)).is_err() || done {
+                break;
+            }
+        }
+    });
+
+    let mut server = lsp::Server::new(std::env::current_dir()?);
+    let load = |server: &mut lsp::Server| match load_coverage(
+        &coverage_dir,
+        analysis,
+        workspace_args,
+        &workspace,
+    ) {
+        Ok(Some(result)) => server.set_result(result),
+        Ok(None) => Vec::new(),
+        Err(err) => vec![lsp::log_warning(&format!(
+            "failed to load coverage: {err:#}"
+        ))],
+    };
+    let mut stdout = std::io::stdout().lock();
+    let mut outgoing = load(&mut server);
+    loop {
+        for message in outgoing {
+            lsp::write_message(&mut stdout, &message)?;
+        }
+        if server.exited() {
+            return Ok(());
+        }
+        outgoing = match rx.recv()? {
+            LspInput::Message(Ok(Some(message))) => server.handle(&message),
+            LspInput::Message(Ok(None)) => return Ok(()),
+            LspInput::Message(Err(FrameError::Json(err))) => vec![lsp::parse_error(&err)],
+            LspInput::Message(Err(err)) => return Err(err.into()),
+            LspInput::Coverage => load(&mut server),
+        };
+    }
+}
+
+/// Analyzes the most recently modified `.json` file in `dir`, if any.
+///
+/// COVERAGE: This function reads the file system, which is tested via E2E
+/// runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn load_coverage(
+    dir: &Path,
+    analysis: &AnalysisArgs,
+    workspace_args: &WorkspaceArgs,
+    workspace: &Workspace,
+) -> anyhow::Result<Option<AnalysisResult>> {
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            let modified = std::fs::metadata(&path)?.modified()?;
+            if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                newest = Some((modified, path));
+            }
+        }
+    }
+    let Some((_, path)) = newest else {
+        return Ok(None);
+    };
+    let json = std::fs::read_to_string(&path)?;
+    analyze(&json, analysis, workspace_args, Some(workspace)).map(Some)
+}
+
+/// Returns whether `event` created or changed a `.json` file.
+fn is_coverage_change(event: &Event) -> bool {
+    let changes_content = match event.kind {
+        EventKind::Create(_) => true,
+        EventKind::Modify(kind) => !matches!(kind, ModifyKind::Metadata(_)),
+        _ => false,
+    };
+    changes_content
+        && event
+            .paths
+            .iter()
+            .any(|path| path.extension().is_some_and(|e| e == "json"))
+}
+
+/// Blocks until a Rust source file outside `target_dir` changes, then
+/// until changes settle.
+///
+/// COVERAGE: This function blocks on file system events, which is tested
+/// via E2E runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn wait_for_change(rx: &Receiver<notify::Result<Event>>, target_dir: &Path) -> anyhow::Result<()> {
+    while !is_source_change(&rx.recv()??, target_dir) {}
+    while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+    Ok(())
+}
+
+/// Returns whether `event` created, changed, or removed a Rust source file
+/// outside `target_dir`.
+fn is_source_change(event: &Event, target_dir: &Path) -> bool {
+    let changes_content = match event.kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(kind) => !matches!(kind, ModifyKind::Metadata(_)),
+        _ => false,
+    };
+    changes_content
+        && event.paths.iter().any(|path| {
+            path.extension().is_some_and(|e| e == "rs") && !path.starts_with(target_dir)
+        })
+}
+
+/// File name of the per-test coverage index.
+const PER_TEST_INDEX: &str = "per-test.json";
+
+/// File name of the coverage history.
+const HISTORY: &str = "history.jsonl";
+
+/// Returns the workspace's target directory.
+fn target_dir(workspace: &Workspace) -> PathBuf {
+    workspace
+        .target_directory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("target"))
+}
+
+/// Returns the path of a file `llvm-cov-easy` keeps under the workspace's
+/// target directory.
+fn data_path(workspace: &Workspace, name: &str) -> PathBuf {
+    target_dir(workspace).join("llvm-cov-easy").join(name)
+}
+
+/// Returns the abbreviated hash of the checked-out commit, if this is a git
+/// repository.
+///
+/// COVERAGE: This function spawns `git`, which is tested via E2E runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn git_head() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Appends the coverage of `result` to the workspace's history.
+///
+/// COVERAGE: This function writes to the target directory and spawns `git`
+/// and `cargo metadata`, which are tested via E2E runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn record_history(result: &AnalysisResult, workspace: Option<Workspace>) -> anyhow::Result<()> {
+    let workspace = match workspace {
+        Some(workspace) => workspace,
+        None => cargo_metadata()?,
+    };
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let entry = HistoryEntry::new(result, git_head(), timestamp);
+    let path = data_path(&workspace, HISTORY);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", entry.to_json_line()?)?;
+    Ok(())
+}
+
+/// Prints coverage over the last `runs` recorded runs.
+///
+/// COVERAGE: This function reads the history from disk; formatting is
+/// tested in the library crate.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn show_trend(runs: usize) -> anyhow::Result<()> {
+    let path = data_path(&cargo_metadata()?, HISTORY);
+    let Ok(jsonl) = std::fs::read_to_string(&path) else {
+        anyhow::bail!(
+            "no coverage history at {}; run `cargo llvm-cov-easy run` or `nextest` first",
+            path.display()
+        );
+    };
+    let entries = parse_history(&jsonl)?;
+    print!("{}", llvm_cov_easy::format::format_trend(&entries, runs));
+    Ok(())
+}
+
+/// Lists the tests covering `location` (`file:line`), one per line.
+fn format_who_covers(index: &TestIndex, location: &str) -> anyhow::Result<String> {
+    let Some((filename, line)) = location
+        .rsplit_once(':')
+        .and_then(|(f, l)| Some((f, l.parse::<u64>().ok()?)))
+    else {
+        anyhow::bail!("invalid location `{location}`, expected `file:line`");
+    };
+    let tests = index.who_covers(filename, line);
+    if tests.is_empty() {
+        return Ok(format!("No tests cover {location}\n"));
+    }
+    Ok(tests.iter().map(|t| format!("{t}\n")).collect())
+}
+
+/// Lists the tests `cargo nextest run` would run with `user_args`.
+///
+/// COVERAGE: This function spawns `cargo nextest list`, which is tested via
+/// E2E runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn list_nextest_tests(user_args: &[String]) -> anyhow::Result<Vec<TestId>> {
+    let (cargo_args, remaining) = split_toolchain(user_args);
     let output = Command::new(cargo_args[0])
         .args(&cargo_args[1..])
-        .args(&llvm_cov_args)
-        .stdout(Stdio::piped())
+        .args(["nextest", "list", "--message-format", "json"])
+        .args(remaining)
         .stderr(Stdio::inherit())
         .output()?;
+    if !output.status.success() {
+        anyhow::bail!("`cargo nextest list` exited with {}", output.status);
+    }
+    Ok(parse_nextest_list(&String::from_utf8(output.stdout)?)?)
+}
+
+/// Parses the `KEY=value` lines `cargo llvm-cov show-env` prints, with
+/// values quoted for a POSIX shell.
+fn parse_show_env(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim_start_matches("export ").split_once('=')?;
+            Some((key.to_string(), unquote_shell(value)))
+        })
+        .collect()
+}
 
+/// Removes POSIX shell quoting: single quotes, double quotes, and
+/// backslash escapes.
+fn unquote_shell(value: &str) -> String {
+    let mut unquoted = String::new();
+    let mut quote = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'') => quote = Some('\''),
+            (None, '"') => quote = Some('"'),
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => unquoted.extend(chars.next()),
+            _ => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Runs `cargo` with the toolchain in `cargo_args`, `args`, and `env` set,
+/// and returns its stdout.
+///
+/// Stderr is inherited so users see compilation and test progress.
+///
+/// COVERAGE: This function spawns an external process, which is tested via
+/// E2E runs.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn run_cargo(
+    cargo_args: &[&str],
+    args: &[&str],
+    env: &[(String, String)],
+) -> anyhow::Result<String> {
+    let output = Command::new(cargo_args[0])
+        .args(&cargo_args[1..])
+        .args(args)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .output()?;
     if !output.status.success() {
         anyhow::bail!(
             "{} {} exited with status {}",
             cargo_args.join(" "),
-            llvm_cov_args.join(" "),
+            args.join(" "),
             output.status
         );
     }
-
     Ok(String::from_utf8(output.stdout)?)
 }
 
-/// COVERAGE: main is the thin entry point; logic is tested via the library crate.
+/// Runs each test alone with its own `LLVM_PROFILE_FILE`, indexes the
+/// lines it covers, and saves the index under the target directory.
+///
+/// Tests are built once with the instrumentation environment from
+/// `cargo llvm-cov show-env`. Each test then runs under `cargo nextest run`
+/// writing profiles named after it, `cargo llvm-cov report` exports them,
+/// and they are removed before the next test.
+///
+/// COVERAGE: This function spawns `cargo nextest` and `cargo llvm-cov` once
+/// per test, which is tested via E2E runs.
 #[cfg_attr(coverage_nightly, coverage(off))]
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
-
-    let Cargo {
-        command: CargoCommand::LlvmCovEasy { command },
-    } = Cargo::parse();
+fn run_per_test(user_args: &[String]) -> anyhow::Result<()> {
+    let workspace = cargo_metadata()?;
+    let tests = list_nextest_tests(user_args)?;
+    let (cargo_args, nextest_args) = split_toolchain(user_args);
+    let mut env = parse_show_env(&run_cargo(&cargo_args, &["llvm-cov", "show-env"], &[])?);
+    let profile_dir = env
+        .iter()
+        .find(|(key, _)| key == "LLVM_PROFILE_FILE")
+        .and_then(|(_, value)| Path::new(value).parent())
+        .map(Path::to_path_buf)
+        .context("`cargo llvm-cov show-env` set no LLVM_PROFILE_FILE")?;
+    env.retain(|(key, _)| key != "LLVM_PROFILE_FILE");
+    run_cargo(&cargo_args, &["llvm-cov", "clean", "--workspace"], &env)?;
 
-    let json = match command {
-        Commands::Analyze { path } => read_input(path)?,
-        Commands::Run { args } => run_cargo_llvm_cov("run", &args)?,
-        Commands::Nextest { args } => run_cargo_llvm_cov("nextest", &args)?,
-    };
-
-    let mut result = llvm_cov_easy::analyze_json(&json)?;
-    if let Ok(cwd) = std::env::current_dir() {
-        result.relativize_paths(&cwd);
+    let mut index = TestIndex::default();
+    for (i, test) in tests.iter().enumerate() {
+        let prefix = format!("llvm-cov-easy-test-{i}-");
+        let mut test_env = env.clone();
+        test_env.push((
+            "LLVM_PROFILE_FILE".to_string(),
+            profile_dir
+                .join(format!("{prefix}%p-%m.profraw"))
+                .to_string_lossy()
+                .into_owned(),
+        ));
+        let run_args = test.run_args(nextest_args);
+        let mut args = vec!["nextest", "run"];
+        args.extend(run_args.iter().map(String::as_str));
+        run_cargo(&cargo_args, &args, &test_env)?;
+        let json = run_cargo(&cargo_args, &["llvm-cov", "report", "--json"], &env)?;
+        index.add(&test.to_string(), &llvm_cov_easy::parse_json(&json)?);
+        for entry in std::fs::read_dir(&profile_dir)? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+            {
+                std::fs::remove_file(path)?;
+            }
+        }
     }
-    let output = llvm_cov_easy::format::format_result(&result);
-    print!("{output}");
+    let path = data_path(&workspace, PER_TEST_INDEX);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, index.to_json()?)?;
+    print!("{}", llvm_cov_easy::format::format_test_index(&index));
+    Ok(())
+}
 
+/// Prints the tests covering `location` from the saved per-test index.
+///
+/// COVERAGE: This function reads the index from disk; the lookup is tested
+/// via `format_who_covers`.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn who_covers(location: &str) -> anyhow::Result<()> {
+    let path = data_path(&cargo_metadata()?, PER_TEST_INDEX);
+    let Ok(json) = std::fs::read_to_string(&path) else {
+        anyhow::bail!(
+            "no per-test index at {}; run `cargo llvm-cov-easy nextest --per-test` first",
+            path.display()
+        );
+    };
+    print!(
+        "{}",
+        format_who_covers(&TestIndex::from_json(&json)?, location)?
+    );
     Ok(())
 }
 
@@ -166,6 +1395,828 @@ fn read_input(path: Option<PathBuf>) -> anyhow::Result<String> {
 mod tests {
     use super::*;
 
+    fn parse(args: &[&str]) -> Commands {
+        let Cargo {
+            command: CargoCommand::LlvmCovEasy { command },
+        } = Cargo::try_parse_from(args).unwrap();
+        command
+    }
+
+    #[test]
+    fn nextest_flags_before_forwarded_args() {
+        let command = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "nextest",
+            "--instantiations",
+            "--weak-threshold",
+            "3",
+            "--hot-lines=5",
+            "--exclude-tests",
+            "--audit-exclusions",
+            "+nightly",
+            "--workspace",
+        ]);
+        let Commands::Nextest { analysis, args, .. } = command else {
+            panic!("expected nextest subcommand");
+        };
+        let options = analysis.to_options();
+        assert!(options.instantiations);
+        assert_eq!(options.weak_threshold, Some(3));
+        assert_eq!(options.hot_lines, Some(5));
+        assert!(options.exclude_tests);
+        assert!(options.audit_exclusions);
+        assert_eq!(args, vec!["+nightly", "--workspace"]);
+    }
+
+    #[test]
+    fn nextest_flags_after_forwarded_args_are_forwarded() {
+        let Commands::Nextest { analysis, args, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "nextest",
+            "--workspace",
+            "--instantiations",
+        ]) else {
+            panic!("expected nextest subcommand");
+        };
+        assert!(!analysis.to_options().instantiations);
+        assert_eq!(args, vec!["--workspace", "--instantiations"]);
+    }
+
+    #[test]
+    fn analyze_defaults_to_no_optional_analyses() {
+        let Commands::Analyze { analysis, path, .. } =
+            parse(&["cargo", "llvm-cov-easy", "analyze", "cov.json"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        let options = analysis.to_options();
+        assert!(!options.instantiations);
+        assert!(options.read_sources);
+        assert!(!options.exclude_tests);
+        assert_eq!(path, Some(PathBuf::from("cov.json")));
+    }
+
+    #[test]
+    fn sort_defaults_to_file_order_without_churn() {
+        let Commands::Analyze { output, .. } = parse(&["cargo", "llvm-cov-easy", "analyze"]) else {
+            panic!("expected analyze subcommand");
+        };
+        let options = output.to_options(|| panic!("churn is only needed for priority order"));
+        assert_eq!(options.order, GapOrder::File);
+        assert!(!options.grouped);
+    }
+
+    #[test]
+    fn format_compact_groups_gaps() {
+        let Commands::Analyze {
+            analysis, output, ..
+        } = parse(&["cargo", "llvm-cov-easy", "analyze", "--format", "compact"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        assert!(output.to_options(BTreeMap::new).grouped);
+        let result = llvm_cov_easy::analyze_json(COVERAGE).unwrap();
+        let rendered = output
+            .render(COVERAGE, &result, &analysis, Terminal::default(), || {
+                panic!("churn is only needed for priority order")
+            })
+            .unwrap();
+        assert!(rendered.contains("\nLegend: "), "{rendered}");
+    }
+
+    #[test]
+    fn sort_priority_collects_churn() {
+        let Commands::Run { output, args, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "run",
+            "--sort",
+            "priority",
+            "--release",
+        ]) else {
+            panic!("expected run subcommand");
+        };
+        let options = output.to_options(|| BTreeMap::from([("./src/lib.rs".to_string(), 3)]));
+        assert_eq!(options.order, GapOrder::Priority);
+        assert_eq!(options.score_context.churn["./src/lib.rs"], 3);
+        assert_eq!(args, vec!["--release"]);
+    }
+
+    #[test]
+    fn format_quickfix_renders_gap_lines() {
+        let Commands::Analyze {
+            analysis, output, ..
+        } = parse(&["cargo", "llvm-cov-easy", "analyze", "--format", "quickfix"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        assert_eq!(output.format, Some(OutputFormat::Quickfix));
+        let result = llvm_cov_easy::analyze_json(COVERAGE).unwrap();
+        let rendered = output
+            .render(COVERAGE, &result, &analysis, Terminal::default(), || {
+                panic!("churn is only needed for priority order")
+            })
+            .unwrap();
+        assert!(!rendered.is_empty());
+        assert!(!rendered.contains("# Code Coverage"));
+        assert!(
+            rendered.lines().all(|line| line.contains(".rs:")),
+            "{rendered}"
+        );
+    }
+
+    #[test]
+    fn format_defaults_to_pretty_on_terminals() {
+        let tty = Terminal {
+            is_tty: true,
+            no_color: false,
+        };
+        let no_color_tty = Terminal {
+            no_color: true,
+            ..tty
+        };
+        let pipe = Terminal::default();
+
+        let Commands::Analyze { output, .. } = parse(&["cargo", "llvm-cov-easy", "analyze"]) else {
+            panic!("expected analyze subcommand");
+        };
+        assert_eq!(output.color, ColorChoice::Auto);
+        assert_eq!(output.format(tty), OutputFormat::Pretty);
+        assert_eq!(output.format(pipe), OutputFormat::Markdown);
+        assert!(output.color(tty));
+        assert!(!output.color(no_color_tty));
+        assert!(!output.color(pipe));
+
+        let Commands::Analyze { output, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--report-color",
+            "always",
+        ]) else {
+            panic!("expected analyze subcommand");
+        };
+        assert_eq!(output.format(pipe), OutputFormat::Pretty);
+        assert!(output.color(no_color_tty));
+        assert!(output.color(pipe));
+
+        let Commands::Analyze { output, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--report-color",
+            "never",
+            "--format",
+            "markdown",
+        ]) else {
+            panic!("expected analyze subcommand");
+        };
+        assert_eq!(output.format(tty), OutputFormat::Markdown);
+        assert!(!output.color(tty));
+    }
+
+    #[test]
+    fn format_pretty_renders_file_headers() {
+        let Commands::Analyze {
+            analysis, output, ..
+        } = parse(&["cargo", "llvm-cov-easy", "analyze", "--format", "pretty"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        let result = llvm_cov_easy::analyze_json(COVERAGE).unwrap();
+        let rendered = output
+            .render(COVERAGE, &result, &analysis, Terminal::default(), || {
+                panic!("churn is only needed for priority order")
+            })
+            .unwrap();
+        assert!(rendered.starts_with("Code Coverage\n"));
+        assert!(!rendered.contains('\x1b'));
+    }
+
+    #[test]
+    fn templates_replace_report() {
+        let Commands::Analyze {
+            analysis, output, ..
+        } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--template",
+            "{file}|{line}|{code}",
+            "--summary-template",
+            "lines={lines}",
+        ])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        let result = llvm_cov_easy::analyze_json(COVERAGE).unwrap();
+        let rendered = output
+            .render(COVERAGE, &result, &analysis, Terminal::default(), || {
+                panic!("churn is only needed for priority order")
+            })
+            .unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+        let (summary, gaps) = lines.split_last().unwrap();
+        assert!(summary.starts_with("lines="), "{rendered}");
+        assert!(!gaps.is_empty());
+        assert!(
+            gaps.iter().all(|line| line.split('|').count() == 3),
+            "{rendered}"
+        );
+
+        let Commands::Analyze { output, .. } =
+            parse(&["cargo", "llvm-cov-easy", "analyze", "--template", "{nope}"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        let err = output.templates().unwrap_err();
+        assert!(format!("{err:#}").starts_with("invalid --template: unknown placeholder `{nope}`"));
+
+        let Commands::Analyze { output, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--template-file",
+            "/nonexistent/templates",
+        ]) else {
+            panic!("expected analyze subcommand");
+        };
+        let err = output.templates().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("failed to read template file /nonexistent/templates")
+        );
+
+        let Commands::Analyze { output, .. } = parse(&["cargo", "llvm-cov-easy", "analyze"]) else {
+            panic!("expected analyze subcommand");
+        };
+        assert!(output.templates().unwrap().is_none());
+        assert!(
+            Cargo::try_parse_from([
+                "cargo",
+                "llvm-cov-easy",
+                "analyze",
+                "--format",
+                "html",
+                "--template",
+                "{file}",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn format_html_renders_page() {
+        let Commands::Analyze {
+            analysis, output, ..
+        } = parse(&["cargo", "llvm-cov-easy", "analyze", "--format", "html"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        let result = llvm_cov_easy::analyze_json(COVERAGE).unwrap();
+        let rendered = output
+            .render(COVERAGE, &result, &analysis, Terminal::default(), || {
+                panic!("churn is only needed for priority order")
+            })
+            .unwrap();
+        assert!(rendered.starts_with("<!DOCTYPE html>"));
+    }
+
+    #[test]
+    fn gitlab_formats_render_reports() {
+        let result = llvm_cov_easy::analyze_json(COVERAGE).unwrap();
+        for (format, prefix) in [("code-quality", "["), ("cobertura", "<?xml")] {
+            let Commands::Analyze {
+                analysis, output, ..
+            } = parse(&["cargo", "llvm-cov-easy", "analyze", "--format", format])
+            else {
+                panic!("expected analyze subcommand");
+            };
+            assert!(output.format(Terminal::default()).is_gitlab());
+            let rendered = output
+                .render(COVERAGE, &result, &analysis, Terminal::default(), || {
+                    panic!("churn is only needed for priority order")
+                })
+                .unwrap();
+            assert!(rendered.starts_with(prefix), "{rendered}");
+        }
+        assert!(!OutputFormat::Markdown.is_gitlab());
+    }
+
+    #[test]
+    fn pr_comment_options() {
+        let Commands::Analyze { output, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--format",
+            "pr-comment",
+            "--link-template",
+            "https://example.com/{commit}/{path}#L{line}",
+            "--max-length",
+            "1000",
+        ]) else {
+            panic!("expected analyze subcommand");
+        };
+        assert_eq!(output.format, Some(OutputFormat::PrComment));
+        let options = output
+            .comment_options(|| Some("abc123".to_string()))
+            .unwrap();
+        assert_eq!(options.commit.as_deref(), Some("abc123"));
+        assert_eq!(options.max_length, 1000);
+        assert!(options.baseline.is_none());
+
+        let Commands::Analyze { output, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--commit",
+            "def456",
+            "--baseline",
+            "/nonexistent/base.json",
+        ]) else {
+            panic!("expected analyze subcommand");
+        };
+        assert_eq!(output.max_length, GITHUB_COMMENT_LIMIT);
+        let err = output
+            .comment_options(|| panic!("commit was given"))
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("failed to read baseline /nonexistent/base.json")
+        );
+        let output = OutputArgs {
+            commit: Some("def456".to_string()),
+            ..OutputArgs::default()
+        };
+        let options = output
+            .comment_options(|| panic!("commit was given"))
+            .unwrap();
+        assert_eq!(options.commit.as_deref(), Some("def456"));
+
+        // The baseline keeps its test code, whose sources may differ.
+        let baseline = std::env::temp_dir().join(format!(
+            "llvm-cov-easy-{}-baseline.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &baseline,
+            include_str!("../../lib/tests/fixtures/with-branches.json")
+                .replace("\"src/lib.rs\"", "\"tests/it.rs\""),
+        )
+        .unwrap();
+        let Commands::Analyze { output, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--exclude-tests",
+            "--baseline",
+            baseline.to_str().unwrap(),
+        ]) else {
+            panic!("expected analyze subcommand");
+        };
+        let options = output.comment_options(|| None);
+        std::fs::remove_file(&baseline).unwrap();
+        let lines = options.unwrap().baseline.unwrap().lines;
+        assert_eq!((lines.covered, lines.count), (10, 12));
+    }
+
+    const METADATA: &str = r#"{
+        "packages": [
+            {"id": "a", "name": "a", "manifest_path": "/ws/a/Cargo.toml", "targets": [
+                {"name": "a-core", "kind": ["lib"], "src_path": "/ws/a/src/lib.rs"}
+            ]},
+            {"id": "b", "name": "b", "manifest_path": "/ws/b/Cargo.toml", "targets": []}
+        ],
+        "workspace_members": ["a", "b"]
+    }"#;
+
+    const COVERAGE: &str = r#"{
+        "type": "llvm.coverage.json.export",
+        "version": "2.0.1",
+        "data": [{
+            "files": [
+                {"filename": "/ws/a/src/lib.rs", "segments": [[1, 1, 0, true, true, false], [2, 1, 0, false, false, false]],
+                 "summary": {"lines": {"count": 1, "covered": 0, "percent": 0.0}}},
+                {"filename": "/ws/b/src/lib.rs", "segments": [[1, 1, 0, true, true, false], [2, 1, 0, false, false, false]],
+                 "summary": {"lines": {"count": 1, "covered": 0, "percent": 0.0}}}
+            ],
+            "totals": {"lines": {"count": 2, "covered": 0, "percent": 0.0}}
+        }]
+    }"#;
+
+    fn apply(args: &[&str]) -> anyhow::Result<AnalysisResult> {
+        let mut argv = vec!["cargo", "llvm-cov-easy", "analyze"];
+        argv.extend(args);
+        let Commands::Analyze { workspace, .. } = parse(&argv) else {
+            panic!("expected analyze subcommand");
+        };
+        let mut result = llvm_cov_easy::analyze_json(COVERAGE).unwrap();
+        workspace.apply(&Workspace::from_metadata(METADATA).unwrap(), &mut result)?;
+        Ok(result)
+    }
+
+    #[test]
+    fn workspace_flags_default_to_no_metadata() {
+        let Commands::Analyze { workspace, .. } = parse(&["cargo", "llvm-cov-easy", "analyze"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        assert!(!workspace.needs_metadata());
+        assert_eq!(apply(&[]).unwrap().files.len(), 2);
+    }
+
+    #[test]
+    fn package_filter_and_rollup() {
+        let result = apply(&["--rollup", "--only-package", "b"]).unwrap();
+        let files: Vec<&str> = result.files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(files, vec!["/ws/b/src/lib.rs"]);
+        let rollups: Vec<&str> = result.rollups.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(rollups, vec!["b"]);
+    }
+
+    #[test]
+    fn rollup_modules() {
+        let result = apply(&["--rollup-modules"]).unwrap();
+        let rollups: Vec<&str> = result.rollups.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(rollups, vec!["a", "a/src", "b", "b/src"]);
+    }
+
+    #[test]
+    fn public_api_uses_library_roots() {
+        let Commands::Analyze { workspace, .. } =
+            parse(&["cargo", "llvm-cov-easy", "analyze", "--public-api"])
+        else {
+            panic!("expected analyze subcommand");
+        };
+        assert!(workspace.needs_metadata());
+        let mut options = AnalysisOptions::default();
+        workspace.configure(&Workspace::from_metadata(METADATA).unwrap(), &mut options);
+        let roots: Vec<&str> = options.public_api.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(roots, vec!["a_core"]);
+    }
+
+    #[test]
+    fn per_test_flag_and_who_covers() {
+        let command = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "nextest",
+            "--per-test",
+            "--workspace",
+        ]);
+        let Commands::Nextest { per_test, args, .. } = command else {
+            panic!("expected nextest subcommand");
+        };
+        assert!(per_test);
+        assert_eq!(args, vec!["--workspace"]);
+
+        let command = parse(&["cargo", "llvm-cov-easy", "who-covers", "src/lib.rs:2"]);
+        let Commands::WhoCovers { location } = command else {
+            panic!("expected who-covers subcommand");
+        };
+        assert_eq!(location, "src/lib.rs:2");
+    }
+
+    #[test]
+    fn per_test_rejects_report_options() {
+        for flag in [
+            "--instantiations",
+            "--format=compact",
+            "--only-package=a",
+            "--rollup",
+        ] {
+            let error =
+                Cargo::try_parse_from(["cargo", "llvm-cov-easy", "nextest", "--per-test", flag])
+                    .err()
+                    .unwrap_or_else(|| panic!("{flag} was accepted with --per-test"));
+            assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn source_options_reject_no_source() {
+        let error = Cargo::try_parse_from([
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--no-source",
+            "--audit-exclusions",
+        ])
+        .err()
+        .expect("--audit-exclusions was accepted with --no-source");
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+        let error = Cargo::try_parse_from([
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--no-source",
+            "--public-api",
+        ])
+        .err()
+        .expect("--public-api was accepted with --no-source");
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn parse_show_env_unquotes_values() {
+        let env = parse_show_env(
+            "RUSTFLAGS='-C instrument-coverage --cfg=coverage'\n\
+             LLVM_PROFILE_FILE=/ws/target/ws-%p-%m.profraw\n\
+             export CARGO_LLVM_COV=1\n\
+             QUOTED='it'\\''s'\n\
+             DOUBLE=\"a \\\"b\\\"\"\n\
+             not an assignment\n",
+        );
+        let env: Vec<(&str, &str)> = env.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(
+            env,
+            vec![
+                ("RUSTFLAGS", "-C instrument-coverage --cfg=coverage"),
+                ("LLVM_PROFILE_FILE", "/ws/target/ws-%p-%m.profraw"),
+                ("CARGO_LLVM_COV", "1"),
+                ("QUOTED", "it's"),
+                ("DOUBLE", "a \"b\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn format_who_covers_lists_tests() {
+        let mut index = TestIndex::default();
+        // Line 1 of `a` ran; line 1 of `b` did not.
+        let json = COVERAGE.replacen("[[1, 1, 0,", "[[1, 1, 3,", 1);
+        index.add("a tests::t", &llvm_cov_easy::parse_json(&json).unwrap());
+        assert_eq!(
+            format_who_covers(&index, "a/src/lib.rs:1").unwrap(),
+            "a tests::t\n"
+        );
+        assert_eq!(
+            format_who_covers(&index, "b/src/lib.rs:1").unwrap(),
+            "No tests cover b/src/lib.rs:1\n"
+        );
+        assert_eq!(
+            format_who_covers(&index, "src/lib.rs")
+                .unwrap_err()
+                .to_string(),
+            "invalid location `src/lib.rs`, expected `file:line`"
+        );
+    }
+
+    #[test]
+    fn data_path_defaults_to_target() {
+        let workspace = Workspace::from_metadata(METADATA).unwrap();
+        assert_eq!(
+            data_path(&workspace, PER_TEST_INDEX),
+            PathBuf::from("target/llvm-cov-easy/per-test.json")
+        );
+    }
+
+    #[test]
+    fn mcp_defaults() {
+        let Commands::Mcp { runner, args, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "mcp",
+            "--exclude-tests",
+            "--workspace",
+        ]) else {
+            panic!("expected mcp subcommand");
+        };
+        assert_eq!(runner.subcommand(), "nextest");
+        assert_eq!(args, vec!["--workspace"]);
+    }
+
+    #[test]
+    fn badge_options() {
+        let Commands::Badge {
+            analysis,
+            metric,
+            label,
+            thresholds,
+            output,
+            endpoint,
+            path,
+        } = parse(&["cargo", "llvm-cov-easy", "badge", "cov.json"])
+        else {
+            panic!("expected badge subcommand");
+        };
+        assert_eq!(metric, BadgeMetric::Lines);
+        assert_eq!(output, PathBuf::from("coverage.svg"));
+        assert_eq!(endpoint, None);
+        assert_eq!(path, Some(PathBuf::from("cov.json")));
+        let badge = make_badge(COVERAGE, &analysis, metric, label.as_deref(), thresholds).unwrap();
+        assert_eq!(badge.label, "coverage");
+        assert!(badge.message.ends_with('%'));
+
+        let Commands::Badge {
+            analysis,
+            metric,
+            label,
+            thresholds,
+            output,
+            endpoint,
+            ..
+        } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "badge",
+            "--metric",
+            "functions",
+            "--label",
+            "fns",
+            "--threshold",
+            "0=blue",
+            "-o",
+            "badge.svg",
+            "--endpoint",
+            "badge.json",
+        ])
+        else {
+            panic!("expected badge subcommand");
+        };
+        assert_eq!(output, PathBuf::from("badge.svg"));
+        assert_eq!(endpoint, Some(PathBuf::from("badge.json")));
+        let badge = make_badge(COVERAGE, &analysis, metric, label.as_deref(), thresholds).unwrap();
+        assert_eq!(badge.label, "fns");
+        assert_eq!(badge.color, "blue");
+
+        assert!(
+            Cargo::try_parse_from(["cargo", "llvm-cov-easy", "badge", "--threshold", "90"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn watch_runner() {
+        let Commands::Watch { runner, args, .. } =
+            parse(&["cargo", "llvm-cov-easy", "watch", "--workspace"])
+        else {
+            panic!("expected watch subcommand");
+        };
+        assert_eq!(runner.subcommand(), "nextest");
+        assert_eq!(args, vec!["--workspace"]);
+        let Commands::Watch { runner, .. } =
+            parse(&["cargo", "llvm-cov-easy", "watch", "--runner", "run"])
+        else {
+            panic!("expected watch subcommand");
+        };
+        assert_eq!(runner.subcommand(), "run");
+    }
+
+    #[test]
+    fn source_changes() {
+        use notify::event::{AccessKind, CreateKind, DataChange, MetadataKind};
+
+        let target = Path::new("/ws/target");
+        let event = |kind, path: &str| Event::new(kind).add_path(PathBuf::from(path));
+        let write = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        assert!(is_source_change(&event(write, "/ws/src/lib.rs"), target));
+        assert!(is_source_change(
+            &event(EventKind::Create(CreateKind::File), "/ws/src/new.rs"),
+            target
+        ));
+        assert!(!is_source_change(&event(write, "/ws/README.md"), target));
+        assert!(!is_source_change(
+            &event(write, "/ws/target/debug/build/out.rs"),
+            target
+        ));
+        assert!(!is_source_change(
+            &event(EventKind::Access(AccessKind::Any), "/ws/src/lib.rs"),
+            target
+        ));
+        assert!(!is_source_change(
+            &event(
+                EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)),
+                "/ws/src/lib.rs"
+            ),
+            target
+        ));
+    }
+
+    #[test]
+    fn coverage_changes() {
+        use notify::event::{CreateKind, DataChange, MetadataKind, RemoveKind};
+
+        let event = |kind, path: &str| Event::new(kind).add_path(PathBuf::from(path));
+        let write = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let dir = "/ws/target/llvm-cov-target";
+        assert!(is_coverage_change(&event(
+            write,
+            &format!("{dir}/coverage.json")
+        )));
+        assert!(is_coverage_change(&event(
+            EventKind::Create(CreateKind::File),
+            &format!("{dir}/coverage.json")
+        )));
+        assert!(!is_coverage_change(&event(
+            write,
+            &format!("{dir}/a.profraw")
+        )));
+        assert!(!is_coverage_change(&event(
+            EventKind::Remove(RemoveKind::File),
+            &format!("{dir}/coverage.json")
+        )));
+        assert!(!is_coverage_change(&event(
+            EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)),
+            &format!("{dir}/coverage.json")
+        )));
+    }
+
+    #[test]
+    fn trend_runs() {
+        let Commands::Trend { runs } = parse(&["cargo", "llvm-cov-easy", "trend"]) else {
+            panic!("expected trend subcommand");
+        };
+        assert_eq!(runs, 10);
+        let Commands::Trend { runs } = parse(&["cargo", "llvm-cov-easy", "trend", "--runs", "3"])
+        else {
+            panic!("expected trend subcommand");
+        };
+        assert_eq!(runs, 3);
+    }
+
+    #[test]
+    fn only_package_filters_a_real_workspace() {
+        let Commands::Analyze { workspace, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "analyze",
+            "--only-package",
+            "member1",
+        ]) else {
+            panic!("expected analyze subcommand");
+        };
+        let metadata = include_str!("../../lib/tests/fixtures/real1-metadata.json");
+        let mut result =
+            llvm_cov_easy::analyze_json(include_str!("../../lib/tests/fixtures/two-packages.json"))
+                .unwrap();
+        let before: Vec<&str> = result.files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(before, vec!["member1/src/lib.rs", "src/lib.rs"]);
+        workspace
+            .apply(&Workspace::from_metadata(metadata).unwrap(), &mut result)
+            .unwrap();
+        let after: Vec<&str> = result.files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(after, vec!["member1/src/lib.rs"]);
+    }
+
+    #[test]
+    fn package_is_forwarded_to_cargo_llvm_cov() {
+        for subcommand in ["run", "nextest", "watch"] {
+            let command = parse(&[
+                "cargo",
+                "llvm-cov-easy",
+                subcommand,
+                "-p",
+                "a",
+                "--package",
+                "b",
+            ]);
+            let (Commands::Run {
+                args, workspace, ..
+            }
+            | Commands::Nextest {
+                args, workspace, ..
+            }
+            | Commands::Watch {
+                args, workspace, ..
+            }) = command
+            else {
+                panic!("expected {subcommand} subcommand");
+            };
+            assert_eq!(args, vec!["-p", "a", "--package", "b"]);
+            assert!(workspace.packages.is_empty());
+        }
+    }
+
+    #[test]
+    fn color_is_forwarded_to_cargo_llvm_cov() {
+        let Commands::Run { args, output, .. } = parse(&[
+            "cargo",
+            "llvm-cov-easy",
+            "run",
+            "--report-color",
+            "never",
+            "--color",
+            "always",
+        ]) else {
+            panic!("expected run subcommand");
+        };
+        assert_eq!(output.color, ColorChoice::Never);
+        assert_eq!(args, vec!["--color", "always"]);
+    }
+
+    #[test]
+    fn unknown_package_is_an_error() {
+        let error = apply(&["--only-package", "c"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "package `c` is not a workspace member (members: a, b)"
+        );
+    }
+
     #[test]
     fn split_toolchain_with_nightly() {
         let args = vec!["+nightly".to_string(), "--workspace".to_string()];
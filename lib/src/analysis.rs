@@ -6,10 +6,18 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
 
-use crate::model::{Branch, CoverageExport, FileData, Segment};
+use serde::{Deserialize, Serialize};
+
+use crate::exclusions::{self, Exclusion};
+use crate::model::{Branch, CoverageExport, ExportData, FileData, FunctionData, Segment};
+use crate::public_api::{self, ApiFunction, CrateRoot};
+use crate::source::SourceCache;
+use crate::test_code::TestCode;
+use crate::totals::{self, FileTotals};
+use crate::workspace::Rollup;
 
 /// A coverage gap found during analysis.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CoverageGap {
     /// One or more consecutive fully-uncovered lines.
     UncoveredLines {
@@ -31,6 +39,59 @@ pub enum CoverageGap {
     },
     /// A branch where one direction was never taken.
     UncoveredBranch {
+        /// Line where the branch occurs.
+        line: u64,
+        /// Column where the branch occurs.
+        col: u64,
+        /// Line where the branch condition ends.
+        line_end: u64,
+        /// Column where the branch condition ends (exclusive).
+        col_end: u64,
+        /// Number of times the true branch was taken.
+        true_count: u64,
+        /// Number of times the false branch was taken.
+        false_count: u64,
+        /// Source text of the condition, if the source file was available.
+        condition: Option<String>,
+    },
+    /// A generic function where some monomorphizations never ran.
+    UncoveredInstantiations {
+        /// Line where the function starts.
+        line: u64,
+        /// Column where the function starts.
+        col: u64,
+        /// Demangled names of instantiations that were executed.
+        executed: Vec<String>,
+        /// Demangled names of instantiations with a zero execution count.
+        unexecuted: Vec<String>,
+    },
+    /// One or more consecutive lines executed fewer times than the
+    /// weak-coverage threshold.
+    WeakLines {
+        /// First weakly covered line (1-based).
+        start_line: u64,
+        /// Last weakly covered line (1-based, inclusive).
+        end_line: u64,
+        /// Highest execution count among the lines.
+        max_count: u64,
+    },
+    /// A region on a well-covered line executed fewer times than the
+    /// weak-coverage threshold.
+    WeakRegion {
+        /// Start line.
+        line_start: u64,
+        /// Start column.
+        col_start: u64,
+        /// End line.
+        line_end: u64,
+        /// End column.
+        col_end: u64,
+        /// Execution count of the region.
+        count: u64,
+    },
+    /// A branch where both directions were taken, but one fewer times than
+    /// the weak-coverage threshold.
+    WeakBranch {
         /// Line where the branch occurs.
         line: u64,
         /// Column where the branch occurs.
@@ -42,6 +103,36 @@ pub enum CoverageGap {
     },
 }
 
+impl CoverageGap {
+    /// Returns the first line of the gap.
+    #[must_use]
+    pub const fn start_line(&self) -> u64 {
+        match self {
+            Self::UncoveredLines { start_line, .. } | Self::WeakLines { start_line, .. } => {
+                *start_line
+            }
+            Self::UncoveredRegion { line_start, .. } | Self::WeakRegion { line_start, .. } => {
+                *line_start
+            }
+            Self::UncoveredBranch { line, .. }
+            | Self::WeakBranch { line, .. }
+            | Self::UncoveredInstantiations { line, .. } => *line,
+        }
+    }
+
+    /// Returns the last line of the gap.
+    #[must_use]
+    pub const fn end_line(&self) -> u64 {
+        match self {
+            Self::UncoveredLines { end_line, .. } | Self::WeakLines { end_line, .. } => *end_line,
+            Self::UncoveredRegion { line_end, .. }
+            | Self::WeakRegion { line_end, .. }
+            | Self::UncoveredBranch { line_end, .. } => *line_end,
+            Self::WeakBranch { line, .. } | Self::UncoveredInstantiations { line, .. } => *line,
+        }
+    }
+}
+
 /// Per-file coverage gap results.
 #[derive(Debug, Clone)]
 pub struct FileGaps {
@@ -49,10 +140,68 @@ pub struct FileGaps {
     pub filename: String,
     /// Coverage gaps found in this file, sorted by location.
     pub gaps: Vec<CoverageGap>,
+    /// Functions defined in this file, sorted by location.
+    pub functions: Vec<FunctionSpan>,
+}
+
+impl FileGaps {
+    /// Returns the innermost function whose span contains `line`.
+    #[must_use]
+    pub fn function_at(&self, line: u64) -> Option<&FunctionSpan> {
+        self.functions
+            .iter()
+            .filter(|f| f.line_start <= line && line <= f.line_end)
+            .min_by_key(|f| f.line_end - f.line_start)
+    }
+}
+
+/// The source extent and execution count of a function, merged across its
+/// instantiations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSpan {
+    /// Demangled function name, without generic arguments.
+    pub name: String,
+    /// Start line.
+    pub line_start: u64,
+    /// Start column.
+    pub col_start: u64,
+    /// End line.
+    pub line_end: u64,
+    /// End column.
+    pub col_end: u64,
+    /// Highest execution count among the function's instantiations.
+    pub count: u64,
+    /// How close the function is to the public API; only known when
+    /// checking the public API with sources read.
+    pub visibility: Option<Visibility>,
+}
+
+/// How close a function is to its crate's public API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// Part of the public API: declared `pub` and reachable from the crate
+    /// root through `pub mod`s.
+    Public,
+    /// Declared `pub` or `pub(...)`, but not reachable from outside the
+    /// crate, e.g. `pub(crate)` or `pub` in a private module.
+    Restricted,
+    /// No visibility modifier.
+    Private,
+}
+
+/// A line with one of the highest execution counts in the report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotLine {
+    /// File path as it appears in the coverage data.
+    pub filename: String,
+    /// Line number (1-based).
+    pub line: u64,
+    /// Execution count of the line.
+    pub count: u64,
 }
 
 /// Summary coverage percentages.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CoverageSummary {
     /// Line coverage percentage (0.0-100.0).
     pub lines_percent: f64,
@@ -62,15 +211,59 @@ pub struct CoverageSummary {
     pub branches_percent: Option<f64>,
     /// Function coverage percentage (0.0-100.0).
     pub functions_percent: f64,
+    /// Instantiation coverage percentage (0.0-100.0), if requested.
+    pub instantiations_percent: Option<f64>,
+}
+
+/// Options controlling which optional analyses are performed.
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisOptions {
+    /// Report generic functions whose instantiations were only partially
+    /// executed, and include the instantiation percentage in the summary.
+    pub instantiations: bool,
+    /// Report executed lines, regions, and branch directions with fewer
+    /// than this many hits as weakly covered.
+    pub weak_threshold: Option<u64>,
+    /// Number of most-executed lines to list, if any.
+    pub hot_lines: Option<usize>,
+    /// Read source files to quote branch conditions in branch gaps and to
+    /// find test items.
+    pub read_sources: bool,
+    /// Leave test code out of gaps, hot lines, and totals: files under a
+    /// package's `tests` directory, and, with `read_sources`,
+    /// `#[cfg(test)]` and `#[test]` items found by parsing the sources.
+    pub exclude_tests: bool,
+    /// List items excluded with `coverage(off)` and flag those that are
+    /// still instrumented. Needs `read_sources`; without it nothing is
+    /// audited.
+    pub audit_exclusions: bool,
+    /// Crates whose public functions and methods to check for test
+    /// coverage (none unless requested). Needs `read_sources`; without it
+    /// nothing is checked.
+    pub public_api: Vec<CrateRoot>,
 }
 
 /// Complete analysis result.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct AnalysisResult {
     /// Per-file coverage gaps (only files with gaps are included).
     pub files: Vec<FileGaps>,
     /// Overall coverage summary.
     pub summary: CoverageSummary,
+    /// Most-executed lines across all files, hottest first (empty unless
+    /// requested).
+    pub hot_lines: Vec<HotLine>,
+    /// Per-package and per-module coverage totals (empty unless computed
+    /// with [`crate::workspace::Workspace::rollup`]).
+    pub rollups: Vec<Rollup>,
+    /// Coverage counts of every file, in coverage data order.
+    pub file_totals: Vec<FileTotals>,
+    /// Items excluded with `coverage(off)`, in file and line order (empty
+    /// unless requested).
+    pub exclusions: Vec<Exclusion>,
+    /// Public functions and methods of the requested crates, in module
+    /// order (empty unless requested).
+    pub public_api: Vec<ApiFunction>,
 }
 
 impl AnalysisResult {
@@ -80,46 +273,174 @@ impl AnalysisResult {
     /// unchanged.
     pub fn relativize_paths(&mut self, base: &Path) {
         for file in &mut self.files {
-            if let Ok(rel) = Path::new(&file.filename).strip_prefix(base) {
-                file.filename = format!("./{}", rel.to_string_lossy());
-            }
+            relativize(&mut file.filename, base);
+        }
+        for hot in &mut self.hot_lines {
+            relativize(&mut hot.filename, base);
+        }
+        for file in &mut self.file_totals {
+            relativize(&mut file.filename, base);
+        }
+        for exclusion in &mut self.exclusions {
+            relativize(&mut exclusion.filename, base);
+        }
+        for function in &mut self.public_api {
+            relativize(&mut function.filename, base);
         }
     }
 }
 
+/// Rewrites `filename` relative to `base` if it lies under it.
+fn relativize(filename: &mut String, base: &Path) {
+    if let Ok(rel) = Path::new(filename.as_str()).strip_prefix(base) {
+        *filename = format!("./{}", rel.to_string_lossy());
+    }
+}
+
 /// Analyzes a coverage export and returns all coverage gaps.
 ///
 /// # Errors
 ///
 /// Returns an error if the coverage data is empty.
 pub fn analyze(export: &CoverageExport) -> Result<AnalysisResult, AnalysisError> {
+    analyze_with_options(export, &AnalysisOptions::default())
+}
+
+/// Analyzes a coverage export using the given options.
+///
+/// # Errors
+///
+/// Returns an error if the coverage data is empty.
+pub fn analyze_with_options(
+    export: &CoverageExport,
+    options: &AnalysisOptions,
+) -> Result<AnalysisResult, AnalysisError> {
+    analyze_with_sources(export, options, &mut SourceCache::new())
+}
+
+/// Analyzes a coverage export, reading source files through `sources`.
+fn analyze_with_sources(
+    export: &CoverageExport,
+    options: &AnalysisOptions,
+    sources: &mut SourceCache,
+) -> Result<AnalysisResult, AnalysisError> {
     let data = export.data.first().ok_or(AnalysisError::EmptyData)?;
 
+    let test_code = detect_test_code(data, options, sources);
+
+    let mut instantiation_gaps = if options.instantiations {
+        partial_instantiations(&data.functions)
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut spans = function_spans(&data.functions);
+    let public_api = if options.read_sources {
+        public_api::report(data, &options.public_api, sources)
+    } else {
+        Vec::new()
+    };
+
+    let regions = code_regions(&data.functions);
     let mut files = Vec::new();
     for file in &data.files {
-        let gaps = analyze_file(file);
+        if test_code.is_whole_file(&file.filename) {
+            continue;
+        }
+        let file_regions = regions.get(file.filename.as_str());
+        let mut gaps = analyze_file(file, file_regions.unwrap_or(&BTreeSet::new()), options);
+        if options.read_sources {
+            quote_branch_conditions(&file.filename, &mut gaps, sources);
+        }
+        if let Some(extra) = instantiation_gaps.remove(&file.filename) {
+            gaps.extend(extra);
+        }
+        // Stable, so gaps on one line keep their order by kind.
+        gaps.sort_by_key(CoverageGap::start_line);
+        let gaps = exclude_lines(gaps, |line| test_code.contains(&file.filename, line));
         if !gaps.is_empty() {
+            let mut functions = spans.remove(&file.filename).unwrap_or_default();
+            functions.retain(|f| !test_code.contains(&file.filename, f.line_start));
+            if options.read_sources && !options.public_api.is_empty() {
+                detect_visibility(&file.filename, &mut functions, &public_api, sources);
+            }
             files.push(FileGaps {
                 filename: file.filename.clone(),
                 gaps,
+                functions,
             });
         }
     }
 
+    let (summary, file_totals) = if options.exclude_tests {
+        let file_totals = totals::recompute(data, &test_code);
+        let overall = totals::sum(&file_totals);
+        let summary = CoverageSummary {
+            instantiations_percent: options
+                .instantiations
+                .then(|| overall.instantiations.percent()),
+            ..overall.summary()
+        };
+        (summary, file_totals)
+    } else {
+        (exported_summary(data, options), totals::exported(data))
+    };
+
+    let hot_lines = options.hot_lines.map_or_else(Vec::new, |limit| {
+        hottest_lines(&data.files, limit, &test_code)
+    });
+
+    let exclusions = if options.audit_exclusions && options.read_sources {
+        exclusions::audit(data, sources)
+    } else {
+        Vec::new()
+    };
+
+    Ok(AnalysisResult {
+        files,
+        summary,
+        hot_lines,
+        rollups: Vec::new(),
+        file_totals,
+        exclusions,
+        public_api,
+    })
+}
+
+/// Finds the test code `options` leave out, reading sources only if
+/// allowed.
+pub(crate) fn detect_test_code(
+    data: &ExportData,
+    options: &AnalysisOptions,
+    sources: &mut SourceCache,
+) -> TestCode {
+    let filenames = data.files.iter().map(|f| f.filename.as_str());
+    match (options.exclude_tests, options.read_sources) {
+        (true, true) => TestCode::detect(filenames, sources),
+        (true, false) => TestCode::detect_paths(filenames),
+        (false, _) => TestCode::default(),
+    }
+}
+
+/// Reads the overall summary from the totals `llvm-cov` exported.
+fn exported_summary(data: &ExportData, options: &AnalysisOptions) -> CoverageSummary {
     let totals = &data.totals;
     let branches_percent = totals
         .branches
         .as_ref()
         .and_then(|b| if b.count > 0 { Some(b.percent) } else { None });
 
-    let summary = CoverageSummary {
+    CoverageSummary {
         lines_percent: totals.lines.as_ref().map_or(0.0, |l| l.percent),
         regions_percent: totals.regions.as_ref().map_or(0.0, |r| r.percent),
         branches_percent,
         functions_percent: totals.functions.as_ref().map_or(0.0, |f| f.percent),
-    };
-
-    Ok(AnalysisResult { files, summary })
+        instantiations_percent: if options.instantiations {
+            totals.instantiations.as_ref().map(|i| i.percent)
+        } else {
+            None
+        },
+    }
 }
 
 /// Errors that can occur during analysis.
@@ -130,8 +451,38 @@ pub enum AnalysisError {
     EmptyData,
 }
 
+/// Start and end (exclusive) of a code region, as (line, column) pairs.
+type Extent = ((u64, u64), (u64, u64));
+
+/// Collects the extents of the code regions of `functions` by file,
+/// merged across instantiations.
+fn code_regions(functions: &[FunctionData]) -> BTreeMap<&str, BTreeSet<Extent>> {
+    let mut regions: BTreeMap<&str, BTreeSet<Extent>> = BTreeMap::new();
+    for function in functions {
+        for region in function.regions.iter().filter(|r| r.kind.is_code()) {
+            let filename = usize::try_from(region.file_id)
+                .ok()
+                .and_then(|id| function.filenames.get(id));
+            if let Some(filename) = filename {
+                regions.entry(filename).or_default().insert((
+                    (region.line_start, region.col_start),
+                    (region.line_end, region.col_end),
+                ));
+            }
+        }
+    }
+    regions
+}
+
 /// Analyzes a single file's coverage data and returns its gaps.
-fn analyze_file(file: &FileData) -> Vec<CoverageGap> {
+///
+/// `regions` are the file's code regions, used to tell which region each
+/// uncovered branch direction enters.
+fn analyze_file(
+    file: &FileData,
+    regions: &BTreeSet<Extent>,
+    options: &AnalysisOptions,
+) -> Vec<CoverageGap> {
     let mut gaps = Vec::new();
 
     if !file.segments.is_empty() {
@@ -144,13 +495,236 @@ fn analyze_file(file: &FileData) -> Vec<CoverageGap> {
     // at each (line, col). LLVM emits separate branch records per generic
     // monomorphization; unexecuted instantiations show true:0 false:0.
     let merged_branches = merge_branches(&file.branches);
+    let branch_ends: BTreeMap<(u64, u64), (u64, u64)> = file
+        .branches
+        .iter()
+        .map(|b| ((b.line_start, b.col_start), (b.line_end, b.col_end)))
+        .collect();
     for ((line, col), (true_count, false_count)) in &merged_branches {
         if *true_count == 0 || *false_count == 0 {
+            let (line_end, col_end) = branch_ends[&(*line, *col)];
             gaps.push(CoverageGap::UncoveredBranch {
                 line: *line,
                 col: *col,
+                line_end,
+                col_end,
                 true_count: *true_count,
                 false_count: *false_count,
+                condition: None,
+            });
+        }
+    }
+    suppress_explained_regions(&mut gaps, regions);
+
+    if let Some(threshold) = options.weak_threshold {
+        gaps.extend(weak_segments(&file.segments, threshold));
+        for ((line, col), (true_count, false_count)) in &merged_branches {
+            let weakest = (*true_count).min(*false_count);
+            if weakest > 0 && weakest < threshold {
+                gaps.push(CoverageGap::WeakBranch {
+                    line: *line,
+                    col: *col,
+                    true_count: *true_count,
+                    false_count: *false_count,
+                });
+            }
+        }
+    }
+
+    gaps
+}
+
+/// Removes region gaps that are already explained by a branch gap.
+///
+/// An untaken branch direction leads into code that never ran, so LLVM
+/// reports both the branch and the zero-count region that direction
+/// enters; the branch gap is kept as the single entry for that untested
+/// path. Among the file's code `regions`, the true direction enters the
+/// region starting first after the condition (the `then` block or right
+/// operand), and the false direction the region starting first after
+/// that one ends (the `else` block or the code that follows). A branch
+/// that was never evaluated explains no region, and other regions are
+/// kept.
+fn suppress_explained_regions(gaps: &mut Vec<CoverageGap>, regions: &BTreeSet<Extent>) {
+    // The outermost region starting first at or after `position`.
+    let next_region = |position: (u64, u64)| {
+        let (start, _) = *regions.range((position, (0, 0))..).next()?;
+        regions
+            .range((start, (0, 0))..=(start, (u64::MAX, u64::MAX)))
+            .next_back()
+            .copied()
+    };
+    let explained: BTreeSet<(u64, u64)> = gaps
+        .iter()
+        .filter_map(|gap| match gap {
+            CoverageGap::UncoveredBranch {
+                line_end,
+                col_end,
+                true_count,
+                false_count,
+                ..
+            } => {
+                let (taken, end) = next_region((*line_end, *col_end))?;
+                match (true_count, false_count) {
+                    (0, 0) => None,
+                    (0, _) => Some(taken),
+                    _ => next_region(end).map(|(start, _)| start),
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    gaps.retain(|gap| match gap {
+        CoverageGap::UncoveredRegion {
+            line_start,
+            col_start,
+            ..
+        } => !explained.contains(&(*line_start, *col_start)),
+        _ => true,
+    });
+}
+
+/// Removes gaps in `excluded` lines.
+///
+/// Line-range gaps are split around excluded lines; other gaps are dropped
+/// when their first line is excluded.
+fn exclude_lines(gaps: Vec<CoverageGap>, excluded: impl Fn(u64) -> bool) -> Vec<CoverageGap> {
+    let mut kept = Vec::with_capacity(gaps.len());
+    for gap in gaps {
+        match gap {
+            CoverageGap::UncoveredLines {
+                start_line,
+                end_line,
+            } => {
+                for (start_line, end_line) in runs(start_line, end_line, &excluded) {
+                    kept.push(CoverageGap::UncoveredLines {
+                        start_line,
+                        end_line,
+                    });
+                }
+            }
+            CoverageGap::WeakLines {
+                start_line,
+                end_line,
+                max_count,
+            } => {
+                for (start_line, end_line) in runs(start_line, end_line, &excluded) {
+                    kept.push(CoverageGap::WeakLines {
+                        start_line,
+                        end_line,
+                        max_count,
+                    });
+                }
+            }
+            CoverageGap::UncoveredRegion { line_start, .. }
+            | CoverageGap::WeakRegion { line_start, .. }
+            | CoverageGap::UncoveredBranch {
+                line: line_start, ..
+            }
+            | CoverageGap::WeakBranch {
+                line: line_start, ..
+            }
+            | CoverageGap::UncoveredInstantiations {
+                line: line_start, ..
+            } => {
+                if !excluded(line_start) {
+                    kept.push(gap);
+                }
+            }
+        }
+    }
+    kept
+}
+
+/// Splits `start..=end` into maximal runs of lines that are not `excluded`.
+fn runs(start: u64, end: u64, excluded: impl Fn(u64) -> bool) -> Vec<(u64, u64)> {
+    let mut runs = Vec::new();
+    let mut current: Option<(u64, u64)> = None;
+    for line in start..=end {
+        if excluded(line) {
+            runs.extend(current.take());
+        } else {
+            current = Some((current.map_or(line, |(s, _)| s), line));
+        }
+    }
+    runs.extend(current);
+    runs
+}
+
+/// Fills in the condition text of branch gaps from the source file.
+///
+/// Gaps are left unchanged when the source is unavailable.
+fn quote_branch_conditions(filename: &str, gaps: &mut [CoverageGap], sources: &mut SourceCache) {
+    for gap in gaps {
+        if let CoverageGap::UncoveredBranch {
+            line,
+            col,
+            line_end,
+            col_end,
+            condition,
+            ..
+        } = gap
+        {
+            *condition = sources.snippet(filename, (*line, *col), (*line_end, *col_end));
+        }
+    }
+}
+
+/// Finds executed lines and regions with fewer than `threshold` hits.
+///
+/// Consecutive weak lines are collapsed into ranges. A weak region is only
+/// reported when its line is otherwise well covered, since a weak line
+/// already covers every region on it.
+fn weak_segments(segments: &[Segment], threshold: u64) -> Vec<CoverageGap> {
+    let lines = line_coverage(segments);
+    let is_weak = |count: u64| count > 0 && count < threshold;
+
+    let mut gaps = Vec::new();
+    let mut current: Option<(u64, u64, u64)> = None;
+    for line in lines.iter().filter(|l| is_weak(l.count)) {
+        current = match current {
+            Some((start, end, max)) if line.line == end + 1 => {
+                Some((start, line.line, max.max(line.count)))
+            }
+            _ => {
+                if let Some((start_line, end_line, max_count)) = current {
+                    gaps.push(CoverageGap::WeakLines {
+                        start_line,
+                        end_line,
+                        max_count,
+                    });
+                }
+                Some((line.line, line.line, line.count))
+            }
+        };
+    }
+    if let Some((start_line, end_line, max_count)) = current {
+        gaps.push(CoverageGap::WeakLines {
+            start_line,
+            end_line,
+            max_count,
+        });
+    }
+
+    let strong_lines: BTreeSet<u64> = lines
+        .iter()
+        .filter(|l| l.count >= threshold)
+        .map(|l| l.line)
+        .collect();
+    for (i, seg) in segments.iter().enumerate() {
+        if seg.is_region_entry
+            && seg.has_count
+            && !seg.is_gap_region
+            && is_weak(seg.count)
+            && strong_lines.contains(&seg.line)
+        {
+            let end = segments.get(i + 1).unwrap_or(seg);
+            gaps.push(CoverageGap::WeakRegion {
+                line_start: seg.line,
+                col_start: seg.col,
+                line_end: end.line,
+                col_end: end.col,
+                count: seg.count,
             });
         }
     }
@@ -158,13 +732,37 @@ fn analyze_file(file: &FileData) -> Vec<CoverageGap> {
     gaps
 }
 
+/// Returns the `limit` most-executed lines across all files, leaving out
+/// `excluded` code.
+///
+/// Ties are broken by file order and then line number.
+fn hottest_lines(files: &[FileData], limit: usize, excluded: &TestCode) -> Vec<HotLine> {
+    let mut hot: Vec<HotLine> = files
+        .iter()
+        .flat_map(|file| {
+            line_coverage(&file.segments)
+                .into_iter()
+                .filter(|l| l.count > 0 && !excluded.contains(&file.filename, l.line))
+                .map(|l| HotLine {
+                    filename: file.filename.clone(),
+                    line: l.line,
+                    count: l.count,
+                })
+        })
+        .collect();
+    // Stable sort keeps file and line order among equal counts.
+    hot.sort_by_key(|h| std::cmp::Reverse(h.count));
+    hot.truncate(limit);
+    hot
+}
+
 /// Merges branch records across generic instantiations.
 ///
 /// LLVM emits separate branch records per monomorphization. When a generic
 /// function has both executed and unexecuted instantiations, the unexecuted
 /// ones show `true:0 false:0`. We merge by `(line, col)`, taking the max
 /// count for each direction, matching how `llvm-cov` computes its summary.
-fn merge_branches(branches: &[Branch]) -> BTreeMap<(u64, u64), (u64, u64)> {
+pub(crate) fn merge_branches(branches: &[Branch]) -> BTreeMap<(u64, u64), (u64, u64)> {
     let mut merged: BTreeMap<(u64, u64), (u64, u64)> = BTreeMap::new();
     for branch in branches {
         let entry = merged
@@ -176,81 +774,343 @@ fn merge_branches(branches: &[Branch]) -> BTreeMap<(u64, u64), (u64, u64)> {
     merged
 }
 
-/// Represents a region entry from a segment with its span derived from
-/// the next segment.
-struct RegionSpan {
-    line_start: u64,
-    col_start: u64,
-    line_end: u64,
-    col_end: u64,
+/// Identifies the instantiations of one function.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct InstantiationKey<'a> {
+    /// File of the function's first code region.
+    pub(crate) filename: &'a str,
+    /// Line of the function's first code region.
+    pub(crate) line: u64,
+    /// Column of the function's first code region.
+    pub(crate) col: u64,
+    /// Demangled path without generic arguments.
+    pub(crate) path: String,
 }
 
-/// Analyzes segments to find uncovered lines and sub-line regions.
+/// Groups function records by the file and position of their first code
+/// region and by their path without generic arguments.
 ///
-/// Returns `(uncovered_line_gaps, uncovered_region_gaps)`.
-fn analyze_segments(segments: &[Segment]) -> (Vec<CoverageGap>, Vec<CoverageGap>) {
-    // Build per-line coverage: track the max count seen on each line.
-    // Also collect region entries with their spans for sub-line analysis.
-    let mut line_max_count: BTreeMap<u64, u64> = BTreeMap::new();
-    let mut lines_with_coverage: BTreeSet<u64> = BTreeSet::new();
-    let mut region_spans: Vec<RegionSpan> = Vec::new();
-
-    for i in 0..segments.len() {
-        let seg = &segments[i];
-        if !seg.has_count {
+/// This mirrors how `llvm-cov` forms instantiation groups: every
+/// monomorphization of a generic function shares the same source location.
+/// The path keeps apart distinct functions expanded from the same macro or
+/// derive, which share a location too.
+pub(crate) fn group_instantiations(
+    functions: &[FunctionData],
+) -> BTreeMap<InstantiationKey<'_>, Vec<&FunctionData>> {
+    let mut groups: BTreeMap<InstantiationKey<'_>, Vec<&FunctionData>> = BTreeMap::new();
+    for function in functions {
+        let Some(first) = function.regions.iter().find(|r| r.kind.is_code()) else {
+            continue;
+        };
+        let Some(filename) = usize::try_from(first.file_id)
+            .ok()
+            .and_then(|id| function.filenames.get(id))
+        else {
+            continue;
+        };
+        groups
+            .entry(InstantiationKey {
+                filename,
+                line: first.line_start,
+                col: first.col_start,
+                path: without_generic_args(&demangle(&function.name)),
+            })
+            .or_default()
+            .push(function);
+    }
+    groups
+}
+
+/// Finds generic functions where at least one instantiation ran and at
+/// least one never did, keyed by filename.
+///
+/// These are exactly the cases hidden by [`merge_branches`] and by the
+/// per-line max over segments.
+fn partial_instantiations(functions: &[FunctionData]) -> BTreeMap<String, Vec<CoverageGap>> {
+    let mut gaps: BTreeMap<String, Vec<CoverageGap>> = BTreeMap::new();
+    for (
+        InstantiationKey {
+            filename,
+            line,
+            col,
+            ..
+        },
+        group,
+    ) in group_instantiations(functions)
+    {
+        let mut executed = BTreeSet::new();
+        let mut unexecuted = BTreeSet::new();
+        for function in group {
+            let name = demangle(&function.name);
+            if function.count > 0 {
+                executed.insert(name);
+            } else {
+                unexecuted.insert(name);
+            }
+        }
+        if executed.is_empty() || unexecuted.is_empty() {
             continue;
         }
+        gaps.entry(filename.to_string())
+            .or_default()
+            .push(CoverageGap::UncoveredInstantiations {
+                line,
+                col,
+                executed: executed.into_iter().collect(),
+                unexecuted: unexecuted.into_iter().collect(),
+            });
+    }
+    gaps
+}
 
-        // Determine span end from the next segment (or same point if last).
-        let (end_line, end_col) = if i + 1 < segments.len() {
-            (segments[i + 1].line, segments[i + 1].col)
-        } else {
-            (seg.line, seg.col)
+/// Computes the span of every function, keyed by filename.
+///
+/// A function's span covers all of its code regions in its own file; its
+/// count is the highest among its instantiations.
+fn function_spans(functions: &[FunctionData]) -> BTreeMap<String, Vec<FunctionSpan>> {
+    let mut spans: BTreeMap<String, Vec<FunctionSpan>> = BTreeMap::new();
+    for (
+        InstantiationKey {
+            filename,
+            line,
+            col,
+            ..
+        },
+        group,
+    ) in group_instantiations(functions)
+    {
+        let mut span = FunctionSpan {
+            name: strip_generic_args(&demangle(&group[0].name)).to_string(),
+            line_start: line,
+            col_start: col,
+            line_end: line,
+            col_end: col,
+            count: group.iter().map(|f| f.count).max().unwrap_or(0),
+            visibility: None,
         };
+        for function in &group {
+            let file_id = function
+                .filenames
+                .iter()
+                .position(|f| f == filename)
+                .and_then(|id| u64::try_from(id).ok());
+            for region in &function.regions {
+                if region.kind.is_code()
+                    && Some(region.file_id) == file_id
+                    && (region.line_end, region.col_end) > (span.line_end, span.col_end)
+                {
+                    span.line_end = region.line_end;
+                    span.col_end = region.col_end;
+                }
+            }
+        }
+        spans.entry(filename.to_string()).or_default().push(span);
+    }
+    spans
+}
+
+/// Fills in how close each function is to the public API: functions in
+/// `api` are public, and others are restricted if the line where they start
+/// declares them `pub` in some form.
+fn detect_visibility(
+    filename: &str,
+    functions: &mut [FunctionSpan],
+    api: &[ApiFunction],
+    sources: &mut SourceCache,
+) {
+    let Some(contents) = sources.get(filename) else {
+        return;
+    };
+    let lines: Vec<&str> = contents.lines().collect();
+    for function in functions {
+        let reachable = api.iter().any(|f| {
+            f.filename == filename && (f.line_start..=f.line_end).contains(&function.line_start)
+        });
+        let line = usize::try_from(function.line_start)
+            .ok()
+            .and_then(|l| l.checked_sub(1))
+            .and_then(|l| lines.get(l));
+        function.visibility = line.map(|text| {
+            let text = text.trim_start();
+            if reachable {
+                Visibility::Public
+            } else if text.starts_with("pub ") || text.starts_with("pub(") {
+                Visibility::Restricted
+            } else {
+                Visibility::Private
+            }
+        });
+    }
+}
+
+/// Strips trailing generic arguments (`::<...>`) from a demangled name.
+fn strip_generic_args(name: &str) -> &str {
+    if name.ends_with('>')
+        && let Some(index) = name.rfind("::<")
+    {
+        return &name[..index];
+    }
+    name
+}
 
-        // Record counts for all lines this segment spans.
-        for line in seg.line..=end_line {
-            let entry = line_max_count.entry(line).or_insert(0);
-            *entry = (*entry).max(seg.count);
-            if seg.count > 0 {
-                lines_with_coverage.insert(line);
+/// Removes every generic argument list from a demangled path, so that all
+/// instantiations of a function share it: `<Vec<u8> as t::T>::f::<u8>`
+/// becomes `<Vec as t::T>::f`.
+///
+/// A `<` right after a name or `::` opens generic arguments; anywhere else
+/// it opens a qualified path such as `<Type as Trait>`, which is kept.
+fn without_generic_args(name: &str) -> String {
+    let mut path = String::new();
+    // Nesting depth inside the generic arguments being removed.
+    let mut depth = 0_usize;
+    let mut previous = ' ';
+    for c in name.chars() {
+        if depth > 0 {
+            match c {
+                '<' => depth += 1,
+                // `->` in a function pointer type closes nothing.
+                '>' if previous != '-' => depth -= 1,
+                _ => {}
             }
+        } else if c == '<' && path.ends_with("::") {
+            path.truncate(path.len() - 2);
+            depth = 1;
+        } else if c == '<' && path.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+            depth = 1;
+        } else {
+            path.push(c);
         }
+        previous = c;
+    }
+    path
+}
 
-        // Collect region entry spans for sub-line analysis.
-        if seg.is_region_entry && seg.count == 0 {
-            region_spans.push(RegionSpan {
-                line_start: seg.line,
-                col_start: seg.col,
-                line_end: end_line,
-                col_end: end_col,
-            });
+/// Demangles a Rust symbol name, dropping the trailing hash.
+///
+/// Names that are not valid Rust symbols are returned unchanged.
+fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
+
+/// Execution data for a single code line.
+///
+/// Produced by [`line_coverage`], which mirrors LLVM's `LineCoverageStats`
+/// so that counts agree with `llvm-cov show` and with the exported line
+/// totals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCoverage {
+    /// Line number (1-based).
+    pub line: u64,
+    /// Execution count of the line.
+    pub count: u64,
+    /// Whether two or more regions start on this line.
+    pub has_multiple_regions: bool,
+}
+
+/// Computes per-line execution counts from a file's segments.
+///
+/// Only lines that `llvm-cov` considers code ("mapped") are returned, in
+/// ascending order. A line's count is the count of the segment wrapping
+/// into it from a previous line, raised to the maximum count of any
+/// non-gap region starting on it. A line is not code if it opens a
+/// skipped region, or if it has neither a wrapped count nor a counted
+/// region starting on it.
+#[must_use]
+pub fn line_coverage(segments: &[Segment]) -> Vec<LineCoverage> {
+    let mut lines = Vec::new();
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return lines;
+    };
+
+    let mut next = 0;
+    let mut wrapped: Option<&Segment> = None;
+    for line in first.line..=last.line {
+        let start = next;
+        while next < segments.len() && segments[next].line == line {
+            next += 1;
+        }
+        let line_segments = &segments[start..next];
+        if let Some(stats) = line_stats(line, line_segments, wrapped) {
+            lines.push(stats);
+        }
+        if let Some(last_on_line) = line_segments.last() {
+            wrapped = Some(last_on_line);
         }
     }
+    lines
+}
+
+/// Classifies one line given the segments starting on it and the segment
+/// wrapping into it, following LLVM's `LineCoverageStats` constructor.
+fn line_stats(line: u64, segments: &[Segment], wrapped: Option<&Segment>) -> Option<LineCoverage> {
+    let is_start_of_region = |s: &&Segment| !s.is_gap_region && s.has_count && s.is_region_entry;
+    let region_starts = segments.iter().filter(is_start_of_region).count();
+
+    let start_of_skipped_region = segments
+        .first()
+        .is_some_and(|s| !s.has_count && s.is_region_entry);
+    let wrapped_has_count = wrapped.is_some_and(|s| s.has_count);
 
-    // Find fully uncovered lines (max count == 0, and line was tracked).
-    let uncovered_lines: BTreeSet<u64> = line_max_count
+    let mapped = (!start_of_skipped_region && (wrapped_has_count || region_starts > 0))
+        || segments.iter().any(|s| s.is_region_entry && s.has_count);
+    if !mapped {
+        return None;
+    }
+
+    let wrapped_count = wrapped.map_or(0, |s| s.count);
+    let count = segments
         .iter()
-        .filter(|(_, count)| **count == 0)
-        .map(|(line, _)| *line)
+        .filter(is_start_of_region)
+        .map(|s| s.count)
+        .fold(wrapped_count, u64::max);
+
+    Some(LineCoverage {
+        line,
+        count,
+        has_multiple_regions: region_starts > 1,
+    })
+}
+
+/// Analyzes segments to find uncovered lines and sub-line regions.
+///
+/// Returns `(uncovered_line_gaps, uncovered_region_gaps)`.
+fn analyze_segments(segments: &[Segment]) -> (Vec<CoverageGap>, Vec<CoverageGap>) {
+    let lines = line_coverage(segments);
+
+    let uncovered_lines: BTreeSet<u64> = lines
+        .iter()
+        .filter(|l| l.count == 0)
+        .map(|l| l.line)
+        .collect();
+    let executed_lines: BTreeSet<u64> = lines
+        .iter()
+        .filter(|l| l.count > 0)
+        .map(|l| l.line)
         .collect();
 
     let line_gaps = collapse_lines(&uncovered_lines);
 
-    // Find uncovered regions on partially-covered lines.
-    // A region is "sub-line" if its start line has other coverage.
-    let region_gaps: Vec<CoverageGap> = region_spans
-        .into_iter()
-        .filter(|r| {
-            // Only show as REGION if the start line is NOT fully uncovered
-            // (otherwise it's already shown as UNCOVERED).
-            !uncovered_lines.contains(&r.line_start) && lines_with_coverage.contains(&r.line_start)
+    // Find uncovered regions on executed lines (a region on an uncovered
+    // line is already shown as UNCOVERED). The region spans until the next
+    // segment, or is empty if it is the last one.
+    let region_gaps: Vec<CoverageGap> = segments
+        .iter()
+        .enumerate()
+        .filter(|(_, seg)| {
+            seg.is_region_entry
+                && seg.has_count
+                && !seg.is_gap_region
+                && seg.count == 0
+                && executed_lines.contains(&seg.line)
         })
-        .map(|r| CoverageGap::UncoveredRegion {
-            line_start: r.line_start,
-            col_start: r.col_start,
-            line_end: r.line_end,
-            col_end: r.col_end,
+        .map(|(i, seg)| {
+            let end = segments.get(i + 1).unwrap_or(seg);
+            CoverageGap::UncoveredRegion {
+                line_start: seg.line,
+                col_start: seg.col,
+                line_end: end.line,
+                col_end: end.col,
+            }
         })
         .collect();
 
@@ -294,6 +1154,7 @@ fn collapse_lines(lines: &BTreeSet<u64>) -> Vec<CoverageGap> {
 #[cfg_attr(coverage_nightly, coverage(off))]
 mod tests {
     use super::*;
+    use crate::model::RegionKind;
     use std::path::PathBuf;
 
     fn make_result(filenames: &[&str]) -> AnalysisResult {
@@ -303,6 +1164,7 @@ mod tests {
                 .map(|f| FileGaps {
                     filename: f.to_string(),
                     gaps: vec![],
+                    functions: vec![],
                 })
                 .collect(),
             summary: CoverageSummary {
@@ -310,7 +1172,9 @@ mod tests {
                 regions_percent: 100.0,
                 branches_percent: None,
                 functions_percent: 100.0,
+                instantiations_percent: None,
             },
+            ..AnalysisResult::default()
         }
     }
 
@@ -353,6 +1217,165 @@ mod tests {
         assert_eq!(merged[&(20, 1)], (3, 4));
     }
 
+    fn make_function(name: &str, count: u64, line: u64) -> FunctionData {
+        FunctionData {
+            name: name.to_string(),
+            count,
+            filenames: vec!["src/lib.rs".to_string()],
+            regions: vec![crate::model::Region {
+                line_start: line,
+                col_start: 1,
+                line_end: line + 2,
+                col_end: 2,
+                execution_count: count,
+                file_id: 0,
+                expanded_file_id: 0,
+                kind: RegionKind::Code,
+            }],
+            branches: vec![],
+        }
+    }
+
+    #[test]
+    fn test_partial_instantiations_reports_mixed_group() {
+        let functions = vec![
+            make_function("parse::<u32>", 4, 10),
+            make_function("parse::<i64>", 0, 10),
+            make_function("helper", 1, 20),
+        ];
+        let gaps = partial_instantiations(&functions);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(
+            gaps["src/lib.rs"],
+            vec![CoverageGap::UncoveredInstantiations {
+                line: 10,
+                col: 1,
+                executed: vec!["parse::<u32>".to_string()],
+                unexecuted: vec!["parse::<i64>".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_partial_instantiations_skips_uniform_groups() {
+        // All executed, or none executed: nothing hidden by the max merge.
+        let functions = vec![
+            make_function("a::<u8>", 1, 10),
+            make_function("a::<u16>", 2, 10),
+            make_function("b::<u8>", 0, 20),
+            make_function("b::<u16>", 0, 20),
+        ];
+        assert!(partial_instantiations(&functions).is_empty());
+    }
+
+    #[test]
+    fn test_group_instantiations_skips_functions_without_file() {
+        let mut no_regions = make_function("a", 1, 10);
+        no_regions.regions.clear();
+        let mut bad_file = make_function("b", 1, 10);
+        bad_file.regions[0].file_id = 3;
+        assert!(group_instantiations(&[no_regions, bad_file]).is_empty());
+    }
+
+    #[test]
+    fn test_function_spans_merge_regions_and_instantiations() {
+        let mut a = make_function("_RNvCs1_1t3foo", 0, 10);
+        a.regions.push(crate::model::Region {
+            line_start: 14,
+            line_end: 15,
+            col_end: 6,
+            ..a.regions[0].clone()
+        });
+        let b = make_function("_RNvCs1_1t3foo", 4, 10);
+        let spans = function_spans(&[a, b]);
+        assert_eq!(
+            spans["src/lib.rs"],
+            vec![FunctionSpan {
+                name: "t::foo".to_string(),
+                line_start: 10,
+                col_start: 1,
+                line_end: 15,
+                col_end: 6,
+                count: 4,
+                visibility: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_detect_visibility() {
+        let mut sources = SourceCache::new();
+        sources.insert(
+            "src/lib.rs",
+            "pub fn a() {}\n    pub(crate) fn b() {}\nfn c() {}\nmod m {\n    pub fn d() {}\n}\n",
+        );
+        let mut functions: Vec<FunctionSpan> = [1, 2, 3, 5, 9]
+            .into_iter()
+            .map(|line| FunctionSpan {
+                name: String::new(),
+                line_start: line,
+                col_start: 1,
+                line_end: line,
+                col_end: 2,
+                count: 0,
+                visibility: None,
+            })
+            .collect();
+        let api = [ApiFunction {
+            filename: "src/lib.rs".to_string(),
+            path: "my_crate::a".to_string(),
+            line_start: 1,
+            line_end: 1,
+            count: 0,
+        }];
+        detect_visibility("src/lib.rs", &mut functions, &api, &mut sources);
+        let visibilities: Vec<_> = functions.iter().map(|f| f.visibility).collect();
+        assert_eq!(
+            visibilities,
+            vec![
+                Some(Visibility::Public),
+                Some(Visibility::Restricted),
+                Some(Visibility::Private),
+                // `pub` in a private module is not public API.
+                Some(Visibility::Restricted),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_at_prefers_innermost() {
+        let span = |name: &str, line_start, line_end| FunctionSpan {
+            name: name.to_string(),
+            line_start,
+            col_start: 1,
+            line_end,
+            col_end: 2,
+            count: 1,
+            visibility: None,
+        };
+        let file = FileGaps {
+            filename: "src/lib.rs".to_string(),
+            gaps: vec![],
+            functions: vec![span("outer", 1, 20), span("closure", 5, 7)],
+        };
+        assert_eq!(file.function_at(6).unwrap().name, "closure");
+        assert_eq!(file.function_at(10).unwrap().name, "outer");
+        assert!(file.function_at(30).is_none());
+    }
+
+    #[test]
+    fn test_strip_generic_args() {
+        assert_eq!(strip_generic_args("t::parse::<u32>"), "t::parse");
+        assert_eq!(strip_generic_args("<t::A as t::B>::f"), "<t::A as t::B>::f");
+    }
+
+    #[test]
+    fn test_demangle_v0_symbol() {
+        assert_eq!(demangle("_RNvCs1_1t3foo"), "t::foo");
+        assert_eq!(demangle("not_mangled"), "not_mangled");
+    }
+
     #[test]
     fn test_relativize_strips_prefix() {
         let mut result = make_result(&["/home/user/project/src/lib.rs"]);
@@ -389,10 +1412,8 @@ mod tests {
 
     #[test]
     fn test_analyze_segments_region_entry_with_coverage() {
-        // A segment that is_region_entry=true but count > 0.
-        // This exercises the false branch of `seg.is_region_entry && seg.count == 0`
-        // at line 199. The only segment is a covered region entry, so no gaps
-        // should be reported.
+        // A segment that is_region_entry=true but count > 0. The only segment
+        // is a covered region entry, so no gaps should be reported.
         let segments = vec![Segment {
             line: 1,
             col: 1,
@@ -410,9 +1431,8 @@ mod tests {
 
     #[test]
     fn test_analyze_segments_uncovered_region_on_uncovered_line() {
-        // An uncovered region entry on a fully uncovered line.
-        // This exercises the true branch of `uncovered_lines.contains(&r.line_start)`
-        // at line 225, which filters out the region (already shown as UNCOVERED line).
+        // An uncovered region entry on a fully uncovered line is filtered
+        // out, since it is already shown as an UNCOVERED line.
         let segments = vec![
             Segment {
                 line: 5,
@@ -445,6 +1465,591 @@ mod tests {
         assert!(region_gaps.is_empty());
     }
 
+    fn segment(line: u64, col: u64, count: u64, flags: (bool, bool, bool)) -> Segment {
+        let (has_count, is_region_entry, is_gap_region) = flags;
+        Segment {
+            line,
+            col,
+            count,
+            has_count,
+            is_region_entry,
+            is_gap_region,
+        }
+    }
+
+    #[test]
+    fn test_analyze_segments_gap_region_is_not_code() {
+        // A zero-count gap region opens after the `}` on line 3. It does not
+        // count as a region start, so line 3 keeps the wrapped count.
+        let segments = vec![
+            segment(1, 1, 2, (true, true, false)),
+            segment(3, 6, 0, (true, true, true)),
+            segment(4, 1, 2, (true, true, false)),
+            segment(5, 2, 0, (false, false, false)),
+        ];
+        let (line_gaps, region_gaps) = analyze_segments(&segments);
+        assert!(line_gaps.is_empty());
+        assert!(region_gaps.is_empty());
+    }
+
+    #[test]
+    fn test_line_coverage_lines_inside_gap_take_gap_count() {
+        // Lines wholly inside a gap region are wrapped by it, exactly as
+        // `llvm-cov show` renders them.
+        let segments = vec![
+            segment(1, 1, 2, (true, true, false)),
+            segment(2, 6, 0, (true, true, true)),
+            segment(4, 1, 2, (true, true, false)),
+            segment(4, 9, 0, (false, false, false)),
+        ];
+        let counts: Vec<(u64, u64)> = line_coverage(&segments)
+            .iter()
+            .map(|l| (l.line, l.count))
+            .collect();
+        assert_eq!(counts, vec![(1, 2), (2, 2), (3, 0), (4, 2)]);
+    }
+
+    #[test]
+    fn test_line_coverage_multiple_regions() {
+        let segments = vec![
+            segment(1, 1, 1, (true, true, false)),
+            segment(1, 5, 0, (true, true, false)),
+            segment(1, 9, 1, (true, false, false)),
+            segment(2, 1, 0, (false, false, false)),
+        ];
+        // Line 2 is wrapped by the counted segment ending line 1 and ends it.
+        let lines = line_coverage(&segments);
+        assert_eq!(
+            lines,
+            vec![
+                LineCoverage {
+                    line: 1,
+                    count: 1,
+                    has_multiple_regions: true,
+                },
+                LineCoverage {
+                    line: 2,
+                    count: 1,
+                    has_multiple_regions: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_coverage_empty() {
+        assert!(line_coverage(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_analyze_segments_skipped_region_is_not_code() {
+        // Lines 3-5 are a `#[cfg]`'d-out item. The preceding region's span
+        // ends on line 3, which must not be reported as uncovered.
+        let segments = vec![
+            segment(1, 1, 0, (true, true, false)),
+            segment(3, 1, 0, (false, true, false)),
+            segment(5, 2, 0, (false, false, false)),
+        ];
+        let (line_gaps, _) = analyze_segments(&segments);
+        assert_eq!(
+            line_gaps,
+            vec![CoverageGap::UncoveredLines {
+                start_line: 1,
+                end_line: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_analyze_segments_region_after_skipped_code_on_same_line() {
+        // A skipped region opens line 3, but a counted region also starts
+        // there, so the line is still code.
+        let segments = vec![
+            segment(3, 1, 0, (false, true, false)),
+            segment(3, 20, 0, (true, true, false)),
+            segment(3, 30, 0, (false, false, false)),
+        ];
+        let (line_gaps, _) = analyze_segments(&segments);
+        assert_eq!(
+            line_gaps,
+            vec![CoverageGap::UncoveredLines {
+                start_line: 3,
+                end_line: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_group_instantiations_uses_first_code_region() {
+        let mut function = make_function("f", 1, 10);
+        function.regions.insert(
+            0,
+            crate::model::Region {
+                kind: RegionKind::Skipped,
+                line_start: 2,
+                ..function.regions[0].clone()
+            },
+        );
+        let groups = group_instantiations(std::slice::from_ref(&function));
+        assert!(groups.contains_key(&InstantiationKey {
+            filename: "src/lib.rs",
+            line: 10,
+            col: 1,
+            path: "f".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_group_instantiations_separates_functions_at_one_location() {
+        // Two instantiations of `deserialize` and a visitor method, all
+        // expanded from the same derive.
+        let functions = [
+            make_function("<t::A as serde::Deserialize>::deserialize::<u8>", 1, 10),
+            make_function("<t::A as serde::Deserialize>::deserialize::<i64>", 1, 10),
+            make_function(
+                "<<t::A as serde::Deserialize>::deserialize::__Visitor as serde::de::Visitor>::expecting",
+                1,
+                10,
+            ),
+        ];
+        let groups: Vec<(String, usize)> = group_instantiations(&functions)
+            .into_iter()
+            .map(|(key, group)| (key.path, group.len()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    "<<t::A as serde::Deserialize>::deserialize::__Visitor as serde::de::Visitor>::expecting".to_string(),
+                    1
+                ),
+                ("<t::A as serde::Deserialize>::deserialize".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_without_generic_args() {
+        assert_eq!(without_generic_args("t::parse::<u32>"), "t::parse");
+        assert_eq!(
+            without_generic_args("<alloc::vec::Vec<u8> as t::T>::f::<&mut X<'_>>"),
+            "<alloc::vec::Vec as t::T>::f"
+        );
+        assert_eq!(
+            without_generic_args("t::call::<fn() -> u8>::{closure#0}"),
+            "t::call::{closure#0}"
+        );
+        assert_eq!(
+            without_generic_args("<<t::A as t::B>::C as t::D>::f"),
+            "<<t::A as t::B>::C as t::D>::f"
+        );
+    }
+
+    #[test]
+    fn test_weak_segments_collapses_lines_and_reports_regions() {
+        // Lines 1-2 run once; line 4 runs 10 times but contains a region
+        // that ran twice; line 6 runs 10 times.
+        let segments = vec![
+            segment(1, 1, 1, (true, true, false)),
+            segment(2, 5, 0, (false, false, false)),
+            segment(4, 1, 10, (true, true, false)),
+            segment(4, 8, 2, (true, true, false)),
+            segment(4, 12, 10, (true, false, false)),
+            segment(6, 1, 10, (true, true, false)),
+            segment(6, 9, 0, (false, false, false)),
+        ];
+        let gaps = weak_segments(&segments, 3);
+        assert_eq!(
+            gaps,
+            vec![
+                CoverageGap::WeakLines {
+                    start_line: 1,
+                    end_line: 2,
+                    max_count: 1,
+                },
+                CoverageGap::WeakRegion {
+                    line_start: 4,
+                    col_start: 8,
+                    line_end: 4,
+                    col_end: 12,
+                    count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weak_segments_separates_non_adjacent_lines() {
+        let segments = vec![
+            segment(1, 1, 1, (true, true, false)),
+            segment(1, 9, 0, (false, false, false)),
+            segment(3, 1, 2, (true, true, false)),
+            segment(3, 9, 0, (false, false, false)),
+        ];
+        let gaps = weak_segments(&segments, 3);
+        assert_eq!(
+            gaps,
+            vec![
+                CoverageGap::WeakLines {
+                    start_line: 1,
+                    end_line: 1,
+                    max_count: 1,
+                },
+                CoverageGap::WeakLines {
+                    start_line: 3,
+                    end_line: 3,
+                    max_count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_file_reports_weak_branches() {
+        let file = FileData {
+            filename: "src/lib.rs".to_string(),
+            segments: vec![],
+            branches: vec![
+                make_branch(1, 1, 1, 20),
+                make_branch(2, 1, 20, 20),
+                make_branch(3, 1, 0, 20),
+            ],
+            summary: crate::model::Summary {
+                branches: None,
+                functions: None,
+                instantiations: None,
+                lines: None,
+                regions: None,
+            },
+        };
+        let options = AnalysisOptions {
+            weak_threshold: Some(5),
+            ..AnalysisOptions::default()
+        };
+        let gaps = analyze_file(&file, &BTreeSet::new(), &options);
+        assert_eq!(
+            gaps,
+            vec![
+                CoverageGap::UncoveredBranch {
+                    line: 3,
+                    col: 1,
+                    line_end: 3,
+                    col_end: 2,
+                    true_count: 0,
+                    false_count: 20,
+                    condition: None,
+                },
+                CoverageGap::WeakBranch {
+                    line: 1,
+                    col: 1,
+                    true_count: 1,
+                    false_count: 20,
+                },
+            ]
+        );
+    }
+
+    fn region_gap(line: u64, col_start: u64, col_end: u64) -> CoverageGap {
+        CoverageGap::UncoveredRegion {
+            line_start: line,
+            col_start,
+            line_end: line,
+            col_end,
+        }
+    }
+
+    fn branch_gap(col: u64, col_end: u64, true_count: u64, false_count: u64) -> CoverageGap {
+        CoverageGap::UncoveredBranch {
+            line: 5,
+            col,
+            line_end: 5,
+            col_end,
+            true_count,
+            false_count,
+            condition: None,
+        }
+    }
+
+    /// Code regions of `fn f() { if a { x } else { z }; if b { y } }` on
+    /// line 5: the body, `{ x }`, `{ z }`, the code after the first `if`,
+    /// and `{ y }`.
+    fn if_else_regions() -> BTreeSet<Extent> {
+        BTreeSet::from([
+            ((5, 1), (5, 48)),
+            ((5, 15), (5, 20)),
+            ((5, 26), (5, 31)),
+            ((5, 31), (5, 48)),
+            ((5, 40), (5, 45)),
+        ])
+    }
+
+    #[test]
+    fn test_suppress_region_entered_by_the_untaken_true_direction() {
+        // `a` never true: `{ x }` never ran. `b` never evaluated: `{ y }`
+        // is its own gap.
+        let mut gaps = vec![
+            region_gap(5, 15, 20),
+            region_gap(5, 40, 45),
+            branch_gap(12, 13, 0, 3),
+            branch_gap(37, 38, 0, 0),
+        ];
+        suppress_explained_regions(&mut gaps, &if_else_regions());
+        assert_eq!(
+            gaps,
+            vec![
+                region_gap(5, 40, 45),
+                branch_gap(12, 13, 0, 3),
+                branch_gap(37, 38, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suppress_region_entered_by_the_untaken_false_direction() {
+        // `a` never false: `{ z }` never ran. `b` never true: `{ y }`
+        // never ran either.
+        let mut gaps = vec![
+            region_gap(5, 26, 31),
+            region_gap(5, 40, 45),
+            branch_gap(12, 13, 3, 0),
+            branch_gap(37, 38, 0, 1),
+        ];
+        suppress_explained_regions(&mut gaps, &if_else_regions());
+        assert_eq!(
+            gaps,
+            vec![branch_gap(12, 13, 3, 0), branch_gap(37, 38, 0, 1)]
+        );
+    }
+
+    #[test]
+    fn test_keep_the_region_of_the_other_direction() {
+        // `if a { x }; if b { y }` with `a` never false and `b` never
+        // evaluated: `a`'s false direction enters the code after its `if`,
+        // which ran, so the nearest uncovered region `{ y }` is kept.
+        let regions = BTreeSet::from([
+            ((5, 1), (5, 38)),
+            ((5, 15), (5, 20)),
+            ((5, 20), (5, 38)),
+            ((5, 30), (5, 35)),
+        ]);
+        let mut gaps = vec![region_gap(5, 30, 35), branch_gap(12, 13, 3, 0)];
+        suppress_explained_regions(&mut gaps, &regions);
+        assert_eq!(gaps, vec![region_gap(5, 30, 35), branch_gap(12, 13, 3, 0)]);
+
+        // Without region data, nothing is explained.
+        let mut gaps = vec![region_gap(5, 15, 20), branch_gap(12, 13, 0, 3)];
+        suppress_explained_regions(&mut gaps, &BTreeSet::new());
+        assert_eq!(gaps.len(), 2);
+    }
+
+    #[test]
+    fn test_exclude_lines_splits_ranges() {
+        let gaps = vec![
+            CoverageGap::UncoveredLines {
+                start_line: 1,
+                end_line: 10,
+            },
+            region_gap(5, 1, 4),
+            region_gap(8, 1, 4),
+            branch_gap(5, 20, 3, 0),
+        ];
+        let kept = exclude_lines(gaps, |line| (4..=6).contains(&line));
+        assert_eq!(
+            kept,
+            vec![
+                CoverageGap::UncoveredLines {
+                    start_line: 1,
+                    end_line: 3,
+                },
+                CoverageGap::UncoveredLines {
+                    start_line: 7,
+                    end_line: 10,
+                },
+                region_gap(8, 1, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runs() {
+        assert_eq!(runs(1, 5, |_| false), vec![(1, 5)]);
+        assert_eq!(runs(1, 5, |_| true), vec![]);
+        assert_eq!(runs(1, 5, |l| l % 2 == 0), vec![(1, 1), (3, 3), (5, 5)]);
+    }
+
+    #[test]
+    fn test_analyze_excludes_test_code() {
+        let export = crate::parse_json(include_str!(
+            "../tests/fixtures/show-missing-lines-multi-missing.json"
+        ))
+        .unwrap();
+        let options = AnalysisOptions {
+            exclude_tests: true,
+            read_sources: true,
+            ..AnalysisOptions::default()
+        };
+        let mut sources = SourceCache::new();
+        let source = format!(
+            "{}#[cfg(test)]\nmod tests {{\n    fn a() {{}}\n    fn b() {{}}\n}}\n",
+            "\n".repeat(13)
+        );
+        sources.insert("src/lib.rs", source);
+        let result = analyze_with_sources(&export, &options, &mut sources).unwrap();
+
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].gaps, vec![region_gap(8, 13, 14)]);
+        assert!((result.summary.lines_percent - 100.0).abs() < f64::EPSILON);
+        let lines = result.file_totals[0].totals.lines;
+        assert_eq!(lines.covered, lines.count);
+    }
+
+    #[test]
+    fn test_exclude_tests_without_sources_reads_no_files() {
+        let export = crate::parse_json(include_str!(
+            "../tests/fixtures/show-missing-lines-multi-missing.json"
+        ))
+        .unwrap();
+        let options = AnalysisOptions {
+            exclude_tests: true,
+            read_sources: false,
+            ..AnalysisOptions::default()
+        };
+        // The `#[cfg(test)]` module in the cached source is not read.
+        let mut sources = SourceCache::new();
+        sources.insert(
+            "src/lib.rs",
+            format!("{}#[cfg(test)]\nmod tests {{}}\n", "\n".repeat(13)),
+        );
+        let result = analyze_with_sources(&export, &options, &mut sources).unwrap();
+        let included = analyze_with_options(
+            &export,
+            &AnalysisOptions {
+                read_sources: false,
+                ..AnalysisOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files[0].gaps, included.files[0].gaps);
+    }
+
+    #[test]
+    fn test_analyze_sorts_instantiation_gaps_by_line() {
+        let mut export =
+            crate::parse_json(include_str!("../tests/fixtures/show-missing-lines.json")).unwrap();
+        export.data[0].functions = vec![
+            make_function("parse::<u32>", 4, 5),
+            make_function("parse::<i64>", 0, 5),
+        ];
+        let options = AnalysisOptions {
+            instantiations: true,
+            read_sources: false,
+            ..AnalysisOptions::default()
+        };
+        let result = analyze_with_options(&export, &options).unwrap();
+        let lines: Vec<u64> = result.files[0]
+            .gaps
+            .iter()
+            .map(CoverageGap::start_line)
+            .collect();
+        assert_eq!(lines, vec![5, 7]);
+    }
+
+    #[test]
+    fn test_quote_branch_conditions() {
+        let mut sources = SourceCache::new();
+        sources.insert("src/lib.rs", "fn f(x: u8) {\n    if x > 3 {}\n}\n");
+        let mut gaps = vec![
+            CoverageGap::UncoveredBranch {
+                line: 2,
+                col: 8,
+                line_end: 2,
+                col_end: 13,
+                true_count: 0,
+                false_count: 2,
+                condition: None,
+            },
+            CoverageGap::UncoveredLines {
+                start_line: 3,
+                end_line: 3,
+            },
+        ];
+        quote_branch_conditions("src/lib.rs", &mut gaps, &mut sources);
+        assert!(matches!(
+            &gaps[0],
+            CoverageGap::UncoveredBranch { condition: Some(c), .. } if c == "x > 3"
+        ));
+        assert_eq!(
+            gaps[1],
+            CoverageGap::UncoveredLines {
+                start_line: 3,
+                end_line: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_hottest_lines_orders_by_count() {
+        let file = |name: &str, segments: Vec<Segment>| FileData {
+            filename: name.to_string(),
+            segments,
+            branches: vec![],
+            summary: crate::model::Summary {
+                branches: None,
+                functions: None,
+                instantiations: None,
+                lines: None,
+                regions: None,
+            },
+        };
+        let files = vec![
+            file(
+                "a.rs",
+                vec![
+                    segment(1, 1, 5, (true, true, false)),
+                    segment(1, 9, 0, (false, false, false)),
+                ],
+            ),
+            file(
+                "b.rs",
+                vec![
+                    segment(1, 1, 9, (true, true, false)),
+                    segment(1, 9, 0, (false, false, false)),
+                    segment(2, 1, 5, (true, true, false)),
+                    segment(2, 9, 0, (false, false, false)),
+                ],
+            ),
+        ];
+        // a.rs:1 and b.rs:2 tie; the earlier file wins the last slot.
+        let hot = hottest_lines(&files, 2, &TestCode::default());
+        assert_eq!(
+            hot,
+            vec![
+                HotLine {
+                    filename: "b.rs".to_string(),
+                    line: 1,
+                    count: 9,
+                },
+                HotLine {
+                    filename: "a.rs".to_string(),
+                    line: 1,
+                    count: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relativize_hot_lines() {
+        let mut result = make_result(&[]);
+        result.hot_lines.push(HotLine {
+            filename: "/home/user/project/src/lib.rs".to_string(),
+            line: 1,
+            count: 1,
+        });
+        result.relativize_paths(Path::new("/home/user/project"));
+        assert_eq!(result.hot_lines[0].filename, "./src/lib.rs");
+    }
+
     #[test]
     fn test_collapse_lines_empty() {
         let lines = BTreeSet::new();
@@ -468,10 +2073,9 @@ mod tests {
 
     #[test]
     fn test_analyze_segments_last_segment_has_count() {
-        // Edge case: last segment has has_count=true and is the final segment,
-        // so the fallback (seg.line, seg.col) path is exercised.
-        // Segment 0 covers line 1 with count=1, ending at segment 1 (line 2).
-        // Segment 1 (no has_count) is skipped.
+        // Edge case: the last segment has has_count=true.
+        // Segment 0 covers line 1 with count=1 and wraps into line 2.
+        // Segment 1 (no has_count) ends it, so lines 3-4 are not code.
         // Segment 2 is the last segment with has_count=true on a fresh line.
         let segments = vec![
             Segment {
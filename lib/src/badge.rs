@@ -0,0 +1,339 @@
+//! Coverage badges.
+//!
+//! Renders one metric of a [`CoverageSummary`] as a flat SVG badge in the
+//! style of shields.io, or as a shields.io endpoint JSON for repositories
+//! that do use the service.
+
+use std::str::FromStr;
+
+use serde_json::json;
+
+use crate::analysis::CoverageSummary;
+use crate::format::escape_xml;
+
+/// Summary metric shown on a badge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// Line coverage.
+    #[default]
+    Lines,
+    /// Region coverage.
+    Regions,
+    /// Branch coverage.
+    Branches,
+    /// Function coverage.
+    Functions,
+    /// Instantiation coverage.
+    Instantiations,
+}
+
+impl Metric {
+    /// Returns the metric's percentage in `summary`, if present.
+    #[must_use]
+    pub const fn percent(self, summary: &CoverageSummary) -> Option<f64> {
+        match self {
+            Self::Lines => Some(summary.lines_percent),
+            Self::Regions => Some(summary.regions_percent),
+            Self::Branches => summary.branches_percent,
+            Self::Functions => Some(summary.functions_percent),
+            Self::Instantiations => summary.instantiations_percent,
+        }
+    }
+
+    /// Returns the default badge label for the metric.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Lines => "coverage",
+            Self::Regions => "region coverage",
+            Self::Branches => "branch coverage",
+            Self::Functions => "function coverage",
+            Self::Instantiations => "instantiation coverage",
+        }
+    }
+}
+
+/// Named colors and their shields.io values.
+const NAMED_COLORS: [(&str, &str); 9] = [
+    ("brightgreen", "#4c1"),
+    ("green", "#97ca00"),
+    ("yellowgreen", "#a4a61d"),
+    ("yellow", "#dfb317"),
+    ("orange", "#fe7d37"),
+    ("red", "#e05d44"),
+    ("blue", "#007ec6"),
+    ("grey", "#555"),
+    ("lightgrey", "#9f9f9f"),
+];
+
+/// Color of a badge whose percentage is below every threshold.
+const BELOW_THRESHOLDS: &str = "red";
+
+/// Color of a badge for a metric missing from the summary.
+const UNKNOWN: &str = "lightgrey";
+
+/// Lowest percentage shown in a color, as in `80=yellow`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorThreshold {
+    /// Lowest percentage shown in `color`.
+    pub min_percent: f64,
+    /// A named color such as `brightgreen`, or a hex color such as `#4c1`.
+    pub color: String,
+}
+
+impl ColorThreshold {
+    /// Returns the default thresholds, from green at 90% to orange at 20%.
+    #[must_use]
+    pub fn defaults() -> Vec<Self> {
+        [
+            (90.0, "brightgreen"),
+            (75.0, "green"),
+            (60.0, "yellowgreen"),
+            (40.0, "yellow"),
+            (20.0, "orange"),
+        ]
+        .into_iter()
+        .map(|(min_percent, color)| Self {
+            min_percent,
+            color: color.to_string(),
+        })
+        .collect()
+    }
+}
+
+/// Error parsing a [`ColorThreshold`].
+#[derive(Debug, thiserror::Error)]
+pub enum ThresholdError {
+    /// The threshold is not of the form `PERCENT=COLOR`.
+    #[error("expected PERCENT=COLOR, got `{0}`")]
+    Syntax(String),
+    /// The percentage is not a number from 0 to 100.
+    #[error("invalid percentage `{0}`")]
+    Percent(String),
+    /// The color is neither a known name nor a hex color.
+    #[error("unknown color `{0}`; use a name such as `green` or a hex color such as `#4c1`")]
+    Color(String),
+}
+
+impl FromStr for ColorThreshold {
+    type Err = ThresholdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (percent, color) = s
+            .split_once('=')
+            .ok_or_else(|| ThresholdError::Syntax(s.to_string()))?;
+        let min_percent = percent
+            .trim()
+            .parse()
+            .ok()
+            .filter(|p| (0.0..=100.0).contains(p))
+            .ok_or_else(|| ThresholdError::Percent(percent.to_string()))?;
+        let color = color.trim();
+        if hex_color(color).is_none() {
+            return Err(ThresholdError::Color(color.to_string()));
+        }
+        Ok(Self {
+            min_percent,
+            color: color.to_string(),
+        })
+    }
+}
+
+/// Returns the hex value of a named or hex color.
+fn hex_color(color: &str) -> Option<String> {
+    if let Some((_, hex)) = NAMED_COLORS.iter().find(|(name, _)| *name == color) {
+        return Some((*hex).to_string());
+    }
+    let digits = color.strip_prefix('#').unwrap_or(color);
+    (matches!(digits.len(), 3 | 6) && digits.bytes().all(|b| b.is_ascii_hexdigit()))
+        .then(|| format!("#{digits}"))
+}
+
+/// A coverage badge: a label, a message, and the message's color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Badge {
+    /// Text on the left, e.g. `coverage`.
+    pub label: String,
+    /// Text on the right, e.g. `92.3%`.
+    pub message: String,
+    /// Color of the message, as accepted by [`ColorThreshold`].
+    pub color: String,
+}
+
+impl Badge {
+    /// Creates the badge for `metric`, colored by the highest threshold
+    /// its percentage reaches.
+    ///
+    /// The label defaults to [`Metric::label`]. A metric missing from the
+    /// summary, such as branches without branch data, reads `unknown`.
+    #[must_use]
+    pub fn new(
+        summary: &CoverageSummary,
+        metric: Metric,
+        label: Option<&str>,
+        thresholds: &[ColorThreshold],
+    ) -> Self {
+        let percent = metric.percent(summary);
+        let color = percent.map_or(UNKNOWN, |percent| {
+            thresholds
+                .iter()
+                .filter(|t| percent >= t.min_percent)
+                .max_by(|a, b| a.min_percent.total_cmp(&b.min_percent))
+                .map_or(BELOW_THRESHOLDS, |t| &t.color)
+        });
+        Self {
+            label: label.unwrap_or_else(|| metric.label()).to_string(),
+            message: percent.map_or_else(|| "unknown".to_string(), |p| format!("{p:.1}%")),
+            color: color.to_string(),
+        }
+    }
+
+    /// Renders the badge as a flat SVG image.
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        let label_width = text_width(&self.label) + 10;
+        let message_width = text_width(&self.message) + 10;
+        let width = label_width + message_width;
+        let label_x = f64::from(label_width) / 2.0;
+        let message_x = f64::from(label_width) + f64::from(message_width) / 2.0;
+        let color = hex_color(&self.color).unwrap_or_else(|| "#9f9f9f".to_string());
+        let label = escape_xml(&self.label);
+        let message = escape_xml(&self.message);
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" role=\"img\" aria-label=\"{label}: {message}\">\
+             <title>{label}: {message}</title>\
+             <linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\
+             <clipPath id=\"r\"><rect width=\"{width}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\
+             <g clip-path=\"url(#r)\"><rect width=\"{label_width}\" height=\"20\" fill=\"#555\"/>\
+             <rect x=\"{label_width}\" width=\"{message_width}\" height=\"20\" fill=\"{color}\"/>\
+             <rect width=\"{width}\" height=\"20\" fill=\"url(#s)\"/></g>\
+             <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\
+             <text x=\"{label_x}\" y=\"15\" fill=\"#010101\" fill-opacity=\".3\">{label}</text><text x=\"{label_x}\" y=\"14\">{label}</text>\
+             <text x=\"{message_x}\" y=\"15\" fill=\"#010101\" fill-opacity=\".3\">{message}</text><text x=\"{message_x}\" y=\"14\">{message}</text>\
+             </g></svg>\n"
+        )
+    }
+
+    /// Renders the badge as a shields.io endpoint JSON.
+    #[must_use]
+    pub fn to_endpoint_json(&self) -> String {
+        let color = self.color.strip_prefix('#').unwrap_or(&self.color);
+        let endpoint = json!({
+            "schemaVersion": 1,
+            "label": self.label,
+            "message": self.message,
+            "color": color,
+        });
+        format!("{endpoint:#}\n")
+    }
+}
+
+/// Estimates the width of `text` in pixels in 11px Verdana.
+fn text_width(text: &str) -> u32 {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '!' | '|' | '\'' => 3,
+            'f' | 'r' | 't' | 'I' | '(' | ')' | '[' | ']' | ' ' | '-' => 5,
+            'm' | 'w' | 'M' | 'W' | '%' => 10,
+            'A'..='Z' | '0'..='9' | '#' | '+' | '=' | '?' => 7,
+            _ => 6,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::totals::Totals;
+
+    fn summary(lines_percent: f64) -> CoverageSummary {
+        CoverageSummary {
+            lines_percent,
+            ..Totals::default().summary()
+        }
+    }
+
+    #[test]
+    fn test_badge_colors() {
+        let thresholds = ColorThreshold::defaults();
+        let color = |percent| Badge::new(&summary(percent), Metric::Lines, None, &thresholds).color;
+        assert_eq!(color(100.0), "brightgreen");
+        assert_eq!(color(90.0), "brightgreen");
+        assert_eq!(color(89.9), "green");
+        assert_eq!(color(45.0), "yellow");
+        assert_eq!(color(5.0), "red");
+
+        let custom = vec!["50=#123456".parse().unwrap(), "80=blue".parse().unwrap()];
+        let badge = Badge::new(&summary(85.0), Metric::Lines, Some("cov"), &custom);
+        assert_eq!(
+            badge,
+            Badge {
+                label: "cov".to_string(),
+                message: "85.0%".to_string(),
+                color: "blue".to_string(),
+            }
+        );
+        assert_eq!(
+            Badge::new(&summary(60.0), Metric::Lines, None, &custom).color,
+            "#123456"
+        );
+
+        let branches = Badge::new(&summary(60.0), Metric::Branches, None, &thresholds);
+        assert_eq!(branches.label, "branch coverage");
+        assert_eq!(branches.message, "unknown");
+        assert_eq!(branches.color, "lightgrey");
+    }
+
+    #[test]
+    fn test_parse_threshold() {
+        assert_eq!(
+            "90=brightgreen".parse::<ColorThreshold>().unwrap(),
+            ColorThreshold {
+                min_percent: 90.0,
+                color: "brightgreen".to_string(),
+            }
+        );
+        assert!(matches!(
+            "90".parse::<ColorThreshold>(),
+            Err(ThresholdError::Syntax(_))
+        ));
+        assert!(matches!(
+            "101=red".parse::<ColorThreshold>(),
+            Err(ThresholdError::Percent(_))
+        ));
+        assert!(matches!(
+            "50=\"/><script>".parse::<ColorThreshold>(),
+            Err(ThresholdError::Color(_))
+        ));
+        assert_eq!(hex_color("ABC"), Some("#ABC".to_string()));
+    }
+
+    #[test]
+    fn test_to_svg() {
+        let badge = Badge {
+            label: "a&b".to_string(),
+            message: "92.3%".to_string(),
+            color: "brightgreen".to_string(),
+        };
+        let svg = badge.to_svg();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\""));
+        assert!(svg.contains("<title>a&amp;b: 92.3%</title>"));
+        assert!(svg.contains("fill=\"#4c1\""));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_to_endpoint_json() {
+        let badge = Badge {
+            label: "coverage".to_string(),
+            message: "92.3%".to_string(),
+            color: "#4c1".to_string(),
+        };
+        assert_eq!(
+            badge.to_endpoint_json(),
+            "{\n  \"color\": \"4c1\",\n  \"label\": \"coverage\",\n  \
+             \"message\": \"92.3%\",\n  \"schemaVersion\": 1\n}\n"
+        );
+    }
+}
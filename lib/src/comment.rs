@@ -0,0 +1,351 @@
+//! Pull request comment report.
+//!
+//! Renders an [`AnalysisResult`] as GitHub-flavored markdown for posting on
+//! a pull request: a table of covered and total counts, optionally compared
+//! with a baseline, and one collapsible section of gaps per file, linked to
+//! the uncovered lines. The output is capped to fit comment size limits.
+
+use std::fmt::Write;
+
+use crate::analysis::{AnalysisResult, CoverageGap};
+use crate::format::format_gap;
+use crate::totals::{self, Counts, Totals};
+
+/// Maximum length of a GitHub comment body, in bytes.
+pub const GITHUB_COMMENT_LIMIT: usize = 65_536;
+
+/// Room kept free for the note about gaps left out.
+const NOTE_RESERVE: usize = 128;
+
+/// Options for [`format_pr_comment`].
+#[derive(Debug, Clone)]
+pub struct CommentOptions {
+    /// Totals to compare with, e.g. from the target branch.
+    pub baseline: Option<Totals>,
+    /// URL of a source line, with `{commit}`, `{path}`, and `{line}`
+    /// placeholders, e.g.
+    /// `https://github.com/org/repo/blob/{commit}/{path}#L{line}`.
+    pub link_template: Option<String>,
+    /// Commit to link to; `HEAD` if unknown.
+    pub commit: Option<String>,
+    /// Maximum length of the comment in bytes.
+    pub max_length: usize,
+}
+
+impl Default for CommentOptions {
+    fn default() -> Self {
+        Self {
+            baseline: None,
+            link_template: None,
+            commit: None,
+            max_length: GITHUB_COMMENT_LIMIT,
+        }
+    }
+}
+
+/// Formats an analysis result as a pull request comment.
+///
+/// Gaps that do not fit in `max_length` are left out and counted in a
+/// closing note.
+///
+/// # Output Format
+///
+/// ```text
+/// ## Code Coverage
+///
+/// | Metric | Covered | Total | Coverage | Change |
+/// |---|---:|---:|---:|---:|
+/// | Lines | 120 | 130 | 92.3% | +1.5 |
+/// | Regions | 88 | 100 | 88.0% | -0.4 |
+/// | Functions | 20 | 20 | 100.0% | 0.0 |
+///
+/// ### 2 gaps in 1 file
+///
+/// <details><summary>src/lib.rs (2 gaps)</summary>
+///
+/// - [src/lib.rs:7](https://github.com/org/repo/blob/abc123/src/lib.rs#L7) UNCOVERED
+/// - [src/lib.rs:42:3-42:18](https://github.com/org/repo/blob/abc123/src/lib.rs#L42) REGION hits:0
+///
+/// </details>
+/// ```
+#[must_use]
+pub fn format_pr_comment(result: &AnalysisResult, options: &CommentOptions) -> String {
+    let mut output = String::from("## Code Coverage\n\n");
+    format_totals_table(
+        &mut output,
+        &totals::sum(&result.file_totals),
+        options.baseline.as_ref(),
+    );
+
+    let files: Vec<_> = result.files.iter().filter(|f| !f.gaps.is_empty()).collect();
+    let total_gaps: usize = files.iter().map(|f| f.gaps.len()).sum();
+    if files.is_empty() {
+        output.push_str("\nNo coverage gaps.\n");
+        return output;
+    }
+    let gaps_plural = if total_gaps == 1 { "" } else { "s" };
+    let files_plural = if files.len() == 1 { "" } else { "s" };
+    // writeln to a String is infallible.
+    writeln!(
+        output,
+        "\n### {total_gaps} gap{gaps_plural} in {} file{files_plural}",
+        files.len()
+    )
+    .unwrap();
+
+    let limit = options.max_length.saturating_sub(NOTE_RESERVE);
+    let close = "\n</details>\n";
+    let mut shown = 0;
+    for file in files {
+        let plural = if file.gaps.len() == 1 { "" } else { "s" };
+        let mut section = format!(
+            "\n<details><summary>{} ({} gap{plural})</summary>\n\n",
+            escape_markdown(&file.filename),
+            file.gaps.len()
+        );
+        let mut listed = 0;
+        for gap in &file.gaps {
+            let item = gap_item(&file.filename, gap, options);
+            if output.len() + section.len() + item.len() + close.len() > limit {
+                break;
+            }
+            section.push_str(&item);
+            listed += 1;
+        }
+        if listed > 0 {
+            output.push_str(&section);
+            output.push_str(close);
+            shown += listed;
+        }
+        if listed < file.gaps.len() {
+            break;
+        }
+    }
+    if shown < total_gaps {
+        let hidden = total_gaps - shown;
+        let hidden_plural = if hidden == 1 { "" } else { "s" };
+        writeln!(
+            output,
+            "\n_{hidden} more gap{hidden_plural} not shown; see the full report._"
+        )
+        .unwrap();
+    }
+    output
+}
+
+/// Formats the table of covered and total counts, with the change in
+/// percentage points from `baseline` if given.
+fn format_totals_table(output: &mut String, totals: &Totals, baseline: Option<&Totals>) {
+    let change_header = if baseline.is_some() {
+        (" Change |", "---:|")
+    } else {
+        ("", "")
+    };
+    // writeln to a String is infallible.
+    writeln!(
+        output,
+        "| Metric | Covered | Total | Coverage |{}\n|---|---:|---:|---:|{}",
+        change_header.0, change_header.1
+    )
+    .unwrap();
+    let metrics = [
+        ("Lines", totals.lines, baseline.map(|b| b.lines)),
+        ("Regions", totals.regions, baseline.map(|b| b.regions)),
+        ("Branches", totals.branches, baseline.map(|b| b.branches)),
+        ("Functions", totals.functions, baseline.map(|b| b.functions)),
+    ];
+    for (name, counts, base) in metrics {
+        if counts.count == 0 && base.is_none_or(|b: Counts| b.count == 0) {
+            continue;
+        }
+        write!(
+            output,
+            "| {name} | {} | {} | {:.1}% |",
+            counts.covered,
+            counts.count,
+            counts.percent()
+        )
+        .unwrap();
+        if let Some(base) = base {
+            let change = counts.percent() - base.percent();
+            if change.abs() < 0.05 {
+                output.push_str(" 0.0 |");
+            } else {
+                write!(output, " {change:+.1} |").unwrap();
+            }
+        }
+        output.push('\n');
+    }
+}
+
+/// Formats a gap as a list item, linking its location when a link
+/// template is set.
+fn gap_item(filename: &str, gap: &CoverageGap, options: &CommentOptions) -> String {
+    let mut text = String::new();
+    format_gap(&mut text, filename, gap);
+    let text = text.trim_end();
+    let (location, description) = text.split_once(' ').unwrap_or((text, ""));
+    let location = escape_markdown(location);
+    let description = escape_markdown(description);
+    match &options.link_template {
+        Some(template) => {
+            let url = template
+                .replace("{commit}", options.commit.as_deref().unwrap_or("HEAD"))
+                .replace("{path}", filename.trim_start_matches("./"))
+                .replace("{line}", &gap.start_line().to_string());
+            format!("- [{location}]({url}) {description}\n")
+        }
+        None => format!("- {location} {description}\n"),
+    }
+}
+
+/// Escapes markdown punctuation outside of code spans.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut in_code = false;
+    for c in text.chars() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if !in_code && matches!(c, '\\' | '*' | '_' | '[' | ']' | '<' | '>' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::FileGaps;
+    use crate::totals::FileTotals;
+
+    fn result(files: &[(&str, usize)]) -> AnalysisResult {
+        let totals = Totals {
+            lines: Counts {
+                covered: 9,
+                count: 10,
+            },
+            functions: Counts {
+                covered: 2,
+                count: 2,
+            },
+            ..Totals::default()
+        };
+        AnalysisResult {
+            files: files
+                .iter()
+                .map(|(filename, gaps)| FileGaps {
+                    filename: (*filename).to_string(),
+                    gaps: (1..=*gaps as u64)
+                        .map(|line| CoverageGap::UncoveredLines {
+                            start_line: line * 10,
+                            end_line: line * 10,
+                        })
+                        .collect(),
+                    functions: vec![],
+                })
+                .collect(),
+            summary: totals.summary(),
+            file_totals: vec![FileTotals {
+                filename: "./src/lib.rs".to_string(),
+                totals,
+            }],
+            ..AnalysisResult::default()
+        }
+    }
+
+    #[test]
+    fn test_format_pr_comment() {
+        let options = CommentOptions {
+            baseline: Some(Totals {
+                lines: Counts {
+                    covered: 8,
+                    count: 10,
+                },
+                functions: Counts {
+                    covered: 1,
+                    count: 1,
+                },
+                ..Totals::default()
+            }),
+            link_template: Some("https://example.com/blob/{commit}/{path}#L{line}".to_string()),
+            commit: Some("abc123".to_string()),
+            ..CommentOptions::default()
+        };
+        assert_eq!(
+            format_pr_comment(&result(&[("./src/my_lib.rs", 1)]), &options),
+            "## Code Coverage\n\n\
+             | Metric | Covered | Total | Coverage | Change |\n\
+             |---|---:|---:|---:|---:|\n\
+             | Lines | 9 | 10 | 90.0% | +10.0 |\n\
+             | Functions | 2 | 2 | 100.0% | 0.0 |\n\
+             \n### 1 gap in 1 file\n\
+             \n<details><summary>./src/my\\_lib.rs (1 gap)</summary>\n\n\
+             - [./src/my\\_lib.rs:10](https://example.com/blob/abc123/src/my_lib.rs#L10) UNCOVERED\n\
+             \n</details>\n"
+        );
+    }
+
+    #[test]
+    fn test_format_pr_comment_without_baseline_or_links() {
+        let output = format_pr_comment(
+            &result(&[("src/a.rs", 2), ("src/b.rs", 1)]),
+            &CommentOptions::default(),
+        );
+        assert!(output.contains("| Metric | Covered | Total | Coverage |\n|---|---:|---:|---:|\n"));
+        assert!(output.contains("### 3 gaps in 2 files\n"));
+        assert!(output.contains("- src/a.rs:10 UNCOVERED\n- src/a.rs:20 UNCOVERED\n"));
+        assert!(!output.contains("not shown"));
+
+        let output = format_pr_comment(&result(&[]), &CommentOptions::default());
+        assert!(output.ends_with("\nNo coverage gaps.\n"));
+    }
+
+    #[test]
+    fn test_format_pr_comment_caps_length() {
+        let result = result(&[("src/a.rs", 200), ("src/b.rs", 200)]);
+        let full = format_pr_comment(&result, &CommentOptions::default());
+        let options = CommentOptions {
+            max_length: full.len() / 2,
+            ..CommentOptions::default()
+        };
+        let capped = format_pr_comment(&result, &options);
+        assert!(capped.len() <= options.max_length);
+        assert!(capped.contains("src/a.rs (200 gaps)"));
+        assert!(!capped.contains("src/b.rs"));
+        assert_eq!(
+            capped.matches("<details>").count(),
+            capped.matches("</details>").count()
+        );
+        let shown = capped.matches("\n- ").count();
+        assert!(capped.ends_with(&format!(
+            "\n_{} more gaps not shown; see the full report._\n",
+            400 - shown
+        )));
+    }
+
+    #[test]
+    fn test_format_pr_comment_note_for_one_gap() {
+        // Room for every gap but the last.
+        let two = result(&[("src/a.rs", 1), ("src/b.rs", 1)]);
+        let full = format_pr_comment(&two, &CommentOptions::default());
+        let options = CommentOptions {
+            max_length: full.len() + NOTE_RESERVE - 1,
+            ..CommentOptions::default()
+        };
+        assert!(
+            format_pr_comment(&two, &options)
+                .ends_with("\n_1 more gap not shown; see the full report._\n")
+        );
+    }
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!(
+            escape_markdown("a_b BRANCH `x_y < 1` [<T>]"),
+            "a\\_b BRANCH `x_y < 1` \\[\\<T\\>\\]"
+        );
+    }
+}
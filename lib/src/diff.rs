@@ -0,0 +1,204 @@
+//! Gap differences between two analyses.
+//!
+//! Compares the gaps of two runs over the same workspace, such as
+//! consecutive iterations of watch mode, to show which gaps new tests
+//! closed and which new code opened. Gaps are matched by kind, enclosing
+//! function, and position relative to the start of that function, so a gap
+//! that only moved because lines were added above its function is the same
+//! gap.
+
+use std::collections::BTreeMap;
+
+use crate::analysis::{AnalysisResult, CoverageGap, FileGaps};
+
+/// A gap that appeared or disappeared between two analyses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GapChange<'a> {
+    /// File path of the gap.
+    pub filename: &'a str,
+    /// The gap itself.
+    pub gap: &'a CoverageGap,
+}
+
+/// The gaps that differ between two analyses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GapDiff<'a> {
+    /// Gaps in the previous analysis that are gone, in file and location
+    /// order.
+    pub closed: Vec<GapChange<'a>>,
+    /// Gaps that were not in the previous analysis, in file and location
+    /// order.
+    pub new: Vec<GapChange<'a>>,
+}
+
+impl GapDiff<'_> {
+    /// Returns whether no gap changed.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.closed.is_empty() && self.new.is_empty()
+    }
+}
+
+impl AnalysisResult {
+    /// Compares this result's gaps with those of an earlier `previous`
+    /// result.
+    #[must_use]
+    pub fn diff_gaps<'a>(&'a self, previous: &'a Self) -> GapDiff<'a> {
+        let before = anchored_gaps(previous);
+        let after = anchored_gaps(self);
+        GapDiff {
+            closed: missing_from(&before, &after),
+            new: missing_from(&after, &before),
+        }
+    }
+}
+
+/// Where a gap sits: its file, kind, enclosing function, and lines and
+/// column relative to the start of that function.
+type Anchor<'a> = (&'a str, &'static str, Option<&'a str>, u64, u64, u64);
+
+/// Returns the gaps of `gaps` whose anchor is not in `other`, in file and
+/// location order.
+fn missing_from<'a>(
+    gaps: &BTreeMap<Anchor<'a>, GapChange<'a>>,
+    other: &BTreeMap<Anchor<'a>, GapChange<'a>>,
+) -> Vec<GapChange<'a>> {
+    let mut missing: Vec<GapChange<'a>> = gaps
+        .iter()
+        .filter(|(anchor, _)| !other.contains_key(*anchor))
+        .map(|(_, change)| *change)
+        .collect();
+    missing.sort_by_key(|change| (change.filename, change.gap.start_line()));
+    missing
+}
+
+/// Collects the gaps of `result` by their anchor.
+fn anchored_gaps(result: &AnalysisResult) -> BTreeMap<Anchor<'_>, GapChange<'_>> {
+    result
+        .files
+        .iter()
+        .flat_map(|file| {
+            file.gaps.iter().map(move |gap| {
+                let change = GapChange {
+                    filename: &file.filename,
+                    gap,
+                };
+                (anchor(file, gap), change)
+            })
+        })
+        .collect()
+}
+
+/// Returns the anchor of `gap` in `file`.
+fn anchor<'a>(file: &'a FileGaps, gap: &CoverageGap) -> Anchor<'a> {
+    let function = file.function_at(gap.start_line());
+    let base = function.map_or(0, |f| f.line_start);
+    let (kind, col) = match gap {
+        CoverageGap::UncoveredLines { .. } => ("lines", 0),
+        CoverageGap::UncoveredRegion { col_start, .. } => ("region", *col_start),
+        CoverageGap::UncoveredBranch { col, .. } => ("branch", *col),
+        CoverageGap::UncoveredInstantiations { col, .. } => ("instantiations", *col),
+        CoverageGap::WeakLines { .. } => ("weak lines", 0),
+        CoverageGap::WeakRegion { col_start, .. } => ("weak region", *col_start),
+        CoverageGap::WeakBranch { col, .. } => ("weak branch", *col),
+    };
+    (
+        &file.filename,
+        kind,
+        function.map(|f| f.name.as_str()),
+        gap.start_line() - base,
+        gap.end_line() - base,
+        col,
+    )
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::FunctionSpan;
+
+    fn result(files: &[(&str, Vec<CoverageGap>)]) -> AnalysisResult {
+        AnalysisResult {
+            files: files
+                .iter()
+                .map(|(filename, gaps)| FileGaps {
+                    filename: (*filename).to_string(),
+                    gaps: gaps.clone(),
+                    functions: vec![],
+                })
+                .collect(),
+            ..AnalysisResult::default()
+        }
+    }
+
+    fn lines(start_line: u64, end_line: u64) -> CoverageGap {
+        CoverageGap::UncoveredLines {
+            start_line,
+            end_line,
+        }
+    }
+
+    #[test]
+    fn test_diff_gaps() {
+        let previous = result(&[
+            ("src/a.rs", vec![lines(1, 2), lines(8, 9)]),
+            ("src/b.rs", vec![lines(3, 3)]),
+        ]);
+        let current = result(&[
+            ("src/a.rs", vec![lines(8, 9), lines(12, 12)]),
+            ("src/c.rs", vec![lines(1, 1)]),
+        ]);
+        let diff = current.diff_gaps(&previous);
+        let changes = |changes: &[GapChange<'_>]| -> Vec<(String, u64)> {
+            changes
+                .iter()
+                .map(|c| (c.filename.to_string(), c.gap.start_line()))
+                .collect()
+        };
+        assert_eq!(
+            changes(&diff.closed),
+            vec![("src/a.rs".to_string(), 1), ("src/b.rs".to_string(), 3)]
+        );
+        assert_eq!(
+            changes(&diff.new),
+            vec![("src/a.rs".to_string(), 12), ("src/c.rs".to_string(), 1)]
+        );
+        assert!(current.diff_gaps(&current).is_empty());
+    }
+
+    #[test]
+    fn test_diff_gaps_follows_moved_functions() {
+        let function = |name: &str, line_start: u64, line_end: u64| FunctionSpan {
+            name: name.to_string(),
+            line_start,
+            col_start: 1,
+            line_end,
+            col_end: 2,
+            count: 1,
+            visibility: None,
+        };
+        let file = |offset: u64, gaps| FileGaps {
+            filename: "src/a.rs".to_string(),
+            gaps,
+            functions: vec![function("a::f", 1 + offset, 10 + offset)],
+        };
+        let previous = AnalysisResult {
+            files: vec![file(0, vec![lines(4, 5)])],
+            ..AnalysisResult::default()
+        };
+        // Three lines were added above `f`, and the gap grew by a line.
+        let moved = AnalysisResult {
+            files: vec![file(3, vec![lines(7, 8)])],
+            ..AnalysisResult::default()
+        };
+        assert!(moved.diff_gaps(&previous).is_empty());
+        let grown = AnalysisResult {
+            files: vec![file(3, vec![lines(7, 9)])],
+            ..AnalysisResult::default()
+        };
+        let diff = grown.diff_gaps(&previous);
+        assert_eq!(diff.closed.len(), 1);
+        assert_eq!(diff.new[0].gap, &lines(7, 9));
+    }
+}
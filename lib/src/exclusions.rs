@@ -0,0 +1,312 @@
+//! Audit of code excluded from instrumentation with `coverage(off)`.
+//!
+//! Finds items marked `#[coverage(off)]` or
+//! `#[cfg_attr(<predicate>, coverage(off))]` by parsing sources, and checks
+//! whether the coverage data still holds functions inside them. That
+//! happens when the `cfg_attr` predicate did not hold for the build (for
+//! example `coverage_nightly` on a stable toolchain), so the exclusion
+//! silently did nothing.
+//!
+//! A file whose items are all excluded has no coverage data at all, so the
+//! audit also reads the other Rust files in the `src` directory of each
+//! package found in the coverage data.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use syn::punctuated::Punctuated;
+use syn::{Attribute, ImplItem, Item, Meta, Token, Type};
+
+use crate::analysis::{InstantiationKey, group_instantiations};
+use crate::model::ExportData;
+use crate::source::SourceCache;
+use crate::test_code::{item_attrs, line_range, package_root};
+
+/// An item excluded from instrumentation with `coverage(off)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exclusion {
+    /// File path as it appears in the coverage data.
+    pub filename: String,
+    /// Item name: a function name, `mod name`, `impl Type`, or `file` for a
+    /// crate-level `#![coverage(off)]`.
+    pub name: String,
+    /// First line of the item, including its attributes.
+    pub line_start: u64,
+    /// Last line of the item.
+    pub line_end: u64,
+    /// Highest execution count among functions that are still instrumented
+    /// inside the item, if there are any.
+    pub instrumented: Option<u64>,
+}
+
+/// An excluded item found in a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludedItem {
+    /// Item name, as in [`Exclusion::name`].
+    pub name: String,
+    /// First line of the item, including its attributes.
+    pub line_start: u64,
+    /// Last line of the item.
+    pub line_end: u64,
+}
+
+/// Finds the outermost items marked `coverage(off)` in `source`.
+///
+/// Items nested inside an excluded item are not listed separately. Returns
+/// no items if the source does not parse.
+#[must_use]
+pub fn excluded_items(source: &str) -> Vec<ExcludedItem> {
+    let mut found = Vec::new();
+    let Ok(file) = syn::parse_file(source) else {
+        return found;
+    };
+    if is_coverage_off(&file.attrs) {
+        found.push(ExcludedItem {
+            name: "file".to_string(),
+            line_start: 1,
+            line_end: u64::MAX,
+        });
+    } else {
+        collect_items(&file.items, &mut found);
+    }
+    found
+}
+
+/// Audits the `coverage(off)` items of every file in the coverage data and
+/// of the other sources of its packages.
+///
+/// Files whose source cannot be read are skipped.
+pub(crate) fn audit(data: &ExportData, sources: &mut SourceCache) -> Vec<Exclusion> {
+    let groups = group_instantiations(&data.functions);
+    let mut exclusions = Vec::new();
+    for path in package_sources(data) {
+        let Some(source) = sources.get(&path) else {
+            continue;
+        };
+        for item in excluded_items(source) {
+            let instrumented = groups
+                .iter()
+                .filter(|(InstantiationKey { filename, line, .. }, _)| {
+                    *filename == path && (item.line_start..=item.line_end).contains(line)
+                })
+                .flat_map(|(_, group)| group.iter().map(|f| f.count))
+                .max();
+            exclusions.push(Exclusion {
+                filename: path.clone(),
+                name: item.name,
+                line_start: item.line_start,
+                line_end: item.line_end,
+                instrumented,
+            });
+        }
+    }
+    exclusions
+}
+
+/// Returns the files in the coverage data and the Rust files under the
+/// `src` directory of their packages, with paths in the style of the
+/// coverage data.
+fn package_sources(data: &ExportData) -> BTreeSet<String> {
+    let mut filenames: BTreeSet<String> = data.files.iter().map(|f| f.filename.clone()).collect();
+    let roots: BTreeSet<&Path> = data
+        .files
+        .iter()
+        .filter_map(|f| package_root(Path::new(&f.filename)))
+        .collect();
+    for root in roots {
+        let mut found = Vec::new();
+        rust_files(&root.join("src"), &mut found);
+        filenames.extend(found.iter().map(|p| p.to_string_lossy().into_owned()));
+    }
+    filenames
+}
+
+/// Collects the `.rs` files under `dir`, skipping unreadable directories.
+fn rust_files(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            rust_files(&path, found);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            found.push(path);
+        }
+    }
+}
+
+/// Records excluded items among `items`, descending into inline modules
+/// and impl blocks that are not excluded themselves.
+fn collect_items(items: &[Item], found: &mut Vec<ExcludedItem>) {
+    for item in items {
+        if is_coverage_off(item_attrs(item)) {
+            let (line_start, line_end) = line_range(item);
+            found.push(ExcludedItem {
+                name: item_name(item),
+                line_start,
+                line_end,
+            });
+            continue;
+        }
+        match item {
+            Item::Mod(module) => {
+                if let Some((_, content)) = &module.content {
+                    collect_items(content, found);
+                }
+            }
+            Item::Impl(block) => {
+                for impl_item in &block.items {
+                    if let ImplItem::Fn(function) = impl_item
+                        && is_coverage_off(&function.attrs)
+                    {
+                        let (line_start, line_end) = line_range(function);
+                        found.push(ExcludedItem {
+                            name: function.sig.ident.to_string(),
+                            line_start,
+                            line_end,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns a short display name for an excluded item.
+fn item_name(item: &Item) -> String {
+    match item {
+        Item::Fn(function) => function.sig.ident.to_string(),
+        Item::Mod(module) => format!("mod {}", module.ident),
+        Item::Impl(block) => match &*block.self_ty {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .map_or_else(|| "impl".to_string(), |s| format!("impl {}", s.ident)),
+            _ => "impl".to_string(),
+        },
+        Item::Trait(tr) => format!("trait {}", tr.ident),
+        _ => "item".to_string(),
+    }
+}
+
+/// Returns whether the attributes contain `coverage(off)`, directly or
+/// inside a `cfg_attr`.
+pub(crate) fn is_coverage_off(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        if attr.path().is_ident("cfg_attr") {
+            attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .is_ok_and(|metas| metas.iter().skip(1).any(is_coverage_off_meta))
+        } else {
+            is_coverage_off_meta(&attr.meta)
+        }
+    })
+}
+
+/// Returns whether `meta` is `coverage(off)`.
+fn is_coverage_off_meta(meta: &Meta) -> bool {
+    match meta {
+        Meta::List(list) => list.path.is_ident("coverage") && list.tokens.to_string() == "off",
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, line_start: u64, line_end: u64) -> ExcludedItem {
+        ExcludedItem {
+            name: name.to_string(),
+            line_start,
+            line_end,
+        }
+    }
+
+    #[test]
+    fn test_direct_and_cfg_attr_exclusions() {
+        let source = "#[coverage(off)]\nfn a() {}\n\
+                      #[cfg_attr(coverage_nightly, coverage(off))]\nfn b() {}\n\
+                      #[cfg_attr(coverage_nightly, coverage(on))]\nfn c() {}\n\
+                      fn d() {}\n";
+        assert_eq!(
+            excluded_items(source),
+            vec![item("a", 1, 2), item("b", 3, 4)]
+        );
+    }
+
+    #[test]
+    fn test_nested_items_are_not_listed_twice() {
+        let source = "#[cfg(test)]\n#[cfg_attr(coverage_nightly, coverage(off))]\nmod tests {\n\
+                      #[coverage(off)]\nfn helper() {}\n}\n\
+                      mod inner {\n    #[coverage(off)]\n    fn f() {}\n}\n\
+                      impl Foo {\n    #[coverage(off)]\n    fn g() {}\n}\n";
+        assert_eq!(
+            excluded_items(source),
+            vec![item("mod tests", 1, 6), item("f", 8, 9), item("g", 12, 13)]
+        );
+    }
+
+    #[test]
+    fn test_impl_and_file_exclusions() {
+        let source = "#[coverage(off)]\nimpl Display for Foo {}\n";
+        assert_eq!(excluded_items(source), vec![item("impl Foo", 1, 2)]);
+        let source = "#![cfg_attr(coverage_nightly, coverage(off))]\nfn a() {}\n";
+        assert_eq!(excluded_items(source), vec![item("file", 1, u64::MAX)]);
+    }
+
+    #[test]
+    fn test_unparsable_source_has_no_items() {
+        assert!(excluded_items("fn (").is_empty());
+    }
+
+    #[test]
+    fn test_audit_flags_instrumented_exclusions() {
+        let data = crate::parse_json(include_str!(
+            "../tests/fixtures/show-missing-lines-multi-missing.json"
+        ))
+        .unwrap()
+        .data
+        .remove(0);
+        let mut sources = SourceCache::new();
+        // Excludes lines 1-3 and 12-14: the fixture has instrumented
+        // functions in the first range but none in the second.
+        let source = format!(
+            "#[coverage(off)]\nmod a {{\n}}\n{}#[coverage(off)]\nmod b {{\n}}\n",
+            "\n".repeat(8)
+        );
+        sources.insert("src/lib.rs", source);
+        let exclusions: Vec<_> = audit(&data, &mut sources)
+            .into_iter()
+            .filter(|e| e.filename == "src/lib.rs")
+            .collect();
+        assert_eq!(exclusions.len(), 2);
+        assert_eq!(exclusions[0].name, "mod a");
+        assert!(exclusions[0].instrumented.is_some());
+        assert_eq!((exclusions[1].line_start, exclusions[1].line_end), (12, 14));
+        assert_eq!(exclusions[1].instrumented, None);
+    }
+
+    #[test]
+    fn test_audit_reads_files_without_coverage_data() {
+        let root = std::env::temp_dir().join(format!("llvm-cov-easy-{}-audit", std::process::id()));
+        std::fs::create_dir_all(root.join("src/off")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+        let lib = root.join("src/lib.rs").to_string_lossy().into_owned();
+        let off = root.join("src/off/mod.rs").to_string_lossy().into_owned();
+        std::fs::write(&lib, "fn a() {}\n").unwrap();
+        std::fs::write(&off, "#![coverage(off)]\nfn b() {}\n").unwrap();
+        let json = include_str!("../tests/fixtures/show-missing-lines-multi-missing.json")
+            .replace("\"src/lib.rs\"", &format!("{lib:?}"));
+        let data = crate::parse_json(&json).unwrap().data.remove(0);
+        let exclusions = audit(&data, &mut SourceCache::new());
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(exclusions.len(), 1);
+        assert_eq!(exclusions[0].filename, off);
+        assert_eq!(exclusions[0].name, "file");
+        assert_eq!(exclusions[0].instrumented, None);
+    }
+}
@@ -4,7 +4,37 @@
 
 use std::fmt::Write;
 
-use crate::analysis::{AnalysisResult, CoverageGap, CoverageSummary};
+use crate::analysis::{AnalysisResult, CoverageGap, CoverageSummary, HotLine};
+use crate::diff::{GapChange, GapDiff};
+use crate::exclusions::Exclusion;
+use crate::history::{HistoryEntry, declining_files, format_timestamp};
+use crate::per_test::TestIndex;
+use crate::public_api::ApiFunction;
+use crate::score::ScoreContext;
+use crate::workspace::Rollup;
+
+pub mod template;
+
+/// Order in which gaps are listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GapOrder {
+    /// Group gaps by file, in location order.
+    #[default]
+    File,
+    /// List the highest-priority gaps first, across all files.
+    Priority,
+}
+
+/// Options controlling the compact text output.
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Order in which gaps are listed.
+    pub order: GapOrder,
+    /// Inputs for priority scoring, used with [`GapOrder::Priority`].
+    pub score_context: ScoreContext,
+    /// Print each file path once, followed by its gaps in short codes.
+    pub grouped: bool,
+}
 
 /// Formats an analysis result as compact, agent-friendly markdown.
 ///
@@ -17,67 +47,780 @@ use crate::analysis::{AnalysisResult, CoverageGap, CoverageSummary};
 /// ./src/lib.rs:8-9 UNCOVERED
 /// ./src/lib.rs:42:3-42:18 REGION hits:0
 /// ./src/lib.rs:50:5 BRANCH true:5 false:0
+/// ./src/lib.rs:55:8 BRANCH `x > limit` never false
+/// ./src/lib.rs:61:1 INSTANTIATIONS executed:[parse::<u32>] unexecuted:[parse::<i64>]
+/// ./src/lib.rs:70-72 WEAK hits:1
+/// ./src/lib.rs:75:9-75:20 WEAK REGION hits:2
+/// ./src/lib.rs:80:8 WEAK BRANCH true:1 false:40
+///
+/// ## Hottest Lines
+/// ./src/lib.rs:12 hits:1043
+///
+/// ## Crate Coverage
+/// core Lines: 95.0% | Regions: 90.2% | Functions: 100.0%
+/// core/src/parser Lines: 81.5% | Regions: 77.0% | Functions: 90.0%
+///
+/// ## Coverage Exclusions
+/// Excluded items: 12 (1 still instrumented)
+/// ./src/main.rs:210 STILL INSTRUMENTED run_cargo_llvm_cov hits:0
 ///
 /// ## Coverage Summary
 /// Lines: 92.3% | Regions: 88.1% | Branches: 75.0% | Functions: 100.0%
 /// ```
 #[must_use]
 pub fn format_result(result: &AnalysisResult) -> String {
+    format_result_with(result, &FormatOptions::default())
+}
+
+/// Formats an analysis result like [`format_result`], using `options`.
+///
+/// With [`GapOrder::Priority`], the gap list is headed
+/// `## Missed Coverage Areas (by priority)` and ordered by
+/// [`AnalysisResult::ranked_gaps`].
+///
+/// With [`FormatOptions::grouped`], each file path is printed once, above
+/// its gaps in short codes, after a legend of the codes used. In priority
+/// order, a file is printed again whenever its gaps resume.
+///
+/// ```text
+/// ## Missed Coverage Areas
+/// Legend: U=uncovered lines R=uncovered region B=branch t/f=true/false hits
+/// ./src/lib.rs
+///   7-9 U
+///   42:3-18 R
+///   50:5 B t5 f0
+///   55:8 B t3 f0 `x > limit`
+/// ```
+#[must_use]
+pub fn format_result_with(result: &AnalysisResult, options: &FormatOptions) -> String {
     let mut output = String::from("# Code Coverage\n");
     let has_gaps = result.files.iter().any(|f| !f.gaps.is_empty());
 
     if has_gaps {
-        output.push_str("\n## Missed Coverage Areas\n");
-        for file in &result.files {
-            for gap in &file.gaps {
-                format_gap(&mut output, &file.filename, gap);
+        let gaps: Vec<(&str, &CoverageGap)> = match options.order {
+            GapOrder::File => {
+                output.push_str("\n## Missed Coverage Areas\n");
+                result
+                    .files
+                    .iter()
+                    .flat_map(|file| file.gaps.iter().map(|gap| (file.filename.as_str(), gap)))
+                    .collect()
+            }
+            GapOrder::Priority => {
+                output.push_str("\n## Missed Coverage Areas (by priority)\n");
+                result
+                    .ranked_gaps(&options.score_context)
+                    .into_iter()
+                    .map(|ranked| (ranked.filename, ranked.gap))
+                    .collect()
+            }
+        };
+        if options.grouped {
+            format_grouped_gaps(&mut output, &gaps);
+        } else {
+            for (filename, gap) in gaps {
+                format_gap(&mut output, filename, gap);
             }
         }
     }
 
+    if !result.hot_lines.is_empty() {
+        output.push_str("\n## Hottest Lines\n");
+        format_hot_lines(&mut output, &result.hot_lines);
+    }
+
+    if !result.rollups.is_empty() {
+        output.push_str("\n## Crate Coverage\n");
+        format_rollups(&mut output, &result.rollups);
+    }
+
+    if !result.exclusions.is_empty() {
+        output.push_str("\n## Coverage Exclusions\n");
+        format_exclusions(&mut output, &result.exclusions);
+    }
+
+    if !result.public_api.is_empty() {
+        output.push_str("\n## Public API\n");
+        format_public_api(&mut output, &result.public_api);
+    }
+
     output.push_str("\n## Coverage Summary\n");
     format_summary(&mut output, &result.summary);
     output
 }
 
-/// Formats a single coverage gap into the output buffer.
-fn format_gap(output: &mut String, filename: &str, gap: &CoverageGap) {
+/// Formats each gap as a GCC-style `path:line:col: message` line.
+///
+/// Vim's quickfix list, Emacs' `compilation-mode`, and VS Code problem
+/// matchers parse this format, unlike the ranges of [`format_result`].
+/// Gaps without a column start at column 1. No summary is included.
+///
+/// # Output Format
+///
+/// ```text
+/// ./src/lib.rs:7:1: uncovered line
+/// ./src/lib.rs:8:1: uncovered lines 8-9
+/// ./src/lib.rs:42:3: uncovered region to 42:18
+/// ./src/lib.rs:50:5: branch never false (true:5 false:0)
+/// ./src/lib.rs:55:8: branch `x > limit` never false
+/// ./src/lib.rs:61:1: unexecuted instantiations: parse::<i64>
+/// ./src/lib.rs:70:1: weakly covered lines 70-72 (hits:1)
+/// ./src/lib.rs:75:9: weakly covered region to 75:20 (hits:2)
+/// ./src/lib.rs:80:8: weakly covered branch (true:1 false:40)
+/// ```
+#[must_use]
+pub fn format_quickfix(result: &AnalysisResult) -> String {
+    let mut output = String::new();
+    for file in &result.files {
+        for gap in &file.gaps {
+            let (line, col, message) = quickfix_entry(gap);
+            // writeln to a String is infallible.
+            writeln!(output, "{}:{line}:{col}: {message}", file.filename).unwrap();
+        }
+    }
+    output
+}
+
+/// Returns the line, column, and message of a gap's quickfix entry.
+pub(crate) fn quickfix_entry(gap: &CoverageGap) -> (u64, u64, String) {
+    let lines = |start_line: u64, end_line: u64| {
+        if start_line == end_line {
+            "line".to_string()
+        } else {
+            format!("lines {start_line}-{end_line}")
+        }
+    };
     match gap {
         CoverageGap::UncoveredLines {
             start_line,
             end_line,
+        } => (
+            *start_line,
+            1,
+            format!("uncovered {}", lines(*start_line, *end_line)),
+        ),
+        CoverageGap::UncoveredRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+        } => (
+            *line_start,
+            *col_start,
+            format!("uncovered region to {line_end}:{col_end}"),
+        ),
+        CoverageGap::UncoveredBranch {
+            line,
+            col,
+            true_count,
+            false_count,
+            condition,
+            ..
         } => {
-            if start_line == end_line {
-                writeln!(output, "{filename}:{start_line} UNCOVERED")
-            } else {
-                writeln!(output, "{filename}:{start_line}-{end_line} UNCOVERED")
+            let missing = missing_direction(*true_count, *false_count);
+            let message = match condition {
+                Some(condition) => format!("branch `{condition}` {missing}"),
+                None => format!("branch {missing} (true:{true_count} false:{false_count})"),
+            };
+            (*line, *col, message)
+        }
+        CoverageGap::UncoveredInstantiations {
+            line,
+            col,
+            unexecuted,
+            ..
+        } => (
+            *line,
+            *col,
+            format!("unexecuted instantiations: {}", unexecuted.join(", ")),
+        ),
+        CoverageGap::WeakLines {
+            start_line,
+            end_line,
+            max_count,
+        } => (
+            *start_line,
+            1,
+            format!(
+                "weakly covered {} (hits:{max_count})",
+                lines(*start_line, *end_line)
+            ),
+        ),
+        CoverageGap::WeakRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+            count,
+        } => (
+            *line_start,
+            *col_start,
+            format!("weakly covered region to {line_end}:{col_end} (hits:{count})"),
+        ),
+        CoverageGap::WeakBranch {
+            line,
+            col,
+            true_count,
+            false_count,
+        } => (
+            *line,
+            *col,
+            format!("weakly covered branch (true:{true_count} false:{false_count})"),
+        ),
+    }
+}
+
+/// Formats the gaps that changed since a previous analysis, followed by the
+/// current coverage summary.
+///
+/// # Output Format
+///
+/// ```text
+/// # Coverage Changes
+/// Closed 3 gaps, 1 new
+///
+/// ## New Gaps
+/// src/lib.rs:42 UNCOVERED
+///
+/// ## Closed Gaps
+/// src/lib.rs:10-12 UNCOVERED
+/// src/lib.rs:20:5-20:30 REGION hits:0
+/// src/main.rs:7 UNCOVERED
+///
+/// ## Coverage Summary
+/// Lines: 92.3% | Regions: 88.1% | Functions: 100.0%
+/// ```
+#[must_use]
+pub fn format_gap_diff(diff: &GapDiff<'_>, summary: &CoverageSummary) -> String {
+    let mut output = String::from("# Coverage Changes\n");
+    let plural = if diff.closed.len() == 1 { "" } else { "s" };
+    // writeln to a String is infallible.
+    writeln!(
+        output,
+        "Closed {} gap{plural}, {} new",
+        diff.closed.len(),
+        diff.new.len()
+    )
+    .unwrap();
+    for (heading, changes) in [("New Gaps", &diff.new), ("Closed Gaps", &diff.closed)] {
+        if !changes.is_empty() {
+            writeln!(output, "\n## {heading}").unwrap();
+            for GapChange { filename, gap } in changes {
+                format_gap(&mut output, filename, gap);
+            }
+        }
+    }
+    output.push_str("\n## Coverage Summary\n");
+    format_summary(&mut output, summary);
+    output
+}
+
+/// Formats a summary of a per-test coverage index.
+///
+/// Lists the lines only one test covers, which would be gaps without that
+/// test, and the tests that cover nothing other tests do not. When there are
+/// any, also lists the tests whose coverage another test strictly
+/// contains, the tests that cover exactly what an earlier test covers (one
+/// of each such group is kept), and a smaller set of tests with the same
+/// total coverage; otherwise every test is needed.
+///
+/// # Output Format
+///
+/// ```text
+/// # Per-Test Coverage
+/// Tests: 4 | Files: 2
+///
+/// ## Gaps Closed Per Test
+/// my-crate tests::parses CLOSES src/parse.rs:12-14,20
+/// my-crate tests::renders CLOSES src/render.rs:7
+///
+/// ## Tests Without Unique Coverage
+/// my-crate tests::parses_empty
+/// my-crate tests::parses_twice
+///
+/// ## Redundant Tests
+/// my-crate tests::parses_empty SUBSET OF my-crate tests::parses
+///
+/// ## Duplicate Tests
+/// my-crate tests::parses_twice SAME AS my-crate tests::parses
+///
+/// ## Minimal Test Set
+/// Tests: 2/4
+/// my-crate tests::parses
+/// my-crate tests::renders
+/// ```
+#[must_use]
+pub fn format_test_index(index: &TestIndex) -> String {
+    let mut output = String::from("# Per-Test Coverage\n");
+    // writeln to a String is infallible.
+    writeln!(
+        output,
+        "Tests: {} | Files: {}",
+        index.tests.len(),
+        index.files.len()
+    )
+    .unwrap();
+    let closed = index.closed_gaps();
+    if !closed.is_empty() {
+        output.push_str("\n## Gaps Closed Per Test\n");
+        for gaps in closed {
+            let ranges: Vec<String> = gaps
+                .lines
+                .iter()
+                .map(|&(start, end)| lines(start, end))
+                .collect();
+            writeln!(
+                output,
+                "{} CLOSES {}:{}",
+                gaps.test,
+                gaps.filename,
+                ranges.join(",")
+            )
+            .unwrap();
+        }
+    }
+    let redundant = index.without_unique_coverage();
+    if !redundant.is_empty() {
+        output.push_str("\n## Tests Without Unique Coverage\n");
+        for test in redundant {
+            output.push_str(test);
+            output.push('\n');
+        }
+        let redundant = index.redundant();
+        if !redundant.is_empty() {
+            output.push_str("\n## Redundant Tests\n");
+            for redundancy in redundant {
+                writeln!(
+                    output,
+                    "{} SUBSET OF {}",
+                    redundancy.test, redundancy.covered_by
+                )
+                .unwrap();
+            }
+        }
+        let duplicates = index.duplicates();
+        if !duplicates.is_empty() {
+            output.push_str("\n## Duplicate Tests\n");
+            for group in duplicates {
+                for test in group.duplicates {
+                    writeln!(output, "{test} SAME AS {}", group.kept).unwrap();
+                }
             }
         }
+        let minimal = index.minimal_set();
+        writeln!(
+            output,
+            "\n## Minimal Test Set\nTests: {}/{}",
+            minimal.len(),
+            index.tests.len()
+        )
+        .unwrap();
+        for test in minimal {
+            output.push_str(test);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Formats the coverage of the last `runs` history entries, oldest first,
+/// followed by the files whose line coverage has been dropping over them.
+///
+/// # Output Format
+///
+/// ```text
+/// # Coverage Trend
+/// 2026-10-17 09:12 a49aecb Lines: 92.3% | Regions: 88.1% | Functions: 100.0%
+/// 2026-10-18 12:34 - Lines: 91.0% | Regions: 87.5% | Functions: 100.0%
+///
+/// ## Declining Files
+/// ./src/parser.rs Lines: 95.0% -> 90.0%
+/// ```
+#[must_use]
+pub fn format_trend(entries: &[HistoryEntry], runs: usize) -> String {
+    let mut output = String::from("# Coverage Trend\n");
+    for entry in &entries[entries.len().saturating_sub(runs)..] {
+        output.push_str(&format_timestamp(entry.timestamp));
+        output.push(' ');
+        output.push_str(entry.commit.as_deref().unwrap_or("-"));
+        output.push(' ');
+        format_summary(&mut output, &entry.summary);
+        output.push('\n');
+    }
+    let declining = declining_files(entries, runs);
+    if !declining.is_empty() {
+        output.push_str("\n## Declining Files\n");
+        for decline in declining {
+            // writeln to a String is infallible.
+            writeln!(
+                output,
+                "{} Lines: {} -> {}",
+                decline.filename,
+                format_percent(decline.first_percent),
+                format_percent(decline.last_percent)
+            )
+            .unwrap();
+        }
+    }
+    output
+}
+
+/// Formats the hottest lines, one per line.
+pub(crate) fn format_hot_lines(output: &mut String, hot_lines: &[HotLine]) {
+    for hot in hot_lines {
+        // writeln to a String is infallible.
+        writeln!(output, "{}:{} hits:{}", hot.filename, hot.line, hot.count).unwrap();
+    }
+}
+
+/// Formats the per-package and per-module totals, one per line.
+pub(crate) fn format_rollups(output: &mut String, rollups: &[Rollup]) {
+    for rollup in rollups {
+        output.push_str(&rollup.name);
+        output.push(' ');
+        format_summary(output, &rollup.summary);
+        output.push('\n');
+    }
+}
+
+/// Formats the exclusion count, then each exclusion that is still
+/// instrumented.
+pub(crate) fn format_exclusions(output: &mut String, exclusions: &[Exclusion]) {
+    let instrumented: Vec<&Exclusion> = exclusions
+        .iter()
+        .filter(|e| e.instrumented.is_some())
+        .collect();
+    // writeln to a String is infallible.
+    writeln!(
+        output,
+        "Excluded items: {} ({} still instrumented)",
+        exclusions.len(),
+        instrumented.len()
+    )
+    .unwrap();
+    for exclusion in instrumented {
+        writeln!(
+            output,
+            "{}:{} STILL INSTRUMENTED {} hits:{}",
+            exclusion.filename,
+            exclusion.line_start,
+            exclusion.name,
+            exclusion.instrumented.unwrap_or(0)
+        )
+        .unwrap();
+    }
+}
+
+/// Formats the share of public functions that tests executed, then each
+/// one they never did.
+pub(crate) fn format_public_api(output: &mut String, functions: &[ApiFunction]) {
+    let untested: Vec<&ApiFunction> = functions.iter().filter(|f| f.count == 0).collect();
+    let tested = functions.len() - untested.len();
+    // writeln to a String is infallible.
+    writeln!(
+        output,
+        "Tested: {tested}/{} ({})",
+        functions.len(),
+        format_percent(tested as f64 * 100.0 / functions.len() as f64)
+    )
+    .unwrap();
+    for function in untested {
+        writeln!(
+            output,
+            "{}:{} UNTESTED {}",
+            function.filename, function.line_start, function.path
+        )
+        .unwrap();
+    }
+}
+
+/// A coverage gap split into the parts of its report line.
+pub(crate) struct GapLine {
+    /// Lines or span, e.g. `8-9` or `42:3-42:18`.
+    pub location: String,
+    /// Gap kind, e.g. `UNCOVERED` or `WEAK REGION`.
+    pub kind: &'static str,
+    /// Counts or condition after the kind; may be empty.
+    pub detail: String,
+}
+
+/// Splits a coverage gap into the parts of its report line.
+pub(crate) fn gap_line(gap: &CoverageGap) -> GapLine {
+    let (location, kind, detail) = match gap {
+        CoverageGap::UncoveredLines {
+            start_line,
+            end_line,
+        } => (lines(*start_line, *end_line), "UNCOVERED", String::new()),
         CoverageGap::UncoveredRegion {
             line_start,
             col_start,
             line_end,
             col_end,
-        } => writeln!(
-            output,
-            "{filename}:{line_start}:{col_start}-{line_end}:{col_end} REGION hits:0"
+        } => (
+            format!("{line_start}:{col_start}-{line_end}:{col_end}"),
+            "REGION",
+            "hits:0".to_string(),
         ),
         CoverageGap::UncoveredBranch {
             line,
             col,
             true_count,
             false_count,
-        } => writeln!(
-            output,
-            "{filename}:{line}:{col} BRANCH true:{true_count} false:{false_count}"
+            condition: Some(condition),
+            ..
+        } => {
+            let missing = missing_direction(*true_count, *false_count);
+            (
+                format!("{line}:{col}"),
+                "BRANCH",
+                format!("`{condition}` {missing}"),
+            )
+        }
+        CoverageGap::UncoveredBranch {
+            line,
+            col,
+            true_count,
+            false_count,
+            condition: None,
+            ..
+        } => (
+            format!("{line}:{col}"),
+            "BRANCH",
+            format!("true:{true_count} false:{false_count}"),
+        ),
+        CoverageGap::UncoveredInstantiations {
+            line,
+            col,
+            executed,
+            unexecuted,
+        } => (
+            format!("{line}:{col}"),
+            "INSTANTIATIONS",
+            format!(
+                "executed:[{}] unexecuted:[{}]",
+                executed.join(", "),
+                unexecuted.join(", ")
+            ),
+        ),
+        CoverageGap::WeakLines {
+            start_line,
+            end_line,
+            max_count,
+        } => (
+            lines(*start_line, *end_line),
+            "WEAK",
+            format!("hits:{max_count}"),
+        ),
+        CoverageGap::WeakRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+            count,
+        } => (
+            format!("{line_start}:{col_start}-{line_end}:{col_end}"),
+            "WEAK REGION",
+            format!("hits:{count}"),
         ),
+        CoverageGap::WeakBranch {
+            line,
+            col,
+            true_count,
+            false_count,
+        } => (
+            format!("{line}:{col}"),
+            "WEAK BRANCH",
+            format!("true:{true_count} false:{false_count}"),
+        ),
+    };
+    GapLine {
+        location,
+        kind,
+        detail,
     }
+}
+
+/// Describes which direction of an uncovered branch never ran.
+pub(crate) const fn missing_direction(true_count: u64, false_count: u64) -> &'static str {
+    match (true_count, false_count) {
+        (0, 0) => "never evaluated",
+        (0, _) => "never true",
+        _ => "never false",
+    }
+}
+
+/// Escapes text for XML and HTML content and attribute values.
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Formats a line range, or a single line.
+fn lines(start: u64, end: u64) -> String {
+    if start == end {
+        start.to_string()
+    } else {
+        format!("{start}-{end}")
+    }
+}
+
+/// Formats a single coverage gap into the output buffer.
+pub(crate) fn format_gap(output: &mut String, filename: &str, gap: &CoverageGap) {
+    let GapLine {
+        location,
+        kind,
+        detail,
+    } = gap_line(gap);
     // writeln to a String is infallible.
-    .unwrap();
+    if detail.is_empty() {
+        writeln!(output, "{filename}:{location} {kind}").unwrap();
+    } else {
+        writeln!(output, "{filename}:{location} {kind} {detail}").unwrap();
+    }
+}
+
+/// Short codes of the gap kinds and their legend entries, in legend
+/// order.
+const GAP_CODES: [(&str, &str); 7] = [
+    ("U", "U=uncovered lines"),
+    ("R", "R=uncovered region"),
+    ("B", "B=branch"),
+    ("I", "I=instantiations +ran -never ran"),
+    ("W", "W=weak lines"),
+    ("WR", "WR=weak region"),
+    ("WB", "WB=weak branch"),
+];
+
+/// Formats a legend of the codes in use, then each run of gaps in the same
+/// file under its path.
+fn format_grouped_gaps(output: &mut String, gaps: &[(&str, &CoverageGap)]) {
+    let entries: Vec<(&str, String)> = gaps.iter().map(|(_, gap)| compact_gap(gap)).collect();
+    let used = |code: &str| entries.iter().any(|(c, _)| *c == code);
+    output.push_str("Legend:");
+    for (code, legend) in GAP_CODES {
+        if used(code) {
+            output.push(' ');
+            output.push_str(legend);
+        }
+    }
+    if used("B") || used("WB") {
+        output.push_str(" t/f=true/false hits");
+    }
+    if used("W") || used("WR") {
+        output.push_str(" h=hits");
+    }
+    output.push('\n');
+
+    let mut entries = entries.into_iter();
+    for group in gaps.chunk_by(|a, b| a.0 == b.0) {
+        output.push_str(group[0].0);
+        output.push('\n');
+        for (_, line) in entries.by_ref().take(group.len()) {
+            output.push_str("  ");
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+}
+
+/// Formats a gap as a short line without the file, returning its code.
+fn compact_gap(gap: &CoverageGap) -> (&'static str, String) {
+    match gap {
+        CoverageGap::UncoveredLines {
+            start_line,
+            end_line,
+        } => ("U", format!("{} U", lines(*start_line, *end_line))),
+        CoverageGap::UncoveredRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+        } => (
+            "R",
+            format!("{} R", span(*line_start, *col_start, *line_end, *col_end)),
+        ),
+        CoverageGap::UncoveredBranch {
+            line,
+            col,
+            true_count,
+            false_count,
+            condition,
+            ..
+        } => {
+            let mut text = format!("{line}:{col} B t{true_count} f{false_count}");
+            if let Some(condition) = condition {
+                // write to a String is infallible.
+                write!(text, " `{condition}`").unwrap();
+            }
+            ("B", text)
+        }
+        CoverageGap::UncoveredInstantiations {
+            line,
+            col,
+            executed,
+            unexecuted,
+        } => (
+            "I",
+            format!(
+                "{line}:{col} I +[{}] -[{}]",
+                executed.join(", "),
+                unexecuted.join(", ")
+            ),
+        ),
+        CoverageGap::WeakLines {
+            start_line,
+            end_line,
+            max_count,
+        } => (
+            "W",
+            format!("{} W h{max_count}", lines(*start_line, *end_line)),
+        ),
+        CoverageGap::WeakRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+            count,
+        } => (
+            "WR",
+            format!(
+                "{} WR h{count}",
+                span(*line_start, *col_start, *line_end, *col_end)
+            ),
+        ),
+        CoverageGap::WeakBranch {
+            line,
+            col,
+            true_count,
+            false_count,
+        } => (
+            "WB",
+            format!("{line}:{col} WB t{true_count} f{false_count}"),
+        ),
+    }
+}
+
+/// Formats a region span, leaving out the end line when it is the start
+/// line.
+fn span(line_start: u64, col_start: u64, line_end: u64, col_end: u64) -> String {
+    if line_start == line_end {
+        format!("{line_start}:{col_start}-{col_end}")
+    } else {
+        format!("{line_start}:{col_start}-{line_end}:{col_end}")
+    }
 }
 
 /// Formats the summary line.
-fn format_summary(output: &mut String, summary: &CoverageSummary) {
+pub(crate) fn format_summary(output: &mut String, summary: &CoverageSummary) {
     let lines = format_percent(summary.lines_percent);
     let regions = format_percent(summary.regions_percent);
     let functions = format_percent(summary.functions_percent);
@@ -97,6 +840,10 @@ fn format_summary(output: &mut String, summary: &CoverageSummary) {
     }
     // write to a String is infallible.
     .unwrap();
+
+    if let Some(ip) = summary.instantiations_percent {
+        write!(output, " | Instantiations: {}", format_percent(ip)).unwrap();
+    }
 }
 
 /// Formats a percentage with one decimal place, dropping trailing `.0`.
@@ -121,13 +868,16 @@ mod tests {
                     start_line: 7,
                     end_line: 7,
                 }],
+                functions: vec![],
             }],
             summary: CoverageSummary {
                 lines_percent: 92.3,
                 regions_percent: 88.1,
                 branches_percent: None,
                 functions_percent: 100.0,
+                instantiations_percent: None,
             },
+            ..AnalysisResult::default()
         };
 
         let output = format_result(&result);
@@ -143,13 +893,16 @@ mod tests {
                     start_line: 8,
                     end_line: 10,
                 }],
+                functions: vec![],
             }],
             summary: CoverageSummary {
                 lines_percent: 90.0,
                 regions_percent: 85.0,
                 branches_percent: None,
                 functions_percent: 100.0,
+                instantiations_percent: None,
             },
+            ..AnalysisResult::default()
         };
 
         let output = format_result(&result);
@@ -164,16 +917,22 @@ mod tests {
                 gaps: vec![CoverageGap::UncoveredBranch {
                     line: 50,
                     col: 5,
+                    line_end: 50,
+                    col_end: 20,
                     true_count: 5,
                     false_count: 0,
+                    condition: None,
                 }],
+                functions: vec![],
             }],
             summary: CoverageSummary {
                 lines_percent: 92.3,
                 regions_percent: 88.1,
                 branches_percent: Some(75.0),
                 functions_percent: 100.0,
+                instantiations_percent: None,
             },
+            ..AnalysisResult::default()
         };
 
         let output = format_result(&result);
@@ -181,16 +940,40 @@ mod tests {
         assert!(output.contains("Branches: 75.0%"));
     }
 
+    #[test]
+    fn test_format_branch_gap_with_condition() {
+        let branch = |true_count, false_count| CoverageGap::UncoveredBranch {
+            line: 55,
+            col: 8,
+            line_end: 55,
+            col_end: 17,
+            true_count,
+            false_count,
+            condition: Some("x > limit".to_string()),
+        };
+        let mut output = String::new();
+        format_gap(&mut output, "src/lib.rs", &branch(5, 0));
+        format_gap(&mut output, "src/lib.rs", &branch(0, 5));
+        format_gap(&mut output, "src/lib.rs", &branch(0, 0));
+        assert_eq!(
+            output,
+            "src/lib.rs:55:8 BRANCH `x > limit` never false\n\
+             src/lib.rs:55:8 BRANCH `x > limit` never true\n\
+             src/lib.rs:55:8 BRANCH `x > limit` never evaluated\n"
+        );
+    }
+
     #[test]
     fn test_format_no_gaps_omits_missed_section() {
         let result = AnalysisResult {
-            files: vec![],
             summary: CoverageSummary {
                 lines_percent: 100.0,
                 regions_percent: 100.0,
                 branches_percent: None,
                 functions_percent: 100.0,
+                instantiations_percent: None,
             },
+            ..AnalysisResult::default()
         };
         let output = format_result(&result);
         assert!(!output.contains("## Missed Coverage Areas"));
@@ -198,6 +981,436 @@ mod tests {
         assert!(output.contains("## Coverage Summary"));
     }
 
+    #[test]
+    fn test_format_instantiations_gap() {
+        let result = AnalysisResult {
+            files: vec![FileGaps {
+                filename: "src/lib.rs".to_string(),
+                gaps: vec![CoverageGap::UncoveredInstantiations {
+                    line: 61,
+                    col: 1,
+                    executed: vec!["t::parse::<u32>".to_string()],
+                    unexecuted: vec!["t::parse::<i64>".to_string(), "t::parse::<u8>".to_string()],
+                }],
+                functions: vec![],
+            }],
+            summary: CoverageSummary {
+                lines_percent: 100.0,
+                regions_percent: 100.0,
+                branches_percent: None,
+                functions_percent: 100.0,
+                instantiations_percent: Some(33.3),
+            },
+            ..AnalysisResult::default()
+        };
+
+        let output = format_result(&result);
+        assert!(output.contains(
+            "src/lib.rs:61:1 INSTANTIATIONS executed:[t::parse::<u32>] \
+             unexecuted:[t::parse::<i64>, t::parse::<u8>]"
+        ));
+        assert!(output.ends_with("| Functions: 100.0% | Instantiations: 33.3%"));
+    }
+
+    #[test]
+    fn test_format_weak_gaps() {
+        let result = AnalysisResult {
+            files: vec![FileGaps {
+                filename: "src/lib.rs".to_string(),
+                gaps: vec![
+                    CoverageGap::WeakLines {
+                        start_line: 70,
+                        end_line: 72,
+                        max_count: 1,
+                    },
+                    CoverageGap::WeakLines {
+                        start_line: 74,
+                        end_line: 74,
+                        max_count: 2,
+                    },
+                    CoverageGap::WeakRegion {
+                        line_start: 75,
+                        col_start: 9,
+                        line_end: 75,
+                        col_end: 20,
+                        count: 2,
+                    },
+                    CoverageGap::WeakBranch {
+                        line: 80,
+                        col: 8,
+                        true_count: 1,
+                        false_count: 40,
+                    },
+                ],
+                functions: vec![],
+            }],
+            summary: CoverageSummary {
+                lines_percent: 100.0,
+                regions_percent: 100.0,
+                branches_percent: Some(100.0),
+                functions_percent: 100.0,
+                instantiations_percent: None,
+            },
+            ..AnalysisResult::default()
+        };
+
+        let output = format_result(&result);
+        assert!(output.contains("src/lib.rs:70-72 WEAK hits:1\n"));
+        assert!(output.contains("src/lib.rs:74 WEAK hits:2\n"));
+        assert!(output.contains("src/lib.rs:75:9-75:20 WEAK REGION hits:2\n"));
+        assert!(output.contains("src/lib.rs:80:8 WEAK BRANCH true:1 false:40\n"));
+    }
+
+    #[test]
+    fn test_format_quickfix() {
+        let result = AnalysisResult {
+            files: vec![FileGaps {
+                filename: "./src/lib.rs".to_string(),
+                gaps: vec![
+                    CoverageGap::UncoveredLines {
+                        start_line: 7,
+                        end_line: 7,
+                    },
+                    CoverageGap::UncoveredLines {
+                        start_line: 8,
+                        end_line: 9,
+                    },
+                    CoverageGap::UncoveredRegion {
+                        line_start: 42,
+                        col_start: 3,
+                        line_end: 42,
+                        col_end: 18,
+                    },
+                    CoverageGap::UncoveredBranch {
+                        line: 50,
+                        col: 5,
+                        line_end: 50,
+                        col_end: 9,
+                        true_count: 5,
+                        false_count: 0,
+                        condition: None,
+                    },
+                    CoverageGap::UncoveredBranch {
+                        line: 55,
+                        col: 8,
+                        line_end: 55,
+                        col_end: 17,
+                        true_count: 0,
+                        false_count: 0,
+                        condition: Some("x > limit".to_string()),
+                    },
+                    CoverageGap::UncoveredInstantiations {
+                        line: 61,
+                        col: 1,
+                        executed: vec!["parse::<u32>".to_string()],
+                        unexecuted: vec!["parse::<i64>".to_string()],
+                    },
+                    CoverageGap::WeakLines {
+                        start_line: 70,
+                        end_line: 70,
+                        max_count: 1,
+                    },
+                    CoverageGap::WeakRegion {
+                        line_start: 75,
+                        col_start: 9,
+                        line_end: 76,
+                        col_end: 2,
+                        count: 2,
+                    },
+                    CoverageGap::WeakBranch {
+                        line: 80,
+                        col: 8,
+                        true_count: 1,
+                        false_count: 40,
+                    },
+                ],
+                functions: vec![],
+            }],
+            ..AnalysisResult::default()
+        };
+
+        assert_eq!(
+            format_quickfix(&result),
+            "./src/lib.rs:7:1: uncovered line\n\
+             ./src/lib.rs:8:1: uncovered lines 8-9\n\
+             ./src/lib.rs:42:3: uncovered region to 42:18\n\
+             ./src/lib.rs:50:5: branch never false (true:5 false:0)\n\
+             ./src/lib.rs:55:8: branch `x > limit` never evaluated\n\
+             ./src/lib.rs:61:1: unexecuted instantiations: parse::<i64>\n\
+             ./src/lib.rs:70:1: weakly covered line (hits:1)\n\
+             ./src/lib.rs:75:9: weakly covered region to 76:2 (hits:2)\n\
+             ./src/lib.rs:80:8: weakly covered branch (true:1 false:40)\n"
+        );
+    }
+
+    #[test]
+    fn test_format_hot_lines_section() {
+        let result = AnalysisResult {
+            summary: CoverageSummary {
+                lines_percent: 100.0,
+                regions_percent: 100.0,
+                branches_percent: None,
+                functions_percent: 100.0,
+                instantiations_percent: None,
+            },
+            hot_lines: vec![HotLine {
+                filename: "src/lib.rs".to_string(),
+                line: 12,
+                count: 1043,
+            }],
+            ..AnalysisResult::default()
+        };
+
+        let output = format_result(&result);
+        assert!(
+            output.contains("\n## Hottest Lines\nsrc/lib.rs:12 hits:1043\n\n## Coverage Summary")
+        );
+    }
+
+    #[test]
+    fn test_format_exclusions() {
+        let exclusion = |line_start, instrumented| Exclusion {
+            filename: "src/main.rs".to_string(),
+            name: "run".to_string(),
+            line_start,
+            line_end: line_start + 5,
+            instrumented,
+        };
+        let result = AnalysisResult {
+            summary: CoverageSummary {
+                lines_percent: 100.0,
+                regions_percent: 100.0,
+                branches_percent: None,
+                functions_percent: 100.0,
+                instantiations_percent: None,
+            },
+            exclusions: vec![exclusion(3, None), exclusion(20, Some(4))],
+            ..AnalysisResult::default()
+        };
+
+        let output = format_result(&result);
+        assert!(output.contains(
+            "\n## Coverage Exclusions\n\
+             Excluded items: 2 (1 still instrumented)\n\
+             src/main.rs:20 STILL INSTRUMENTED run hits:4\n\
+             \n## Coverage Summary\n"
+        ));
+    }
+
+    #[test]
+    fn test_format_public_api() {
+        let function = |path: &str, line_start, count| ApiFunction {
+            filename: "src/lib.rs".to_string(),
+            path: path.to_string(),
+            line_start,
+            line_end: line_start + 3,
+            count,
+        };
+        let result = AnalysisResult {
+            summary: CoverageSummary {
+                lines_percent: 100.0,
+                regions_percent: 100.0,
+                branches_percent: None,
+                functions_percent: 100.0,
+                instantiations_percent: None,
+            },
+            public_api: vec![
+                function("app::parse", 3, 12),
+                function("app::Parser::new", 10, 0),
+                function("app::render", 20, 1),
+            ],
+            ..AnalysisResult::default()
+        };
+
+        let output = format_result(&result);
+        assert!(output.contains(
+            "\n## Public API\n\
+             Tested: 2/3 (66.7%)\n\
+             src/lib.rs:10 UNTESTED app::Parser::new\n\
+             \n## Coverage Summary\n"
+        ));
+    }
+
+    #[test]
+    fn test_format_rollups() {
+        let summary = |lines_percent, branches_percent| CoverageSummary {
+            lines_percent,
+            regions_percent: 50.0,
+            branches_percent,
+            functions_percent: 100.0,
+            instantiations_percent: None,
+        };
+        let result = AnalysisResult {
+            summary: summary(80.0, None),
+            rollups: vec![
+                Rollup {
+                    name: "core".to_string(),
+                    summary: summary(75.0, Some(40.0)),
+                },
+                Rollup {
+                    name: "core/src".to_string(),
+                    summary: summary(75.0, Some(40.0)),
+                },
+            ],
+            ..AnalysisResult::default()
+        };
+
+        let output = format_result(&result);
+        assert!(output.contains(
+            "\n## Crate Coverage\n\
+             core Lines: 75.0% | Regions: 50.0% | Branches: 40.0% | Functions: 100.0%\n\
+             core/src Lines: 75.0% | Regions: 50.0% | Branches: 40.0% | Functions: 100.0%\n\
+             \n## Coverage Summary\n"
+        ));
+    }
+
+    #[test]
+    fn test_format_priority_order() {
+        let result = AnalysisResult {
+            files: vec![
+                FileGaps {
+                    filename: "src/a.rs".to_string(),
+                    gaps: vec![CoverageGap::UncoveredLines {
+                        start_line: 3,
+                        end_line: 3,
+                    }],
+                    functions: vec![],
+                },
+                FileGaps {
+                    filename: "src/b.rs".to_string(),
+                    gaps: vec![CoverageGap::UncoveredLines {
+                        start_line: 10,
+                        end_line: 30,
+                    }],
+                    functions: vec![],
+                },
+            ],
+            summary: CoverageSummary {
+                lines_percent: 50.0,
+                regions_percent: 50.0,
+                branches_percent: None,
+                functions_percent: 50.0,
+                instantiations_percent: None,
+            },
+            ..AnalysisResult::default()
+        };
+        let options = FormatOptions {
+            order: GapOrder::Priority,
+            ..FormatOptions::default()
+        };
+
+        let output = format_result_with(&result, &options);
+        assert!(output.contains(
+            "## Missed Coverage Areas (by priority)\nsrc/b.rs:10-30 UNCOVERED\nsrc/a.rs:3 UNCOVERED\n"
+        ));
+    }
+
+    #[test]
+    fn test_format_grouped() {
+        let gaps = vec![
+            CoverageGap::UncoveredLines {
+                start_line: 7,
+                end_line: 9,
+            },
+            CoverageGap::UncoveredRegion {
+                line_start: 42,
+                col_start: 3,
+                line_end: 42,
+                col_end: 18,
+            },
+            CoverageGap::UncoveredBranch {
+                line: 50,
+                col: 5,
+                line_end: 50,
+                col_end: 10,
+                true_count: 5,
+                false_count: 0,
+                condition: None,
+            },
+            CoverageGap::UncoveredBranch {
+                line: 55,
+                col: 8,
+                line_end: 55,
+                col_end: 17,
+                true_count: 3,
+                false_count: 0,
+                condition: Some("x > limit".to_string()),
+            },
+            CoverageGap::WeakRegion {
+                line_start: 75,
+                col_start: 9,
+                line_end: 76,
+                col_end: 20,
+                count: 2,
+            },
+        ];
+        let result = AnalysisResult {
+            files: vec![
+                FileGaps {
+                    filename: "src/a.rs".to_string(),
+                    gaps,
+                    functions: vec![],
+                },
+                FileGaps {
+                    filename: "src/b.rs".to_string(),
+                    gaps: vec![CoverageGap::UncoveredInstantiations {
+                        line: 61,
+                        col: 1,
+                        executed: vec!["parse::<u32>".to_string()],
+                        unexecuted: vec!["parse::<i64>".to_string()],
+                    }],
+                    functions: vec![],
+                },
+            ],
+            ..AnalysisResult::default()
+        };
+        let options = FormatOptions {
+            grouped: true,
+            ..FormatOptions::default()
+        };
+
+        let output = format_result_with(&result, &options);
+        assert!(output.contains(
+            "## Missed Coverage Areas\n\
+             Legend: U=uncovered lines R=uncovered region B=branch \
+             I=instantiations +ran -never ran WR=weak region t/f=true/false hits h=hits\n\
+             src/a.rs\n  \
+             7-9 U\n  \
+             42:3-18 R\n  \
+             50:5 B t5 f0\n  \
+             55:8 B t3 f0 `x > limit`\n  \
+             75:9-76:20 WR h2\n\
+             src/b.rs\n  \
+             61:1 I +[parse::<u32>] -[parse::<i64>]\n\n"
+        ));
+    }
+
+    #[test]
+    fn test_format_grouped_priority_order() {
+        let gap = |line| CoverageGap::UncoveredLines {
+            start_line: line,
+            end_line: line,
+        };
+        let result = AnalysisResult {
+            files: vec![FileGaps {
+                filename: "src/a.rs".to_string(),
+                gaps: vec![gap(3)],
+                functions: vec![],
+            }],
+            ..AnalysisResult::default()
+        };
+        let options = FormatOptions {
+            order: GapOrder::Priority,
+            grouped: true,
+            ..FormatOptions::default()
+        };
+
+        let output = format_result_with(&result, &options);
+        assert!(output.contains(
+            "## Missed Coverage Areas (by priority)\nLegend: U=uncovered lines\nsrc/a.rs\n  3 U\n"
+        ));
+    }
+
     #[test]
     fn test_format_summary_without_branches() {
         let summary = CoverageSummary {
@@ -205,6 +1418,7 @@ mod tests {
             regions_percent: 88.1,
             branches_percent: None,
             functions_percent: 100.0,
+            instantiations_percent: None,
         };
         let mut output = String::new();
         format_summary(&mut output, &summary);
@@ -218,6 +1432,7 @@ mod tests {
             regions_percent: 88.1,
             branches_percent: Some(75.0),
             functions_percent: 100.0,
+            instantiations_percent: None,
         };
         let mut output = String::new();
         format_summary(&mut output, &summary);
@@ -226,4 +1441,155 @@ mod tests {
             "Lines: 92.3% | Regions: 88.1% | Branches: 75.0% | Functions: 100.0%"
         );
     }
+
+    #[test]
+    fn test_format_test_index() {
+        let mut index = TestIndex {
+            tests: vec!["app tests::a".to_string(), "app tests::b".to_string()],
+            ..TestIndex::default()
+        };
+        index
+            .files
+            .entry("/ws/src/lib.rs".to_string())
+            .or_default()
+            .lines
+            .extend([(1, vec![0]), (2, vec![0, 1])]);
+        assert_eq!(
+            format_test_index(&index),
+            "# Per-Test Coverage\n\
+             Tests: 2 | Files: 1\n\
+             \n## Gaps Closed Per Test\n\
+             app tests::a CLOSES /ws/src/lib.rs:1\n\
+             \n## Tests Without Unique Coverage\n\
+             app tests::b\n\
+             \n## Redundant Tests\n\
+             app tests::b SUBSET OF app tests::a\n\
+             \n## Minimal Test Set\n\
+             Tests: 1/2\n\
+             app tests::a\n"
+        );
+        index
+            .files
+            .get_mut("/ws/src/lib.rs")
+            .unwrap()
+            .branches
+            .insert("2:5:false".to_string(), vec![1]);
+        assert_eq!(
+            format_test_index(&index),
+            "# Per-Test Coverage\nTests: 2 | Files: 1\n\
+             \n## Gaps Closed Per Test\n\
+             app tests::a CLOSES /ws/src/lib.rs:1\n"
+        );
+
+        // Identical tests are duplicates, not subsets of each other.
+        let mut twins = TestIndex {
+            tests: vec!["app tests::a".to_string(), "app tests::b".to_string()],
+            ..TestIndex::default()
+        };
+        twins
+            .files
+            .entry("/ws/src/lib.rs".to_string())
+            .or_default()
+            .lines
+            .insert(1, vec![0, 1]);
+        assert_eq!(
+            format_test_index(&twins),
+            "# Per-Test Coverage\n\
+             Tests: 2 | Files: 1\n\
+             \n## Tests Without Unique Coverage\n\
+             app tests::a\n\
+             app tests::b\n\
+             \n## Duplicate Tests\n\
+             app tests::b SAME AS app tests::a\n\
+             \n## Minimal Test Set\n\
+             Tests: 1/2\n\
+             app tests::a\n"
+        );
+    }
+
+    #[test]
+    fn test_format_trend() {
+        let entry = |timestamp, commit: Option<&str>, covered| {
+            let totals = crate::totals::Totals {
+                lines: crate::totals::Counts { covered, count: 4 },
+                ..crate::totals::Totals::default()
+            };
+            HistoryEntry {
+                commit: commit.map(ToString::to_string),
+                timestamp,
+                summary: totals.summary(),
+                files: [("./src/lib.rs".to_string(), totals)].into(),
+            }
+        };
+        let entries = [
+            entry(0, Some("0000000"), 1),
+            entry(1_792_240_320, Some("a49aecb"), 4),
+            entry(1_792_326_840, None, 3),
+        ];
+        assert_eq!(
+            format_trend(&entries, 2),
+            "# Coverage Trend\n\
+             2026-10-17 12:32 a49aecb Lines: 100.0% | Regions: 0.0% | Functions: 0.0%\n\
+             2026-10-18 12:34 - Lines: 75.0% | Regions: 0.0% | Functions: 0.0%\n\
+             \n## Declining Files\n\
+             ./src/lib.rs Lines: 100.0% -> 75.0%\n"
+        );
+        assert_eq!(
+            format_trend(&entries[..1], 5),
+            "# Coverage Trend\n\
+             1970-01-01 00:00 0000000 Lines: 25.0% | Regions: 0.0% | Functions: 0.0%\n"
+        );
+    }
+
+    #[test]
+    fn test_format_gap_diff() {
+        let closed = CoverageGap::UncoveredLines {
+            start_line: 10,
+            end_line: 12,
+        };
+        let new = CoverageGap::UncoveredLines {
+            start_line: 42,
+            end_line: 42,
+        };
+        let summary = CoverageSummary {
+            lines_percent: 90.0,
+            regions_percent: 80.0,
+            branches_percent: None,
+            functions_percent: 100.0,
+            instantiations_percent: None,
+        };
+        let diff = GapDiff {
+            closed: vec![GapChange {
+                filename: "src/lib.rs",
+                gap: &closed,
+            }],
+            new: vec![GapChange {
+                filename: "src/lib.rs",
+                gap: &new,
+            }],
+        };
+        assert_eq!(
+            format_gap_diff(&diff, &summary),
+            "# Coverage Changes\n\
+             Closed 1 gap, 1 new\n\
+             \n## New Gaps\n\
+             src/lib.rs:42 UNCOVERED\n\
+             \n## Closed Gaps\n\
+             src/lib.rs:10-12 UNCOVERED\n\
+             \n## Coverage Summary\n\
+             Lines: 90.0% | Regions: 80.0% | Functions: 100.0%"
+        );
+        assert!(
+            format_gap_diff(&GapDiff::default(), &summary)
+                .starts_with("# Coverage Changes\nClosed 0 gaps, 0 new\n\n## Coverage Summary\n")
+        );
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
 }
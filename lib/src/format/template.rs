@@ -0,0 +1,529 @@
+//! User-defined output templates.
+//!
+//! A template is text with `{placeholder}` fields, rendered once per gap
+//! or once for the summary. `{{` and `}}` stand for literal braces, and
+//! `\n`, `\t`, and `\\` for a newline, a tab, and a backslash.
+
+use super::{
+    FormatOptions, GapLine, GapOrder, compact_gap, format_gap, format_summary, gap_line,
+    quickfix_entry,
+};
+use crate::analysis::{AnalysisResult, CoverageGap, FileGaps};
+
+/// Placeholders of gap templates.
+///
+/// - `file`: path of the file
+/// - `line`, `col`: start of the gap; `col` is 1 for whole lines
+/// - `end_line`, `end_col`: end of the gap; `end_col` is empty for whole
+///   lines and instantiations
+/// - `location`: lines or span as in the default report, e.g. `8-9`
+/// - `kind`: kind as in the default report, e.g. `WEAK REGION`
+/// - `code`: kind as in the compact report, e.g. `WR`
+/// - `detail`: text after the kind in the default report
+/// - `message`: description as in the quickfix report
+/// - `count`: hits of a line or region gap
+/// - `true_count`, `false_count`: hits of each branch direction
+/// - `condition`: source of a branch condition, if read
+/// - `function`: name of the innermost enclosing function
+///
+/// Placeholders that do not apply to a gap are empty.
+pub const GAP_FIELDS: [&str; 15] = [
+    "file",
+    "line",
+    "col",
+    "end_line",
+    "end_col",
+    "location",
+    "kind",
+    "code",
+    "detail",
+    "message",
+    "count",
+    "true_count",
+    "false_count",
+    "condition",
+    "function",
+];
+
+/// Placeholders of summary templates.
+///
+/// - `lines`, `regions`, `branches`, `functions`, `instantiations`:
+///   coverage percentage with one decimal, without `%`; `n/a` for
+///   branches and instantiations without data
+/// - `gaps`: number of gaps
+/// - `files`: number of files with gaps
+pub const SUMMARY_FIELDS: [&str; 7] = [
+    "lines",
+    "regions",
+    "branches",
+    "functions",
+    "instantiations",
+    "gaps",
+    "files",
+];
+
+/// Error parsing a template or template file.
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    /// A placeholder is not one of the known fields.
+    #[error("unknown placeholder `{{{name}}}`; expected one of: {}", .expected.join(", "))]
+    UnknownPlaceholder {
+        /// The placeholder name.
+        name: String,
+        /// The known fields.
+        expected: &'static [&'static str],
+    },
+    /// A `{` has no matching `}`.
+    #[error("unclosed `{{` at byte {0}")]
+    Unclosed(usize),
+    /// A `}` has no matching `{`; write `}}` for a literal brace.
+    #[error("unmatched `}}` at byte {0}")]
+    Unmatched(usize),
+    /// A template file line is not `gap = ...` or `summary = ...`.
+    #[error("line {0}: expected `gap = TEMPLATE` or `summary = TEMPLATE`")]
+    Syntax(usize),
+    /// A template in a template file is invalid.
+    #[error("line {line}: {source}")]
+    Line {
+        /// Line number, from 1.
+        line: usize,
+        /// The template error.
+        source: Box<Self>,
+    },
+}
+
+/// A piece of a parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    /// Literal text.
+    Text(String),
+    /// A placeholder, one of the template kind's fields.
+    Field(&'static str),
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses a gap template, with the placeholders in [`GAP_FIELDS`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unknown placeholders and unmatched braces.
+    pub fn gap(source: &str) -> Result<Self, TemplateError> {
+        Self::parse(source, &GAP_FIELDS)
+    }
+
+    /// Parses a summary template, with the placeholders in
+    /// [`SUMMARY_FIELDS`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unknown placeholders and unmatched braces.
+    pub fn summary(source: &str) -> Result<Self, TemplateError> {
+        Self::parse(source, &SUMMARY_FIELDS)
+    }
+
+    /// Parses `source`, accepting the placeholders in `fields`.
+    fn parse(source: &str, fields: &'static [&'static str]) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, c)| c == '{').is_some() => text.push('{'),
+                '}' if chars.next_if(|&(_, c)| c == '}').is_some() => text.push('}'),
+                '{' => {
+                    let len = source[i + 1..]
+                        .find('}')
+                        .ok_or(TemplateError::Unclosed(i))?;
+                    let name = &source[i + 1..i + 1 + len];
+                    let field = fields.iter().find(|f| **f == name).ok_or_else(|| {
+                        TemplateError::UnknownPlaceholder {
+                            name: name.to_string(),
+                            expected: fields,
+                        }
+                    })?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                    while chars.next_if(|&(j, _)| j <= i + 1 + len).is_some() {}
+                }
+                '}' => return Err(TemplateError::Unmatched(i)),
+                '\\' => match chars.next_if(|&(_, c)| matches!(c, 'n' | 't' | '\\')) {
+                    Some((_, 'n')) => text.push('\n'),
+                    Some((_, 't')) => text.push('\t'),
+                    _ => text.push('\\'),
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Renders the template, looking up placeholders with `value`.
+    fn render(&self, output: &mut String, value: impl Fn(&str) -> String) {
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => output.push_str(text),
+                Part::Field(field) => output.push_str(&value(field)),
+            }
+        }
+    }
+}
+
+/// Templates replacing the default gap lines and summary line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Templates {
+    /// Template of each gap line; the default format if unset.
+    pub gap: Option<Template>,
+    /// Template of the summary line; the default format if unset.
+    pub summary: Option<Template>,
+}
+
+impl Templates {
+    /// Parses a template file.
+    ///
+    /// Each line is `gap = TEMPLATE` or `summary = TEMPLATE`; blank lines
+    /// and lines starting with `#` are ignored. One space after the `=` is
+    /// not part of the template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for malformed lines and invalid templates, with
+    /// the line number.
+    pub fn parse_file(contents: &str) -> Result<Self, TemplateError> {
+        let mut templates = Self::default();
+        for (index, line) in contents.lines().enumerate() {
+            let number = index + 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (key, source) = line.split_once('=').ok_or(TemplateError::Syntax(number))?;
+            let source = source.strip_prefix(' ').unwrap_or(source);
+            let (template, parsed) = match key.trim() {
+                "gap" => (&mut templates.gap, Template::gap(source)),
+                "summary" => (&mut templates.summary, Template::summary(source)),
+                _ => return Err(TemplateError::Syntax(number)),
+            };
+            *template = Some(parsed.map_err(|source| TemplateError::Line {
+                line: number,
+                source: Box::new(source),
+            })?);
+        }
+        Ok(templates)
+    }
+}
+
+/// Formats an analysis result with user-defined templates.
+///
+/// Prints one line per gap, in the order of `options`, then the summary
+/// line. Lines without a template use the default format; there are no
+/// headings or other sections.
+///
+/// # Output Format
+///
+/// With the gap template `{file}|{line}|{code}|{function}` and the summary
+/// template `lines={lines} branches={branches}`:
+///
+/// ```text
+/// ./src/lib.rs|8|U|parse
+/// ./src/lib.rs|42|R|Parser::next
+/// lines=92.3 branches=n/a
+/// ```
+#[must_use]
+pub fn format_templated(
+    result: &AnalysisResult,
+    options: &FormatOptions,
+    templates: &Templates,
+) -> String {
+    let gaps: Vec<(&FileGaps, &CoverageGap)> = match options.order {
+        GapOrder::File => result
+            .files
+            .iter()
+            .flat_map(|file| file.gaps.iter().map(move |gap| (file, gap)))
+            .collect(),
+        GapOrder::Priority => result
+            .ranked_gaps(&options.score_context)
+            .into_iter()
+            .filter_map(|ranked| {
+                let file = result
+                    .files
+                    .iter()
+                    .find(|f| f.filename == ranked.filename)?;
+                Some((file, ranked.gap))
+            })
+            .collect(),
+    };
+
+    let mut output = String::new();
+    for (file, gap) in gaps {
+        match &templates.gap {
+            Some(template) => {
+                template.render(&mut output, |field| gap_field(file, gap, field));
+                output.push('\n');
+            }
+            None => format_gap(&mut output, &file.filename, gap),
+        }
+    }
+    match &templates.summary {
+        Some(template) => {
+            template.render(&mut output, |field| summary_field(result, field));
+        }
+        None => format_summary(&mut output, &result.summary),
+    }
+    output.push('\n');
+    output
+}
+
+/// Returns the value of a gap placeholder.
+fn gap_field(file: &FileGaps, gap: &CoverageGap, field: &str) -> String {
+    let (line, col, message) = quickfix_entry(gap);
+    match field {
+        "file" => file.filename.clone(),
+        "line" => line.to_string(),
+        "col" => col.to_string(),
+        "end_line" => gap.end_line().to_string(),
+        "end_col" => match gap {
+            CoverageGap::UncoveredRegion { col_end, .. }
+            | CoverageGap::WeakRegion { col_end, .. }
+            | CoverageGap::UncoveredBranch { col_end, .. } => col_end.to_string(),
+            _ => String::new(),
+        },
+        "location" | "kind" | "detail" => {
+            let GapLine {
+                location,
+                kind,
+                detail,
+            } = gap_line(gap);
+            match field {
+                "location" => location,
+                "kind" => kind.to_string(),
+                _ => detail,
+            }
+        }
+        "code" => compact_gap(gap).0.to_string(),
+        "message" => message,
+        "count" => match gap {
+            CoverageGap::UncoveredLines { .. } | CoverageGap::UncoveredRegion { .. } => {
+                "0".to_string()
+            }
+            CoverageGap::WeakLines { max_count, .. } => max_count.to_string(),
+            CoverageGap::WeakRegion { count, .. } => count.to_string(),
+            _ => String::new(),
+        },
+        "true_count" | "false_count" => match gap {
+            CoverageGap::UncoveredBranch {
+                true_count,
+                false_count,
+                ..
+            }
+            | CoverageGap::WeakBranch {
+                true_count,
+                false_count,
+                ..
+            } => if field == "true_count" {
+                true_count
+            } else {
+                false_count
+            }
+            .to_string(),
+            _ => String::new(),
+        },
+        "condition" => match gap {
+            CoverageGap::UncoveredBranch {
+                condition: Some(condition),
+                ..
+            } => condition.clone(),
+            _ => String::new(),
+        },
+        "function" => file
+            .function_at(gap.start_line())
+            .map(|f| f.name.clone())
+            .unwrap_or_default(),
+        _ => unreachable!("placeholders are checked when parsing"),
+    }
+}
+
+/// Returns the value of a summary placeholder.
+fn summary_field(result: &AnalysisResult, field: &str) -> String {
+    let percent =
+        |value: Option<f64>| value.map_or_else(|| "n/a".to_string(), |v| format!("{v:.1}"));
+    let summary = &result.summary;
+    match field {
+        "lines" => percent(Some(summary.lines_percent)),
+        "regions" => percent(Some(summary.regions_percent)),
+        "branches" => percent(summary.branches_percent),
+        "functions" => percent(Some(summary.functions_percent)),
+        "instantiations" => percent(summary.instantiations_percent),
+        "gaps" => result
+            .files
+            .iter()
+            .map(|f| f.gaps.len())
+            .sum::<usize>()
+            .to_string(),
+        "files" => result
+            .files
+            .iter()
+            .filter(|f| !f.gaps.is_empty())
+            .count()
+            .to_string(),
+        _ => unreachable!("placeholders are checked when parsing"),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::{CoverageSummary, FunctionSpan};
+
+    fn result() -> AnalysisResult {
+        AnalysisResult {
+            files: vec![FileGaps {
+                filename: "src/lib.rs".to_string(),
+                gaps: vec![
+                    CoverageGap::UncoveredLines {
+                        start_line: 8,
+                        end_line: 9,
+                    },
+                    CoverageGap::UncoveredBranch {
+                        line: 12,
+                        col: 5,
+                        line_end: 12,
+                        col_end: 14,
+                        true_count: 3,
+                        false_count: 0,
+                        condition: Some("x > limit".to_string()),
+                    },
+                ],
+                functions: vec![FunctionSpan {
+                    name: "parse".to_string(),
+                    line_start: 1,
+                    col_start: 1,
+                    line_end: 10,
+                    col_end: 2,
+                    count: 1,
+                    visibility: None,
+                }],
+            }],
+            summary: CoverageSummary {
+                lines_percent: 92.34,
+                regions_percent: 88.0,
+                branches_percent: None,
+                functions_percent: 100.0,
+                instantiations_percent: None,
+            },
+            ..AnalysisResult::default()
+        }
+    }
+
+    #[test]
+    fn test_format_templated() {
+        let templates = Templates {
+            gap: Some(
+                Template::gap(
+                    "{file}:{line}:{col}-{end_line}:{end_col} {code} {kind} [{detail}] \
+                     t={true_count} f={false_count} c={count} `{condition}` in {function}",
+                )
+                .unwrap(),
+            ),
+            summary: Some(
+                Template::summary("{{lines}}={lines}% b={branches} {gaps} gaps in {files}")
+                    .unwrap(),
+            ),
+        };
+        assert_eq!(
+            format_templated(&result(), &FormatOptions::default(), &templates),
+            "src/lib.rs:8:1-9: U UNCOVERED [] t= f= c=0 `` in parse\n\
+             src/lib.rs:12:5-12:14 B BRANCH [`x > limit` never false] t=3 f=0 c= `x > limit` in \n\
+             {lines}=92.3% b=n/a 2 gaps in 1\n"
+        );
+    }
+
+    #[test]
+    fn test_format_templated_defaults() {
+        let templates = Templates {
+            gap: Some(Template::gap("{message}").unwrap()),
+            summary: None,
+        };
+        assert_eq!(
+            format_templated(&result(), &FormatOptions::default(), &templates),
+            "uncovered lines 8-9\n\
+             branch `x > limit` never false\n\
+             Lines: 92.3% | Regions: 88.0% | Functions: 100.0%\n"
+        );
+        let output = format_templated(&result(), &FormatOptions::default(), &Templates::default());
+        assert!(output.starts_with("src/lib.rs:8-9 UNCOVERED\n"));
+    }
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!(
+            Template::gap("a\\tb\\n\\\\{{x}}\\q").unwrap(),
+            Template {
+                parts: vec![Part::Text("a\tb\n\\{x}\\q".to_string())],
+            }
+        );
+        assert!(matches!(
+            Template::gap("{lines}"),
+            Err(TemplateError::UnknownPlaceholder { name, .. }) if name == "lines"
+        ));
+        assert!(matches!(
+            Template::summary("{lines"),
+            Err(TemplateError::Unclosed(0))
+        ));
+        assert!(matches!(
+            Template::summary("a}"),
+            Err(TemplateError::Unmatched(1))
+        ));
+        assert_eq!(
+            Template::gap("{bogus}").unwrap_err().to_string(),
+            format!(
+                "unknown placeholder `{{bogus}}`; expected one of: {}",
+                GAP_FIELDS.join(", ")
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let templates = Templates::parse_file(
+            "# Editor format\n\ngap = {file}:{line}: {message}\nsummary = {lines}%\n",
+        )
+        .unwrap();
+        assert_eq!(
+            templates.gap,
+            Some(Template::gap("{file}:{line}: {message}").unwrap())
+        );
+        assert_eq!(
+            templates.summary,
+            Some(Template::summary("{lines}%").unwrap())
+        );
+
+        assert!(matches!(
+            Templates::parse_file("gap {file}"),
+            Err(TemplateError::Syntax(1))
+        ));
+        assert!(matches!(
+            Templates::parse_file("\nheader = x"),
+            Err(TemplateError::Syntax(2))
+        ));
+        assert_eq!(
+            Templates::parse_file("summary = {file}")
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "line 1: unknown placeholder `{{file}}`; expected one of: {}",
+                SUMMARY_FIELDS.join(", ")
+            )
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! GitLab CI reports.
+//!
+//! GitLab cannot show GitHub annotations, but it reads a Code Quality
+//! report, in which every coverage gap becomes an issue on the merge
+//! request, and a Cobertura report, which marks covered and uncovered
+//! lines in the diff. The job's coverage percentage comes from a log line
+//! matched by the project's coverage regex.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::{Value, json};
+
+use crate::analysis::{
+    AnalysisOptions, AnalysisResult, CoverageGap, CoverageSummary, detect_test_code, line_coverage,
+    merge_branches,
+};
+use crate::format::{escape_xml, quickfix_entry};
+use crate::model::CoverageExport;
+use crate::source::SourceCache;
+use crate::totals::{self, Counts, Totals};
+
+/// Regular expression to set as the project's test coverage parsing
+/// setting, matching [`format_coverage_line`].
+pub const COVERAGE_REGEX: &str = r"/^Coverage: \d+\.\d+%$/";
+
+/// Formats the gaps as a GitLab Code Quality report.
+///
+/// Each gap is one issue. Fingerprints are derived from the file, gap
+/// kind, and location, so a gap keeps its fingerprint across runs while
+/// it stays in place.
+#[must_use]
+pub fn format_code_quality(result: &AnalysisResult) -> String {
+    let issues = result
+        .files
+        .iter()
+        .flat_map(|file| {
+            let path = file.filename.trim_start_matches("./");
+            file.gaps.iter().map(move |gap| {
+                let (line, col, description) = quickfix_entry(gap);
+                let (check_name, severity) = check(gap);
+                let end = gap.end_line();
+                json!({
+                    "description": description,
+                    "check_name": check_name,
+                    "fingerprint": fingerprint(&format!("{path}\0{check_name}\0{line}:{col}-{end}")),
+                    "severity": severity,
+                    "location": { "path": path, "lines": { "begin": line, "end": end } },
+                })
+            })
+        })
+        .collect();
+    format!("{:#}", Value::Array(issues))
+}
+
+/// Returns the Code Quality check name and severity of a gap.
+const fn check(gap: &CoverageGap) -> (&'static str, &'static str) {
+    match gap {
+        CoverageGap::UncoveredLines { .. } => ("uncovered-lines", "minor"),
+        CoverageGap::UncoveredRegion { .. } => ("uncovered-region", "minor"),
+        CoverageGap::UncoveredBranch { .. } => ("uncovered-branch", "minor"),
+        CoverageGap::UncoveredInstantiations { .. } => ("uncovered-instantiations", "minor"),
+        CoverageGap::WeakLines { .. } => ("weak-lines", "info"),
+        CoverageGap::WeakRegion { .. } => ("weak-region", "info"),
+        CoverageGap::WeakBranch { .. } => ("weak-branch", "info"),
+    }
+}
+
+/// Hashes `key` with 64-bit FNV-1a, as 16 hex digits.
+fn fingerprint(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Formats line and branch coverage as a Cobertura XML report.
+///
+/// Covers every file in the export that `options` keep, with paths
+/// relative to `base` where possible. With `exclude_tests`, test code is
+/// left out of the lines and the rates, which then agree with the summary
+/// of [`analyze_with_options`](crate::analysis::analyze_with_options).
+/// `timestamp` is the report time in seconds since the Unix epoch.
+#[must_use]
+pub fn format_cobertura(
+    export: &CoverageExport,
+    options: &AnalysisOptions,
+    base: &Path,
+    timestamp: u64,
+) -> String {
+    let mut classes = String::new();
+    let mut overall = Totals::default();
+    if let Some(data) = export.data.first() {
+        let test_code = detect_test_code(data, options, &mut SourceCache::new());
+        let file_totals = if options.exclude_tests {
+            totals::recompute(data, &test_code)
+        } else {
+            totals::exported(data)
+        };
+        overall = totals::sum(&file_totals);
+        let file_totals: BTreeMap<&str, &Totals> = file_totals
+            .iter()
+            .map(|f| (f.filename.as_str(), &f.totals))
+            .collect();
+        for file in &data.files {
+            let Some(totals) = file_totals.get(file.filename.as_str()) else {
+                continue;
+            };
+            let path = Path::new(&file.filename).strip_prefix(base).map_or_else(
+                |_| file.filename.clone(),
+                |p| p.to_string_lossy().into_owned(),
+            );
+            let mut branches: BTreeMap<u64, (u64, u64)> = BTreeMap::new();
+            for ((line, _), (true_count, false_count)) in merge_branches(&file.branches) {
+                let entry = branches.entry(line).or_default();
+                entry.0 += u64::from(true_count > 0) + u64::from(false_count > 0);
+                entry.1 += 2;
+            }
+            let mut lines = String::new();
+            for line in line_coverage(&file.segments) {
+                if test_code.contains(&file.filename, line.line) {
+                    continue;
+                }
+                // write to a String is infallible.
+                write!(
+                    lines,
+                    "<line number=\"{}\" hits=\"{}\"",
+                    line.line, line.count
+                )
+                .unwrap();
+                match branches.get(&line.line) {
+                    Some(&(taken, total)) => writeln!(
+                        lines,
+                        " branch=\"true\" condition-coverage=\"{}% ({taken}/{total})\"/>",
+                        taken * 100 / total
+                    )
+                    .unwrap(),
+                    None => lines.push_str(" branch=\"false\"/>\n"),
+                }
+            }
+            writeln!(
+                classes,
+                "<class name=\"{path}\" filename=\"{path}\" line-rate=\"{}\" branch-rate=\"{}\" complexity=\"0\">\n\
+                 <methods/>\n<lines>\n{lines}</lines>\n</class>",
+                rate(totals.lines),
+                rate(totals.branches),
+                path = escape_xml(&path),
+            )
+            .unwrap();
+        }
+    }
+    let line_rate = rate(overall.lines);
+    let branch_rate = rate(overall.branches);
+    format!(
+        "<?xml version=\"1.0\" ?>\n\
+         <!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">\n\
+         <coverage line-rate=\"{line_rate}\" branch-rate=\"{branch_rate}\" \
+         lines-covered=\"{}\" lines-valid=\"{}\" \
+         branches-covered=\"{}\" branches-valid=\"{}\" \
+         complexity=\"0\" version=\"0\" timestamp=\"{timestamp}\">\n\
+         <sources>\n<source>{}</source>\n</sources>\n\
+         <packages>\n<package name=\"\" line-rate=\"{line_rate}\" branch-rate=\"{branch_rate}\" complexity=\"0\">\n\
+         <classes>\n{classes}</classes>\n</package>\n</packages>\n</coverage>\n",
+        overall.lines.covered,
+        overall.lines.count,
+        overall.branches.covered,
+        overall.branches.count,
+        escape_xml(&base.to_string_lossy()),
+    )
+}
+
+/// Formats a covered ratio with four decimals, or `0` when there is
+/// nothing to cover, like [`Counts::percent`].
+fn rate(counts: Counts) -> String {
+    format!("{:.4}", counts.percent() / 100.0)
+}
+
+/// Formats the line coverage as a log line for GitLab's coverage regex,
+/// [`COVERAGE_REGEX`].
+#[must_use]
+pub fn format_coverage_line(summary: &CoverageSummary) -> String {
+    format!("Coverage: {:.2}%", summary.lines_percent)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::FileGaps;
+
+    fn result(gaps: Vec<CoverageGap>) -> AnalysisResult {
+        AnalysisResult {
+            files: vec![FileGaps {
+                filename: "./src/lib.rs".to_string(),
+                gaps,
+                functions: vec![],
+            }],
+            ..AnalysisResult::default()
+        }
+    }
+
+    #[test]
+    fn test_format_code_quality() {
+        let gaps = vec![
+            CoverageGap::UncoveredLines {
+                start_line: 8,
+                end_line: 9,
+            },
+            CoverageGap::WeakBranch {
+                line: 12,
+                col: 5,
+                true_count: 1,
+                false_count: 9,
+            },
+        ];
+        let report: Value = serde_json::from_str(&format_code_quality(&result(gaps))).unwrap();
+        assert_eq!(
+            report[0],
+            json!({
+                "description": "uncovered lines 8-9",
+                "check_name": "uncovered-lines",
+                "fingerprint": fingerprint("src/lib.rs\0uncovered-lines\x008:1-9"),
+                "severity": "minor",
+                "location": { "path": "src/lib.rs", "lines": { "begin": 8, "end": 9 } },
+            })
+        );
+        assert_eq!(report[1]["check_name"], "weak-branch");
+        assert_eq!(report[1]["severity"], "info");
+        assert_ne!(report[0]["fingerprint"], report[1]["fingerprint"]);
+
+        // Hit counts do not change the fingerprint.
+        let recount = vec![CoverageGap::WeakBranch {
+            line: 12,
+            col: 5,
+            true_count: 2,
+            false_count: 9,
+        }];
+        let rerun: Value = serde_json::from_str(&format_code_quality(&result(recount))).unwrap();
+        assert_eq!(rerun[0]["fingerprint"], report[1]["fingerprint"]);
+        assert_eq!(format_code_quality(&result(vec![])), "[]");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        // FNV-1a 64 reference values.
+        assert_eq!(fingerprint(""), "cbf29ce484222325");
+        assert_eq!(fingerprint("a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn test_format_cobertura() {
+        let export =
+            crate::parse_json(include_str!("../tests/fixtures/with-branches.json")).unwrap();
+        let xml = format_cobertura(
+            &export,
+            &AnalysisOptions::default(),
+            Path::new("/nonexistent"),
+            42,
+        );
+        assert!(xml.starts_with("<?xml version=\"1.0\" ?>\n"));
+        // The rates follow the exported totals, as the summary does.
+        assert!(xml.contains(
+            "<coverage line-rate=\"0.8333\" branch-rate=\"0.6667\" lines-covered=\"10\" \
+             lines-valid=\"12\" branches-covered=\"4\" branches-valid=\"6\" complexity=\"0\" \
+             version=\"0\" timestamp=\"42\">"
+        ));
+        assert!(xml.contains("<class name=\"src/lib.rs\" filename=\"src/lib.rs\""));
+        assert!(xml.contains(
+            "<line number=\"5\" hits=\"5\" branch=\"true\" condition-coverage=\"50% (1/2)\"/>"
+        ));
+        assert!(xml.contains("<source>/nonexistent</source>"));
+    }
+
+    #[test]
+    fn test_format_cobertura_leaves_out_test_code() {
+        let json = include_str!("../tests/fixtures/with-branches.json")
+            .replace("\"src/lib.rs\"", "\"tests/it.rs\"");
+        let export = crate::parse_json(&json).unwrap();
+        let options = AnalysisOptions {
+            exclude_tests: true,
+            ..AnalysisOptions::default()
+        };
+        let xml = format_cobertura(&export, &options, Path::new("/nonexistent"), 42);
+        assert!(
+            xml.contains(
+                "<coverage line-rate=\"0.0000\" branch-rate=\"0.0000\" lines-covered=\"0\""
+            )
+        );
+        assert!(!xml.contains("<class "));
+        let summary = crate::analysis::analyze_with_options(&export, &options)
+            .unwrap()
+            .summary;
+        assert_eq!(format_coverage_line(&summary), "Coverage: 0.00%");
+    }
+
+    #[test]
+    fn test_format_coverage_line() {
+        let summary = CoverageSummary {
+            lines_percent: 92.3456,
+            ..Totals::default().summary()
+        };
+        assert_eq!(format_coverage_line(&summary), "Coverage: 92.35%");
+    }
+
+    #[test]
+    fn test_rate() {
+        assert_eq!(rate(Counts::default()), "0.0000");
+        assert_eq!(
+            rate(Counts {
+                covered: 1,
+                count: 3
+            }),
+            "0.3333"
+        );
+    }
+}
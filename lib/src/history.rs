@@ -0,0 +1,224 @@
+//! Coverage history.
+//!
+//! Each run appends one [`HistoryEntry`] to a JSON-lines file, holding the
+//! overall summary and per-file counts along with the git commit and time
+//! of the run. The history shows how coverage moved over recent runs and
+//! which files have been losing coverage.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{AnalysisResult, CoverageSummary};
+use crate::totals::Totals;
+
+/// The coverage of one run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Abbreviated hash of the checked-out commit, if known.
+    pub commit: Option<String>,
+    /// Time of the run, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Overall coverage percentages.
+    pub summary: CoverageSummary,
+    /// Counts per file, keyed by file path.
+    pub files: BTreeMap<String, Totals>,
+}
+
+impl HistoryEntry {
+    /// Records the coverage of `result`.
+    ///
+    /// File paths are stored as they appear in `result`, so relativize them
+    /// first to keep the history valid when the workspace moves.
+    #[must_use]
+    pub fn new(result: &AnalysisResult, commit: Option<String>, timestamp: u64) -> Self {
+        Self {
+            commit,
+            timestamp,
+            summary: result.summary.clone(),
+            files: result
+                .file_totals
+                .iter()
+                .map(|f| (f.filename.clone(), f.totals))
+                .collect(),
+        }
+    }
+
+    /// Serializes the entry as one line of JSON, without a trailing
+    /// newline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails.
+    pub fn to_json_line(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+/// Parses a JSON-lines history, oldest run first. Blank lines are ignored.
+///
+/// # Errors
+///
+/// Returns an error if a line is not a valid entry.
+pub fn parse_history(jsonl: &str) -> Result<Vec<HistoryEntry>, serde_json::Error> {
+    jsonl
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
+
+/// A file whose line coverage fell over recent runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decline<'a> {
+    /// File path as recorded.
+    pub filename: &'a str,
+    /// Line coverage percentage in the oldest of the runs.
+    pub first_percent: f64,
+    /// Line coverage percentage in the latest run.
+    pub last_percent: f64,
+}
+
+/// Finds files whose line coverage has been dropping over the last `runs`
+/// entries.
+///
+/// A file is dropping when its coverage never rose from one run to the
+/// next and ended lower than it started. Only files present in all of
+/// those runs are considered.
+#[must_use]
+pub fn declining_files(entries: &[HistoryEntry], runs: usize) -> Vec<Decline<'_>> {
+    let recent = &entries[entries.len().saturating_sub(runs)..];
+    let Some(latest) = recent.last().filter(|_| recent.len() >= 2) else {
+        return Vec::new();
+    };
+    latest
+        .files
+        .keys()
+        .filter_map(|filename| {
+            let percents = recent
+                .iter()
+                .map(|e| e.files.get(filename).map(|t| t.lines.percent()))
+                .collect::<Option<Vec<f64>>>()?;
+            let (first, last) = (percents[0], percents[percents.len() - 1]);
+            (percents.windows(2).all(|w| w[1] <= w[0]) && last < first).then_some(Decline {
+                filename,
+                first_percent: first,
+                last_percent: last,
+            })
+        })
+        .collect()
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM` in UTC.
+#[must_use]
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86_400, timestamp % 86_400);
+    // Converts days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`, shifted so that years start in March.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        seconds / 3_600,
+        seconds % 3_600 / 60
+    )
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::totals::Counts;
+
+    fn entry(files: &[(&str, u64)]) -> HistoryEntry {
+        HistoryEntry {
+            commit: Some("abc1234".to_string()),
+            timestamp: 0,
+            summary: Totals::default().summary(),
+            files: files
+                .iter()
+                .map(|(filename, covered)| {
+                    let totals = Totals {
+                        lines: Counts {
+                            covered: *covered,
+                            count: 10,
+                        },
+                        ..Totals::default()
+                    };
+                    ((*filename).to_string(), totals)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_history_round_trip() {
+        let entries = [entry(&[("./src/lib.rs", 9)]), entry(&[])];
+        let jsonl: String = entries
+            .iter()
+            .map(|e| format!("{}\n\n", e.to_json_line().unwrap()))
+            .collect();
+        let parsed = parse_history(&jsonl).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].commit.as_deref(), Some("abc1234"));
+        assert_eq!(parsed[0].files, entries[0].files);
+        assert!(parse_history("{").is_err());
+    }
+
+    #[test]
+    fn test_new_entry_from_result() {
+        let mut result = crate::analyze_json(include_str!(
+            "../tests/fixtures/show-missing-lines-multi-missing.json"
+        ))
+        .unwrap();
+        result.relativize_paths(std::path::Path::new("/nonexistent"));
+        let entry = HistoryEntry::new(&result, None, 42);
+        assert_eq!(entry.timestamp, 42);
+        assert_eq!(entry.files.len(), result.file_totals.len());
+        assert!((entry.summary.lines_percent - result.summary.lines_percent).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_declining_files() {
+        let entries = [
+            entry(&[("a.rs", 5), ("b.rs", 9)]),
+            entry(&[("a.rs", 9), ("b.rs", 9), ("c.rs", 9)]),
+            entry(&[("a.rs", 8), ("b.rs", 9), ("c.rs", 8)]),
+            entry(&[("a.rs", 8), ("b.rs", 7), ("c.rs", 9)]),
+        ];
+        let names = |runs| -> Vec<&str> {
+            declining_files(&entries, runs)
+                .iter()
+                .map(|d| d.filename)
+                .collect()
+        };
+        // `c.rs` recovered in the last run.
+        assert_eq!(names(3), vec!["a.rs", "b.rs"]);
+        // `a.rs` rose in the second run, and `c.rs` is missing from the
+        // first.
+        assert_eq!(names(4), vec!["b.rs"]);
+        assert!(names(1).is_empty());
+        let decline = &declining_files(&entries, 2)[0];
+        assert!((decline.first_percent - 90.0).abs() < f64::EPSILON);
+        assert!((decline.last_percent - 70.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_868_740), "2000-02-29 23:59");
+        assert_eq!(format_timestamp(1_792_326_840), "2026-10-18 12:34");
+    }
+}
@@ -0,0 +1,477 @@
+//! Standalone HTML report.
+//!
+//! Renders an [`AnalysisResult`] as a single self-contained HTML page that
+//! only covers files with gaps. Each file lists its gaps exactly as the
+//! text report does, summarizes its functions, and shows its source with
+//! uncovered lines, regions, and branch conditions highlighted.
+
+use std::fmt::Write;
+
+use crate::analysis::{AnalysisResult, CoverageGap, FileGaps};
+use crate::format::{escape_xml, format_gap, format_summary, quickfix_entry};
+use crate::source::SourceCache;
+use crate::totals::{Counts, FileTotals};
+
+/// Styles embedded in the report.
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }
+td.num { text-align: right; }
+ul.gaps { font-family: monospace; }
+pre { background: #fafafa; border: 1px solid #ddd; padding: 0.5em 0; overflow-x: auto; }
+pre span.line { display: block; padding-right: 1em; }
+pre span.ln { display: inline-block; width: 4em; padding-right: 1em; text-align: right; color: #999; user-select: none; }
+.uncovered { background: #fdd; }
+.weak { background: #ffefc2; }
+.partial { background: #fff7e0; }
+mark.region { background: #f99; }
+mark.branch { background: #f9a; text-decoration: underline wavy #c00; }
+mark.weak-region { background: #fc6; }
+";
+
+/// Formats an analysis result as a self-contained HTML page.
+///
+/// Source files are read through `sources`; files that cannot be read are
+/// listed without their source.
+#[must_use]
+pub fn format_html(result: &AnalysisResult, sources: &mut SourceCache) -> String {
+    let mut output = String::from(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Code Coverage</title>\n<style>\n",
+    );
+    output.push_str(STYLE);
+    output.push_str("</style>\n</head>\n<body>\n<h1>Code Coverage</h1>\n");
+    let mut summary = String::new();
+    format_summary(&mut summary, &result.summary);
+    // writeln to a String is infallible.
+    writeln!(output, "<p>{}</p>", escape_xml(&summary)).unwrap();
+
+    let files: Vec<&FileGaps> = result.files.iter().filter(|f| !f.gaps.is_empty()).collect();
+    if files.is_empty() {
+        output.push_str("<p>No coverage gaps.</p>\n");
+    } else {
+        format_file_table(&mut output, result, &files);
+        for (index, file) in files.iter().enumerate() {
+            let totals = file_totals(result, &file.filename);
+            format_file(&mut output, index, file, totals, sources);
+        }
+    }
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+/// Returns the totals of `filename`, if the result has them.
+fn file_totals<'a>(result: &'a AnalysisResult, filename: &str) -> Option<&'a FileTotals> {
+    result.file_totals.iter().find(|t| t.filename == filename)
+}
+
+/// Formats the table of files with gaps, linking to their sections.
+fn format_file_table(output: &mut String, result: &AnalysisResult, files: &[&FileGaps]) {
+    output.push_str(
+        "<table>\n<tr><th>File</th><th>Lines</th><th>Regions</th>\
+         <th>Branches</th><th>Functions</th><th>Gaps</th></tr>\n",
+    );
+    for (index, file) in files.iter().enumerate() {
+        let cells = file_totals(result, &file.filename).map_or_else(
+            || "<td></td>".repeat(4),
+            |t| {
+                [
+                    t.totals.lines,
+                    t.totals.regions,
+                    t.totals.branches,
+                    t.totals.functions,
+                ]
+                .into_iter()
+                .map(|counts| format!("<td class=\"num\">{}</td>", format_counts(counts)))
+                .collect()
+            },
+        );
+        // writeln to a String is infallible.
+        writeln!(
+            output,
+            "<tr><td><a href=\"#file-{index}\">{}</a></td>{cells}<td class=\"num\">{}</td></tr>",
+            escape_xml(&file.filename),
+            file.gaps.len()
+        )
+        .unwrap();
+    }
+    output.push_str("</table>\n");
+}
+
+/// Formats covered and total counts with the percentage, or `-` when
+/// there is nothing to count.
+fn format_counts(counts: Counts) -> String {
+    if counts.count == 0 {
+        "-".to_string()
+    } else {
+        format!(
+            "{}/{} ({:.1}%)",
+            counts.covered,
+            counts.count,
+            counts.percent()
+        )
+    }
+}
+
+/// Formats the section of one file: summary, gaps, functions, and source.
+fn format_file(
+    output: &mut String,
+    index: usize,
+    file: &FileGaps,
+    totals: Option<&FileTotals>,
+    sources: &mut SourceCache,
+) {
+    // writeln to a String is infallible.
+    writeln!(
+        output,
+        "<section id=\"file-{index}\">\n<h2>{}</h2>",
+        escape_xml(&file.filename)
+    )
+    .unwrap();
+    if let Some(totals) = totals {
+        let mut summary = String::new();
+        format_summary(&mut summary, &totals.totals.summary());
+        writeln!(output, "<p>{}</p>", escape_xml(&summary)).unwrap();
+    }
+
+    output.push_str("<ul class=\"gaps\">\n");
+    for gap in &file.gaps {
+        let mut line = String::new();
+        format_gap(&mut line, &file.filename, gap);
+        writeln!(output, "<li>{}</li>", escape_xml(line.trim_end())).unwrap();
+    }
+    output.push_str("</ul>\n");
+
+    if !file.functions.is_empty() {
+        output.push_str(
+            "<table>\n<tr><th>Function</th><th>Lines</th><th>Runs</th><th>Gaps</th></tr>\n",
+        );
+        for function in &file.functions {
+            let gaps = file
+                .gaps
+                .iter()
+                .filter(|gap| {
+                    file.function_at(gap.start_line())
+                        .is_some_and(|f| std::ptr::eq(f, function))
+                })
+                .count();
+            writeln!(
+                output,
+                "<tr><td>{}</td><td>{}-{}</td><td class=\"num\">{}</td><td class=\"num\">{gaps}</td></tr>",
+                escape_xml(&function.name),
+                function.line_start,
+                function.line_end,
+                function.count
+            )
+            .unwrap();
+        }
+        output.push_str("</table>\n");
+    }
+
+    match sources.get(&file.filename) {
+        Some(source) => format_source(output, source, &file.gaps),
+        None => output.push_str("<p>Source not available.</p>\n"),
+    }
+    output.push_str("</section>\n");
+}
+
+/// A highlighted span on one source line, in byte offsets.
+struct Mark<'a> {
+    start: usize,
+    end: usize,
+    class: &'static str,
+    title: &'a str,
+}
+
+/// Formats `source` with line numbers, highlighting the gaps.
+fn format_source(output: &mut String, source: &str, gaps: &[CoverageGap]) {
+    let titles: Vec<String> = gaps.iter().map(|gap| quickfix_entry(gap).2).collect();
+    output.push_str("<pre>");
+    for (index, text) in source.lines().enumerate() {
+        let line = index as u64 + 1;
+        let mut class = "";
+        let mut marks = Vec::new();
+        for (gap, title) in gaps.iter().zip(&titles) {
+            let (line_class, span) = highlight(gap, line);
+            if !line_class.is_empty() && (class.is_empty() || line_class != "partial") {
+                class = line_class;
+            }
+            if let Some((start, end, mark_class)) = span {
+                marks.push(Mark {
+                    start: clip(text, start),
+                    end: clip(text, end),
+                    class: mark_class,
+                    title,
+                });
+            }
+        }
+        // write to a String is infallible.
+        write!(
+            output,
+            "<span class=\"line {class}\"><span class=\"ln\">{line}</span>"
+        )
+        .unwrap();
+        format_marked_line(output, text, &marks);
+        output.push_str("</span>");
+    }
+    output.push_str("</pre>\n");
+}
+
+/// Returns the class `gap` gives to `line`, and the byte range, with its
+/// class, that it highlights on that line.
+///
+/// Ranges use 0-based offsets, with `usize::MAX` for the end of the line.
+fn highlight(gap: &CoverageGap, line: u64) -> (&'static str, Option<(usize, usize, &'static str)>) {
+    let span = |(line_start, col_start): (u64, u64), (line_end, col_end): (u64, u64), class| {
+        (line_start..=line_end).contains(&line).then(|| {
+            let offset = |col: u64| usize::try_from(col.saturating_sub(1)).unwrap_or(usize::MAX);
+            let start = if line == line_start {
+                offset(col_start)
+            } else {
+                0
+            };
+            let end = if line == line_end {
+                offset(col_end)
+            } else {
+                usize::MAX
+            };
+            (start, end, class)
+        })
+    };
+    match gap {
+        CoverageGap::UncoveredLines {
+            start_line,
+            end_line,
+        } if (*start_line..=*end_line).contains(&line) => ("uncovered", None),
+        CoverageGap::WeakLines {
+            start_line,
+            end_line,
+            ..
+        } if (*start_line..=*end_line).contains(&line) => ("weak", None),
+        CoverageGap::UncoveredRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+        } => (
+            if line == *line_start { "partial" } else { "" },
+            span((*line_start, *col_start), (*line_end, *col_end), "region"),
+        ),
+        CoverageGap::WeakRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+            ..
+        } => (
+            "",
+            span(
+                (*line_start, *col_start),
+                (*line_end, *col_end),
+                "weak-region",
+            ),
+        ),
+        CoverageGap::UncoveredBranch {
+            line: line_start,
+            col,
+            line_end,
+            col_end,
+            ..
+        } => (
+            if line == *line_start { "partial" } else { "" },
+            span((*line_start, *col), (*line_end, *col_end), "branch"),
+        ),
+        CoverageGap::UncoveredInstantiations { line: gap_line, .. } if *gap_line == line => {
+            ("partial", None)
+        }
+        _ => ("", None),
+    }
+}
+
+/// Clamps a byte offset into `text`, moving it back to a character
+/// boundary.
+fn clip(text: &str, offset: usize) -> usize {
+    text.floor_char_boundary(offset.min(text.len()))
+}
+
+/// Formats one source line, wrapping the marked ranges in `<mark>`.
+///
+/// Where marks overlap, branches win over regions, and earlier gaps over
+/// later ones.
+fn format_marked_line(output: &mut String, text: &str, marks: &[Mark<'_>]) {
+    let mut bounds: Vec<usize> = marks
+        .iter()
+        .flat_map(|m| [m.start, m.end])
+        .chain([0, text.len()])
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+    let mut open: Option<usize> = None;
+    for window in bounds.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let active = marks
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.start <= start && end <= m.end && m.start < m.end)
+            .min_by_key(|(i, m)| (m.class != "branch", *i))
+            .map(|(i, _)| i);
+        if active != open {
+            if open.is_some() {
+                output.push_str("</mark>");
+            }
+            if let Some(i) = active {
+                let mark = &marks[i];
+                // write to a String is infallible.
+                write!(
+                    output,
+                    "<mark class=\"{}\" title=\"{}\">",
+                    mark.class,
+                    escape_xml(mark.title)
+                )
+                .unwrap();
+            }
+            open = active;
+        }
+        output.push_str(&escape_xml(&text[start..end]));
+    }
+    if open.is_some() {
+        output.push_str("</mark>");
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::FunctionSpan;
+    use crate::totals::Totals;
+
+    const SOURCE: &str = "fn check(x: i32) -> bool {\n    if x > 0 && x < 10 {\n        return true;\n    }\n    false\n}\n";
+
+    fn result(gaps: Vec<CoverageGap>) -> AnalysisResult {
+        let totals = Totals {
+            lines: Counts {
+                covered: 4,
+                count: 6,
+            },
+            ..Totals::default()
+        };
+        AnalysisResult {
+            files: vec![
+                FileGaps {
+                    filename: "src/check.rs".to_string(),
+                    gaps,
+                    functions: vec![FunctionSpan {
+                        name: "app::check".to_string(),
+                        line_start: 1,
+                        col_start: 1,
+                        line_end: 6,
+                        col_end: 2,
+                        count: 3,
+                        visibility: None,
+                    }],
+                },
+                FileGaps {
+                    filename: "src/covered.rs".to_string(),
+                    gaps: vec![],
+                    functions: vec![],
+                },
+            ],
+            summary: totals.summary(),
+            file_totals: vec![FileTotals {
+                filename: "src/check.rs".to_string(),
+                totals,
+            }],
+            ..AnalysisResult::default()
+        }
+    }
+
+    fn sources() -> SourceCache {
+        let mut sources = SourceCache::new();
+        sources.insert("src/check.rs", SOURCE);
+        sources
+    }
+
+    #[test]
+    fn test_format_html_lists_files_with_gaps() {
+        let html = format_html(
+            &result(vec![CoverageGap::UncoveredLines {
+                start_line: 3,
+                end_line: 3,
+            }]),
+            &mut sources(),
+        );
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p>Lines: 66.7% | Regions: 0.0% | Functions: 0.0%</p>"));
+        assert!(html.contains(
+            "<tr><td><a href=\"#file-0\">src/check.rs</a></td><td class=\"num\">4/6 (66.7%)</td>"
+        ));
+        assert!(!html.contains("src/covered.rs"));
+        assert!(html.contains("<li>src/check.rs:3 UNCOVERED</li>"));
+        assert!(html.contains(
+            "<tr><td>app::check</td><td>1-6</td><td class=\"num\">3</td><td class=\"num\">1</td></tr>"
+        ));
+        assert!(html.contains(
+            "<span class=\"line uncovered\"><span class=\"ln\">3</span>        return true;</span>"
+        ));
+        assert!(html.contains("<span class=\"line \"><span class=\"ln\">5</span>    false</span>"));
+    }
+
+    #[test]
+    fn test_format_html_highlights_regions_and_branches() {
+        let html = format_html(
+            &result(vec![
+                CoverageGap::UncoveredBranch {
+                    line: 2,
+                    col: 17,
+                    line_end: 2,
+                    col_end: 23,
+                    true_count: 3,
+                    false_count: 0,
+                    condition: Some("x < 10".to_string()),
+                },
+                CoverageGap::UncoveredRegion {
+                    line_start: 2,
+                    col_start: 17,
+                    line_end: 4,
+                    col_end: 6,
+                },
+            ]),
+            &mut sources(),
+        );
+        assert!(html.contains(
+            "<span class=\"line partial\"><span class=\"ln\">2</span>    if x &gt; 0 &amp;&amp; \
+             <mark class=\"branch\" title=\"branch `x &lt; 10` never false\">x &lt; 10</mark>\
+             <mark class=\"region\" title=\"uncovered region to 4:6\"> {</mark></span>"
+        ));
+        assert!(html.contains(
+            "<span class=\"ln\">3</span><mark class=\"region\" title=\"uncovered region to 4:6\">        return true;</mark>"
+        ));
+        assert!(html.contains(
+            "<span class=\"ln\">4</span><mark class=\"region\" title=\"uncovered region to 4:6\">    }</mark></span>"
+        ));
+    }
+
+    #[test]
+    fn test_format_html_without_gaps_or_source() {
+        let html = format_html(&result(vec![]), &mut SourceCache::new());
+        assert!(html.contains("<p>No coverage gaps.</p>"));
+        assert!(!html.contains("<table>"));
+
+        let html = format_html(
+            &result(vec![CoverageGap::UncoveredLines {
+                start_line: 9,
+                end_line: 9,
+            }]),
+            &mut SourceCache::new(),
+        );
+        assert!(html.contains("<p>Source not available.</p>"));
+    }
+
+    #[test]
+    fn test_clip() {
+        assert_eq!(clip("aé", 2), 1);
+        assert_eq!(clip("ab", usize::MAX), 2);
+    }
+}
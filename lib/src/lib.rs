@@ -6,10 +6,27 @@
 //! regions, and branches lack coverage.
 
 pub mod analysis;
+pub mod badge;
+pub mod comment;
+pub mod diff;
+pub mod exclusions;
 pub mod format;
+pub mod gitlab;
+pub mod history;
+pub mod html;
+pub mod lsp;
+pub mod mcp;
 pub mod model;
+pub mod per_test;
+pub mod pretty;
+pub mod public_api;
+pub mod score;
+pub mod source;
+pub mod test_code;
+pub mod totals;
+pub mod workspace;
 
-use analysis::AnalysisResult;
+use analysis::{AnalysisOptions, AnalysisResult};
 use model::CoverageExport;
 
 /// Parses coverage JSON from a string and analyzes it for coverage gaps.
@@ -21,11 +38,32 @@ use model::CoverageExport;
 ///
 /// Returns an error if the JSON is malformed or the coverage data is empty.
 pub fn analyze_json(json: &str) -> Result<AnalysisResult, Error> {
-    let export: CoverageExport = serde_json::from_str(json)?;
-    let result = analysis::analyze(&export)?;
+    analyze_json_with_options(json, &AnalysisOptions::default())
+}
+
+/// Parses coverage JSON from a string and analyzes it with the given options.
+///
+/// # Errors
+///
+/// Returns an error if the JSON is malformed or the coverage data is empty.
+pub fn analyze_json_with_options(
+    json: &str,
+    options: &AnalysisOptions,
+) -> Result<AnalysisResult, Error> {
+    let export = parse_json(json)?;
+    let result = analysis::analyze_with_options(&export, options)?;
     Ok(result)
 }
 
+/// Parses coverage JSON from a string without analyzing it.
+///
+/// # Errors
+///
+/// Returns an error if the JSON is malformed.
+pub fn parse_json(json: &str) -> Result<CoverageExport, Error> {
+    Ok(serde_json::from_str(json)?)
+}
+
 /// Parses coverage JSON and returns formatted compact output.
 ///
 /// Convenience function that combines parsing, analysis, and formatting.
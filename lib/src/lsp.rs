@@ -0,0 +1,559 @@
+//! Language Server Protocol diagnostics.
+//!
+//! Publishes coverage gaps as informational diagnostics on the documents
+//! an editor has open, so uncovered code shows up without a dedicated
+//! plugin. The server only tracks open documents and the latest
+//! [`AnalysisResult`]; reading coverage and the stdio transport are left to
+//! the caller, which uses [`read_message`] and [`write_message`] for the
+//! `Content-Length` framing.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::analysis::{AnalysisResult, CoverageGap, FileGaps};
+use crate::format::missing_direction;
+
+/// JSON-RPC error code for a message that is not valid JSON.
+const PARSE_ERROR: i64 = -32700;
+
+/// LSP error code for an unknown method.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// LSP diagnostic severity for informational messages.
+const SEVERITY_INFORMATION: u64 = 3;
+
+/// LSP text document sync kind that sends the full text on every change.
+const SYNC_FULL: u64 = 1;
+
+/// A JSON-RPC message.
+pub type Message = Value;
+
+/// Errors reading a framed message.
+#[derive(Debug, thiserror::Error)]
+pub enum FrameError {
+    /// Reading from the stream failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The header block had no valid `Content-Length`.
+    #[error("missing or invalid Content-Length header")]
+    MissingLength,
+    /// The message body is not valid JSON.
+    #[error("invalid message: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Reads one `Content-Length` framed message, or `None` at end of input.
+///
+/// # Errors
+///
+/// Returns an error if reading fails, the headers lack a length, or the
+/// body is not JSON.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>, FrameError> {
+    let mut length = None;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 {
+            return if length.is_none() {
+                Ok(None)
+            } else {
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+            };
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| FrameError::MissingLength)?,
+            );
+        }
+    }
+    let mut body = vec![0; length.ok_or(FrameError::MissingLength)?];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Writes `message` with a `Content-Length` header and flushes.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+/// A language server publishing coverage gaps as diagnostics.
+pub struct Server {
+    /// Directory that relative file paths in the result are relative to.
+    root: PathBuf,
+    /// Text of each open document, keyed by URI.
+    documents: BTreeMap<String, String>,
+    result: Option<AnalysisResult>,
+    exited: bool,
+}
+
+impl Server {
+    /// Creates a server without coverage data. Relative file paths in
+    /// results are resolved against `root`.
+    #[must_use]
+    pub const fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            documents: BTreeMap::new(),
+            result: None,
+            exited: false,
+        }
+    }
+
+    /// Returns whether the client sent `exit`.
+    #[must_use]
+    pub const fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handles one message from the client and returns the messages to
+    /// send back: a response for requests, and diagnostics for documents
+    /// that were opened or closed.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Value::Null);
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(Value::as_str)
+            .map(ToString::to_string);
+        if let Some(id) = message.get("id") {
+            let response = match method {
+                "initialize" => json!({ "jsonrpc": "2.0", "id": id, "result": {
+                    "capabilities": {
+                        "textDocumentSync": { "openClose": true, "change": SYNC_FULL },
+                    },
+                    "serverInfo": { "name": "llvm-cov-easy", "version": env!("CARGO_PKG_VERSION") },
+                }}),
+                "shutdown" => json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+                _ => json!({ "jsonrpc": "2.0", "id": id, "error": {
+                    "code": METHOD_NOT_FOUND,
+                    "message": format!("unknown method `{method}`"),
+                }}),
+            };
+            return vec![response];
+        }
+        match (method, uri) {
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params.pointer("/textDocument/text").and_then(Value::as_str);
+                self.documents
+                    .insert(uri.clone(), text.unwrap_or_default().to_string());
+                vec![self.publish(&uri)]
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // With full sync the last change holds the whole text.
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Value::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Value::as_str);
+                if let (Some(document), Some(text)) = (self.documents.get_mut(&uri), text) {
+                    *document = text.to_string();
+                }
+                Vec::new()
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                vec![publish_diagnostics(&uri, &[])]
+            }
+            ("exit", _) => {
+                self.exited = true;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Replaces the coverage data and returns fresh diagnostics for every
+    /// open document.
+    pub fn set_result(&mut self, result: AnalysisResult) -> Vec<Value> {
+        self.result = Some(result);
+        self.documents.keys().map(|uri| self.publish(uri)).collect()
+    }
+
+    /// Builds the diagnostics notification for the open document `uri`.
+    fn publish(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map(String::as_str);
+        let diagnostics: Vec<Value> = self
+            .file(uri)
+            .map(|file| file.gaps.iter().map(|gap| diagnostic(gap, text)).collect())
+            .unwrap_or_default();
+        publish_diagnostics(uri, &diagnostics)
+    }
+
+    /// Finds the gaps of the file at `uri`.
+    fn file(&self, uri: &str) -> Option<&FileGaps> {
+        let path = uri_to_path(uri)?;
+        self.result
+            .as_ref()?
+            .files
+            .iter()
+            .find(|file| self.root.join(&file.filename) == path)
+    }
+}
+
+/// Builds a `window/logMessage` notification carrying a warning, e.g. for
+/// coverage data that could not be read.
+#[must_use]
+pub fn log_warning(message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "window/logMessage",
+        "params": { "type": 2, "message": message },
+    })
+}
+
+/// Builds a `textDocument/publishDiagnostics` notification.
+fn publish_diagnostics(uri: &str, diagnostics: &[Value]) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Builds the error response to a message whose body is not valid JSON.
+///
+/// [`read_message`] consumes the whole body before failing with
+/// [`FrameError::Json`], so the server can answer with this and keep
+/// reading.
+#[must_use]
+pub fn parse_error(error: &serde_json::Error) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": PARSE_ERROR, "message": format!("parse error: {error}") },
+    })
+}
+
+/// Converts a `file://` URI to a path, decoding percent escapes. Windows
+/// URIs such as `file:///C:/src/lib.rs` lose the slash before the drive.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escape = (encoded[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        if let Some(byte) = escape {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(encoded[i]);
+            i += 1;
+        }
+    }
+    let decoded = String::from_utf8_lossy(&decoded);
+    let path = match decoded.as_bytes() {
+        [b'/', drive, b':', rest @ ..]
+            if drive.is_ascii_alphabetic() && rest.first().is_none_or(|&b| b == b'/') =>
+        {
+            &decoded[1..]
+        }
+        _ => &*decoded,
+    };
+    Some(Path::new(path).to_path_buf())
+}
+
+/// Builds the diagnostic for `gap`, using the document `text` to convert
+/// byte columns to the UTF-16 offsets LSP expects.
+fn diagnostic(gap: &CoverageGap, text: Option<&str>) -> Value {
+    let position = |line: u64, col: u64| json!({ "line": line.saturating_sub(1), "character": character(text, line, col) });
+    // Ranges without an end column run to the start of the next line.
+    let line_end = |line: u64| json!({ "line": line, "character": 0 });
+    let (start, end) = match gap {
+        CoverageGap::UncoveredLines {
+            start_line,
+            end_line,
+        }
+        | CoverageGap::WeakLines {
+            start_line,
+            end_line,
+            ..
+        } => (position(*start_line, 1), line_end(*end_line)),
+        CoverageGap::UncoveredRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+        }
+        | CoverageGap::WeakRegion {
+            line_start,
+            col_start,
+            line_end,
+            col_end,
+            ..
+        } => (
+            position(*line_start, *col_start),
+            position(*line_end, *col_end),
+        ),
+        CoverageGap::UncoveredBranch {
+            line,
+            col,
+            line_end,
+            col_end,
+            ..
+        } => (position(*line, *col), position(*line_end, *col_end)),
+        CoverageGap::UncoveredInstantiations { line, col, .. }
+        | CoverageGap::WeakBranch { line, col, .. } => (position(*line, *col), line_end(*line)),
+    };
+    json!({
+        "range": { "start": start, "end": end },
+        "severity": SEVERITY_INFORMATION,
+        "source": "llvm-cov-easy",
+        "message": message(gap),
+    })
+}
+
+/// Converts a 1-based byte column on a 1-based line to a 0-based UTF-16
+/// offset. Without the line's text, the byte offset is used as is.
+fn character(text: Option<&str>, line: u64, col: u64) -> u64 {
+    let byte = usize::try_from(col.saturating_sub(1)).unwrap_or(usize::MAX);
+    let line_text = usize::try_from(line.saturating_sub(1))
+        .ok()
+        .and_then(|index| text?.lines().nth(index));
+    let Some(line_text) = line_text else {
+        return col.saturating_sub(1);
+    };
+    line_text
+        .char_indices()
+        .take_while(|(i, _)| *i < byte)
+        .map(|(_, c)| c.len_utf16() as u64)
+        .sum()
+}
+
+/// Describes `gap` for a diagnostic message.
+fn message(gap: &CoverageGap) -> String {
+    match gap {
+        CoverageGap::UncoveredLines { .. } => "Not covered by tests".to_string(),
+        CoverageGap::UncoveredRegion { .. } => "Region never executed".to_string(),
+        CoverageGap::UncoveredBranch {
+            true_count,
+            false_count,
+            condition,
+            ..
+        } => {
+            let missing = missing_direction(*true_count, *false_count);
+            match condition {
+                Some(condition) => format!("Branch `{condition}` {missing}"),
+                None => format!("Branch {missing}"),
+            }
+        }
+        CoverageGap::UncoveredInstantiations { unexecuted, .. } => {
+            format!("Instantiations never executed: {}", unexecuted.join(", "))
+        }
+        CoverageGap::WeakLines { max_count, .. } => {
+            format!("Weakly covered: executed at most {max_count} times")
+        }
+        CoverageGap::WeakRegion { count, .. } => {
+            format!("Weakly covered region: executed {count} times")
+        }
+        CoverageGap::WeakBranch {
+            true_count,
+            false_count,
+            ..
+        } => format!("Weakly covered branch: true {true_count} times, false {false_count} times"),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn result() -> AnalysisResult {
+        AnalysisResult {
+            files: vec![FileGaps {
+                filename: "./src/lib.rs".to_string(),
+                gaps: vec![
+                    CoverageGap::UncoveredLines {
+                        start_line: 2,
+                        end_line: 3,
+                    },
+                    CoverageGap::UncoveredBranch {
+                        line: 1,
+                        col: 9,
+                        line_end: 1,
+                        col_end: 14,
+                        true_count: 0,
+                        false_count: 4,
+                        condition: Some("x > 0".to_string()),
+                    },
+                ],
+                functions: vec![],
+            }],
+            ..AnalysisResult::default()
+        }
+    }
+
+    fn open(uri: &str, text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "languageId": "rust", "version": 1, "text": text } },
+        })
+    }
+
+    #[test]
+    fn test_framing_round_trip() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &json!({ "id": 1, "text": "é" })).unwrap();
+        assert!(buffer.starts_with(b"Content-Length: 20\r\n\r\n"));
+        buffer.extend_from_slice(b"content-length: 2\r\nContent-Type: x\r\n\r\n{}");
+        let mut reader = buffer.as_slice();
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(json!({ "id": 1, "text": "é" }))
+        );
+        assert_eq!(read_message(&mut reader).unwrap(), Some(json!({})));
+        assert!(read_message(&mut reader).unwrap().is_none());
+
+        assert!(matches!(
+            read_message(&mut b"Content-Type: x\r\n\r\n{}".as_slice()),
+            Err(FrameError::MissingLength)
+        ));
+        assert!(matches!(
+            read_message(&mut b"Content-Length: 1\r\n\r\n{".as_slice()),
+            Err(FrameError::Json(_))
+        ));
+        // A malformed body is consumed, so the next message still reads.
+        let mut reader = b"Content-Length: 1\r\n\r\n{Content-Length: 2\r\n\r\n{}".as_slice();
+        let Err(FrameError::Json(error)) = read_message(&mut reader) else {
+            panic!("expected a JSON error");
+        };
+        assert_eq!(parse_error(&error)["error"]["code"], PARSE_ERROR);
+        assert_eq!(parse_error(&error)["id"], Value::Null);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(json!({})));
+        assert!(matches!(
+            read_message(&mut b"Content-Length: 10\r\n".as_slice()),
+            Err(FrameError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_requests() {
+        let mut server = Server::new(PathBuf::from("/ws"));
+        let responses = server
+            .handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }));
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["textDocumentSync"]["change"],
+            SYNC_FULL
+        );
+        let responses =
+            server.handle(&json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover" }));
+        assert_eq!(responses[0]["error"]["code"], METHOD_NOT_FOUND);
+        let responses = server.handle(&json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }));
+        assert_eq!(responses[0]["result"], Value::Null);
+        assert!(!server.exited());
+        server.handle(&json!({ "jsonrpc": "2.0", "method": "exit" }));
+        assert!(server.exited());
+        assert_eq!(log_warning("oops")["params"]["message"], "oops");
+        assert!(
+            server
+                .handle(&json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_publishes_diagnostics_for_open_documents() {
+        let mut server = Server::new(PathBuf::from("/ws"));
+        let uri = "file:///ws/src/lib.rs";
+        let published = server.handle(&open(uri, "if ü && x > 0 {\n    a();\n}\n"));
+        assert_eq!(published[0]["params"]["diagnostics"], json!([]));
+        server.handle(&open("file:///ws/src/main.rs", ""));
+
+        let published = server.set_result(result());
+        assert_eq!(published.len(), 2);
+        let lib = published
+            .iter()
+            .find(|p| p["params"]["uri"] == uri)
+            .unwrap();
+        let diagnostics = &lib["params"]["diagnostics"];
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({ "start": { "line": 1, "character": 0 }, "end": { "line": 3, "character": 0 } })
+        );
+        assert_eq!(diagnostics[0]["severity"], SEVERITY_INFORMATION);
+        assert_eq!(diagnostics[0]["message"], "Not covered by tests");
+        // `ü` takes two bytes but one UTF-16 unit.
+        assert_eq!(
+            diagnostics[1]["range"],
+            json!({ "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 12 } })
+        );
+        assert_eq!(diagnostics[1]["message"], "Branch `x > 0` never true");
+
+        let closed = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didClose",
+            "params": { "textDocument": { "uri": uri } },
+        }));
+        assert_eq!(closed[0]["params"]["diagnostics"], json!([]));
+        assert_eq!(server.set_result(result()).len(), 1);
+    }
+
+    #[test]
+    fn test_did_change_updates_text() {
+        let mut server = Server::new(PathBuf::from("/ws"));
+        server.handle(&open("file:///ws/src/lib.rs", "if ü && x > 0 {\n"));
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": { "uri": "file:///ws/src/lib.rs", "version": 2 },
+                "contentChanges": [{ "text": "if u && x > 0 {\n" }],
+            },
+        }));
+        let published = server.set_result(result());
+        assert_eq!(
+            published[0]["params"]["diagnostics"][1]["range"]["start"]["character"],
+            8
+        );
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///ws/my%20crate/src/lib.rs"),
+            Some(PathBuf::from("/ws/my crate/src/lib.rs"))
+        );
+        assert_eq!(uri_to_path("file:///a%2"), Some(PathBuf::from("/a%2")));
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+        assert_eq!(
+            uri_to_path("file:///C:/ws/src/lib.rs"),
+            Some(PathBuf::from("C:/ws/src/lib.rs"))
+        );
+        assert_eq!(
+            uri_to_path("file:///c%3A/ws/src/lib.rs"),
+            Some(PathBuf::from("c:/ws/src/lib.rs"))
+        );
+        assert_eq!(uri_to_path("file:///C:"), Some(PathBuf::from("C:")));
+        assert_eq!(uri_to_path("file:///ab:/x"), Some(PathBuf::from("/ab:/x")));
+    }
+
+    #[test]
+    fn test_character_without_text() {
+        assert_eq!(character(None, 3, 5), 4);
+        assert_eq!(character(Some("ab"), 3, 5), 4);
+        assert_eq!(character(Some("ab"), 1, 10), 2);
+    }
+}
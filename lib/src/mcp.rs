@@ -0,0 +1,515 @@
+//! Model Context Protocol server.
+//!
+//! Lets coding agents query coverage through MCP tools instead of parsing a
+//! full report. Messages are JSON-RPC 2.0, one per line. The server keeps
+//! the latest [`AnalysisResult`] in memory: `run_coverage` refreshes it,
+//! and `list_gaps`, `gaps_for_function`, and `summary` answer from it.
+//! Running coverage is left to the caller, which supplies a function that
+//! produces a fresh result.
+
+use serde_json::{Value, json};
+
+use crate::analysis::{AnalysisResult, CoverageGap};
+use crate::format::{format_gap, format_summary};
+
+/// MCP protocol versions the server speaks, newest last.
+const PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// JSON-RPC error code for malformed JSON.
+const PARSE_ERROR: i64 = -32700;
+
+/// JSON-RPC error code for a message that is not a valid request.
+const INVALID_REQUEST: i64 = -32600;
+
+/// JSON-RPC error code for an unknown method.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code for invalid method parameters.
+const INVALID_PARAMS: i64 = -32602;
+
+/// An MCP server over a cached analysis result.
+///
+/// `run` runs coverage with extra arguments for `cargo llvm-cov` and
+/// returns the analysis, or an error message.
+pub struct Server<F> {
+    run: F,
+    result: Option<AnalysisResult>,
+}
+
+impl<F> Server<F>
+where
+    F: FnMut(&[String]) -> Result<AnalysisResult, String>,
+{
+    /// Creates a server with no coverage data yet.
+    pub const fn new(run: F) -> Self {
+        Self { run, result: None }
+    }
+
+    /// Creates a server that starts from an existing result.
+    pub const fn with_result(run: F, result: AnalysisResult) -> Self {
+        Self {
+            run,
+            result: Some(result),
+        }
+    }
+
+    /// Handles one line of input and returns the response line, if the
+    /// message needs one.
+    pub fn handle_line(&mut self, line: &str) -> Option<String> {
+        let response = match serde_json::from_str::<Value>(line) {
+            Ok(message) => self.handle(&message)?,
+            Err(err) => error_response(&Value::Null, PARSE_ERROR, &err.to_string()),
+        };
+        Some(response.to_string())
+    }
+
+    /// Handles one JSON-RPC message and returns the response, or `None`
+    /// for notifications and responses.
+    pub fn handle(&mut self, message: &Value) -> Option<Value> {
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // Responses to requests we never send need no answer.
+            return message
+                .get("id")
+                .is_none()
+                .then(|| error_response(&Value::Null, INVALID_REQUEST, "missing method"));
+        };
+        let id = message.get("id")?;
+        let params = message.get("params").unwrap_or(&Value::Null);
+        let result = match method {
+            "initialize" => Ok(initialize(params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools() })),
+            "tools/call" => self.call_tool(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{method}`"))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    /// Runs a tool and wraps its text output in a tool result.
+    fn call_tool(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let Some(name) = params.get("name").and_then(Value::as_str) else {
+            return Err((INVALID_PARAMS, "missing tool name".to_string()));
+        };
+        let arguments = params.get("arguments").unwrap_or(&Value::Null);
+        let string = |key: &str| arguments.get(key).and_then(Value::as_str);
+        let output = match name {
+            "run_coverage" => {
+                let args: Vec<String> = arguments
+                    .get("args")
+                    .and_then(Value::as_array)
+                    .map(|args| {
+                        args.iter()
+                            .filter_map(Value::as_str)
+                            .map(ToString::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                self.run_coverage(&args)
+            }
+            "list_gaps" => self
+                .cached()
+                .map(|r| list_gaps(r, string("file"), string("kind"))),
+            "gaps_for_function" => match string("name") {
+                Some(function) => self.cached().map(|r| gaps_for_function(r, function)),
+                None => return Err((INVALID_PARAMS, "missing argument `name`".to_string())),
+            },
+            "summary" => self.cached().map(|r| {
+                let mut output = String::new();
+                format_summary(&mut output, &r.summary);
+                output.push('\n');
+                output
+            }),
+            _ => return Err((INVALID_PARAMS, format!("unknown tool `{name}`"))),
+        };
+        let (text, is_error) = match output {
+            Ok(text) => (text, false),
+            Err(text) => (text, true),
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+
+    /// Runs coverage, caches the result, and describes it.
+    fn run_coverage(&mut self, args: &[String]) -> Result<String, String> {
+        let result = (self.run)(args)?;
+        let gaps: usize = result.files.iter().map(|f| f.gaps.len()).sum();
+        let files = result.files.iter().filter(|f| !f.gaps.is_empty()).count();
+        let mut output = format!("Found {gaps} gaps in {files} files\n");
+        format_summary(&mut output, &result.summary);
+        output.push('\n');
+        self.result = Some(result);
+        Ok(output)
+    }
+
+    /// Returns the cached result, or an error asking for a run first.
+    fn cached(&self) -> Result<&AnalysisResult, String> {
+        self.result
+            .as_ref()
+            .ok_or_else(|| "No coverage data yet; call `run_coverage` first".to_string())
+    }
+}
+
+/// Builds a JSON-RPC error response.
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Answers `initialize`, agreeing to the client's protocol version when
+/// it is supported.
+fn initialize(params: &Value) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let version = requested
+        .filter(|v| PROTOCOL_VERSIONS.contains(v))
+        .unwrap_or(PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "llvm-cov-easy", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// Describes the available tools.
+fn tools() -> Value {
+    json!([
+        {
+            "name": "run_coverage",
+            "description": "Run the tests under coverage and cache the result. Returns the gap count and coverage summary.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "args": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Extra arguments for `cargo llvm-cov`, replacing the server's defaults.",
+                    },
+                },
+            },
+        },
+        {
+            "name": "list_gaps",
+            "description": "List coverage gaps from the last run, one per line.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "Only gaps in files whose path ends with this.",
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["lines", "region", "branch", "instantiations", "weak"],
+                        "description": "Only gaps of this kind.",
+                    },
+                },
+            },
+        },
+        {
+            "name": "gaps_for_function",
+            "description": "List coverage gaps inside a function from the last run.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Function name, either the full path or its last segments (e.g. `Parser::parse`).",
+                    },
+                },
+                "required": ["name"],
+            },
+        },
+        {
+            "name": "summary",
+            "description": "Coverage percentages from the last run.",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+    ])
+}
+
+/// Returns the `kind` filter value matching `gap`.
+const fn gap_kind(gap: &CoverageGap) -> &'static str {
+    match gap {
+        CoverageGap::UncoveredLines { .. } => "lines",
+        CoverageGap::UncoveredRegion { .. } => "region",
+        CoverageGap::UncoveredBranch { .. } => "branch",
+        CoverageGap::UncoveredInstantiations { .. } => "instantiations",
+        CoverageGap::WeakLines { .. }
+        | CoverageGap::WeakRegion { .. }
+        | CoverageGap::WeakBranch { .. } => "weak",
+    }
+}
+
+/// Formats the gaps of `result`, optionally only in files ending with
+/// `file` and of kind `kind`.
+fn list_gaps(result: &AnalysisResult, file: Option<&str>, kind: Option<&str>) -> String {
+    let mut output = String::new();
+    for gaps in &result.files {
+        if file.is_some_and(|f| !gaps.filename.ends_with(f.trim_start_matches("./"))) {
+            continue;
+        }
+        for gap in &gaps.gaps {
+            if kind.is_none_or(|k| k == gap_kind(gap)) {
+                format_gap(&mut output, &gaps.filename, gap);
+            }
+        }
+    }
+    if output.is_empty() {
+        output.push_str("No gaps\n");
+    }
+    output
+}
+
+/// Formats the gaps inside functions named `name`.
+///
+/// A function matches when its full name equals `name` or ends with
+/// `::name`.
+fn gaps_for_function(result: &AnalysisResult, name: &str) -> String {
+    let suffix = format!("::{name}");
+    let mut output = String::new();
+    for file in &result.files {
+        for gap in &file.gaps {
+            let function = file.function_at(gap.start_line());
+            if function.is_some_and(|f| f.name == name || f.name.ends_with(&suffix)) {
+                format_gap(&mut output, &file.filename, gap);
+            }
+        }
+    }
+    if output.is_empty() {
+        output = format!("No gaps in `{name}`\n");
+    }
+    output
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::{FileGaps, FunctionSpan};
+
+    fn result() -> AnalysisResult {
+        let function = |name: &str, line_start, line_end| FunctionSpan {
+            name: name.to_string(),
+            line_start,
+            col_start: 1,
+            line_end,
+            col_end: 2,
+            count: 1,
+            visibility: None,
+        };
+        AnalysisResult {
+            files: vec![
+                FileGaps {
+                    filename: "./src/parser.rs".to_string(),
+                    gaps: vec![
+                        CoverageGap::UncoveredLines {
+                            start_line: 3,
+                            end_line: 4,
+                        },
+                        CoverageGap::UncoveredBranch {
+                            line: 12,
+                            col: 8,
+                            line_end: 12,
+                            col_end: 14,
+                            true_count: 2,
+                            false_count: 0,
+                            condition: None,
+                        },
+                    ],
+                    functions: vec![
+                        function("app::parser::Parser::new", 1, 5),
+                        function("app::parser::Parser::parse", 10, 20),
+                    ],
+                },
+                FileGaps {
+                    filename: "./src/main.rs".to_string(),
+                    gaps: vec![CoverageGap::UncoveredLines {
+                        start_line: 7,
+                        end_line: 7,
+                    }],
+                    functions: vec![],
+                },
+            ],
+            ..AnalysisResult::default()
+        }
+    }
+
+    fn call(
+        server: &mut Server<impl FnMut(&[String]) -> Result<AnalysisResult, String>>,
+        request: &str,
+    ) -> Value {
+        serde_json::from_str(&server.handle_line(request).unwrap()).unwrap()
+    }
+
+    fn tool_text(
+        server: &mut Server<impl FnMut(&[String]) -> Result<AnalysisResult, String>>,
+        tool: &str,
+        arguments: &Value,
+    ) -> (String, bool) {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": tool, "arguments": arguments },
+        });
+        let response = call(server, &request.to_string());
+        let result = &response["result"];
+        (
+            result["content"][0]["text"].as_str().unwrap().to_string(),
+            result["isError"].as_bool().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_initialize_and_list_tools() {
+        let mut server = Server::new(|_: &[String]| Err("unused".to_string()));
+        let response = call(
+            &mut server,
+            r#"{"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"protocolVersion": "2025-03-26"}}"#,
+        );
+        assert_eq!(response["id"], 0);
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(response["result"]["serverInfo"]["name"], "llvm-cov-easy");
+
+        let response = call(
+            &mut server,
+            r#"{"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"protocolVersion": "1999-01-01"}}"#,
+        );
+        assert_eq!(response["result"]["protocolVersion"], "2025-06-18");
+
+        assert!(
+            server
+                .handle_line(r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#)
+                .is_none()
+        );
+
+        let response = call(
+            &mut server,
+            r#"{"jsonrpc": "2.0", "id": "a", "method": "tools/list"}"#,
+        );
+        let names: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["run_coverage", "list_gaps", "gaps_for_function", "summary"]
+        );
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let mut server = Server::new(|_: &[String]| Err("unused".to_string()));
+        assert_eq!(call(&mut server, "{")["error"]["code"], PARSE_ERROR);
+        assert_eq!(
+            call(
+                &mut server,
+                r#"{"jsonrpc": "2.0", "id": 2, "method": "resources/list"}"#
+            )["error"]["code"],
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            call(
+                &mut server,
+                r#"{"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "nope"}}"#
+            )["error"]["code"],
+            INVALID_PARAMS
+        );
+        assert_eq!(call(&mut server, "[]")["error"]["code"], INVALID_REQUEST);
+        assert!(
+            server
+                .handle_line(r#"{"jsonrpc": "2.0", "id": 4, "result": {}}"#)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_tools_need_coverage_first() {
+        let mut server = Server::new(|_: &[String]| Err("build failed".to_string()));
+        let (text, is_error) = tool_text(&mut server, "summary", &json!({}));
+        assert!(is_error);
+        assert_eq!(text, "No coverage data yet; call `run_coverage` first");
+        assert_eq!(
+            tool_text(&mut server, "run_coverage", &json!({})),
+            ("build failed".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_run_coverage_caches_result() {
+        let mut runs = Vec::new();
+        let mut server = Server::new(|args: &[String]| {
+            runs.push(args.to_vec());
+            Ok(result())
+        });
+        let (text, is_error) = tool_text(
+            &mut server,
+            "run_coverage",
+            &json!({ "args": ["--workspace"] }),
+        );
+        assert!(!is_error);
+        assert_eq!(
+            text,
+            "Found 3 gaps in 2 files\nLines: 0.0% | Regions: 0.0% | Functions: 0.0%\n"
+        );
+        let (text, _) = tool_text(&mut server, "summary", &json!({}));
+        assert_eq!(text, "Lines: 0.0% | Regions: 0.0% | Functions: 0.0%\n");
+        drop(server);
+        assert_eq!(runs, vec![vec!["--workspace".to_string()]]);
+    }
+
+    #[test]
+    fn test_list_gaps_filters() {
+        let mut server = Server::with_result(|_: &[String]| Err("unused".to_string()), result());
+        let (text, _) = tool_text(&mut server, "list_gaps", &json!({}));
+        assert_eq!(text.lines().count(), 3);
+        let (text, _) = tool_text(&mut server, "list_gaps", &json!({ "file": "src/main.rs" }));
+        assert_eq!(text, "./src/main.rs:7 UNCOVERED\n");
+        let (text, _) = tool_text(
+            &mut server,
+            "list_gaps",
+            &json!({ "file": "parser.rs", "kind": "lines" }),
+        );
+        assert_eq!(text, "./src/parser.rs:3-4 UNCOVERED\n");
+        let (text, _) = tool_text(&mut server, "list_gaps", &json!({ "kind": "weak" }));
+        assert_eq!(text, "No gaps\n");
+    }
+
+    #[test]
+    fn test_gaps_for_function() {
+        let mut server = Server::with_result(|_: &[String]| Err("unused".to_string()), result());
+        let (text, _) = tool_text(
+            &mut server,
+            "gaps_for_function",
+            &json!({ "name": "Parser::parse" }),
+        );
+        assert!(text.starts_with("./src/parser.rs:12:8"), "{text}");
+        assert_eq!(text.lines().count(), 1);
+        let (text, _) = tool_text(
+            &mut server,
+            "gaps_for_function",
+            &json!({ "name": "app::parser::Parser::new" }),
+        );
+        assert_eq!(text, "./src/parser.rs:3-4 UNCOVERED\n");
+        let (text, _) = tool_text(
+            &mut server,
+            "gaps_for_function",
+            &json!({ "name": "parse_all" }),
+        );
+        assert_eq!(text, "No gaps in `parse_all`\n");
+        let response = call(
+            &mut server,
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": {"name": "gaps_for_function", "arguments": {}}}"#,
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+}
@@ -61,6 +61,9 @@ pub struct Segment {
     /// Whether this segment starts a new region.
     pub is_region_entry: bool,
     /// Whether this is a gap region (inserted for non-code areas).
+    ///
+    /// Gap regions carry a count only so that the whitespace between code
+    /// regions inherits a sensible value; they never make a line code.
     pub is_gap_region: bool,
 }
 
@@ -152,7 +155,7 @@ pub struct Region {
     /// Expanded file ID.
     pub expanded_file_id: u64,
     /// Region kind.
-    pub kind: u64,
+    pub kind: RegionKind,
 }
 
 impl<'de> Deserialize<'de> for Region {
@@ -169,11 +172,61 @@ impl<'de> Deserialize<'de> for Region {
             execution_count: arr.4,
             file_id: arr.5,
             expanded_file_id: arr.6,
-            kind: arr.7,
+            kind: RegionKind::from(arr.7),
         })
     }
 }
 
+/// The kind of a coverage mapping region.
+///
+/// Mirrors LLVM's `CounterMappingRegion::RegionKind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Executable code with its own execution count.
+    Code,
+    /// A macro expansion whose code lives in another file ID.
+    Expansion,
+    /// Code that was not compiled, such as `#[cfg]`'d-out items.
+    Skipped,
+    /// Non-code area between regions (whitespace, braces, `else`).
+    Gap,
+    /// A branch condition with true/false counts.
+    Branch,
+    /// An MC/DC decision spanning several conditions.
+    McdcDecision,
+    /// A single condition within an MC/DC decision.
+    McdcBranch,
+    /// A kind not known to this version of the parser.
+    Unknown(u64),
+}
+
+impl RegionKind {
+    /// Returns whether the region counts executable code.
+    ///
+    /// Only code regions contribute to region coverage; skipped and gap
+    /// regions describe non-code areas and branch regions are reported
+    /// separately.
+    #[must_use]
+    pub const fn is_code(self) -> bool {
+        matches!(self, Self::Code)
+    }
+}
+
+impl From<u64> for RegionKind {
+    fn from(kind: u64) -> Self {
+        match kind {
+            0 => Self::Code,
+            1 => Self::Expansion,
+            2 => Self::Skipped,
+            3 => Self::Gap,
+            4 => Self::Branch,
+            5 => Self::McdcDecision,
+            6 => Self::McdcBranch,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
 /// Coverage summary with counts for different coverage metrics.
 #[derive(Debug, Deserialize)]
 pub struct Summary {
@@ -204,3 +257,35 @@ pub struct CoverageCounts {
     /// Coverage percentage.
     pub percent: f64,
 }
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_kind_from_llvm_encoding() {
+        assert_eq!(RegionKind::from(0), RegionKind::Code);
+        assert_eq!(RegionKind::from(1), RegionKind::Expansion);
+        assert_eq!(RegionKind::from(2), RegionKind::Skipped);
+        assert_eq!(RegionKind::from(3), RegionKind::Gap);
+        assert_eq!(RegionKind::from(4), RegionKind::Branch);
+        assert_eq!(RegionKind::from(5), RegionKind::McdcDecision);
+        assert_eq!(RegionKind::from(6), RegionKind::McdcBranch);
+        assert_eq!(RegionKind::from(9), RegionKind::Unknown(9));
+    }
+
+    #[test]
+    fn test_only_code_regions_are_code() {
+        assert!(RegionKind::Code.is_code());
+        assert!(!RegionKind::Skipped.is_code());
+        assert!(!RegionKind::Gap.is_code());
+        assert!(!RegionKind::Branch.is_code());
+    }
+
+    #[test]
+    fn test_region_deserializes_kind() {
+        let region: Region = serde_json::from_str("[1, 1, 3, 2, 0, 0, 0, 2]").unwrap();
+        assert_eq!(region.kind, RegionKind::Skipped);
+    }
+}
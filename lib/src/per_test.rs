@@ -0,0 +1,642 @@
+//! Per-test coverage attribution.
+//!
+//! A [`TestIndex`] maps each covered line, region, and branch direction to
+//! the tests that executed it. It is built from one coverage export per
+//! test, each produced by running that test alone with its own profile
+//! data, and answers which tests cover a line, which tests are redundant
+//! or duplicate each other, and which subset of tests preserves the
+//! suite's coverage.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::line_coverage;
+use crate::model::CoverageExport;
+
+/// A test as listed by `cargo nextest list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestId {
+    /// Binary ID, e.g. `my-crate` or `my-crate::integration`.
+    pub binary_id: String,
+    /// Test name within the binary, e.g. `tests::parses_empty_input`.
+    pub name: String,
+}
+
+/// Options `cargo nextest` takes a filterset with.
+const FILTER_FLAGS: [&str; 3] = ["-E", "--filterset", "--filter-expr"];
+
+impl TestId {
+    /// Returns a nextest filter expression matching only this test.
+    #[must_use]
+    pub fn filter(&self) -> String {
+        format!("binary_id(={}) & test(={})", self.binary_id, self.name)
+    }
+
+    /// Returns `cargo nextest run` arguments selecting only this test out
+    /// of those `user_args` select.
+    ///
+    /// Nextest runs the union of repeated filtersets, so the user's
+    /// filtersets are intersected with [`TestId::filter`] into a single
+    /// one, passed before any `--` that starts the test binary arguments.
+    #[must_use]
+    pub fn run_args(&self, user_args: &[String]) -> Vec<String> {
+        let end = user_args
+            .iter()
+            .position(|arg| arg == "--")
+            .unwrap_or(user_args.len());
+        let (options, binary_args) = user_args.split_at(end);
+        let mut args = Vec::new();
+        let mut filters = Vec::new();
+        let mut options = options.iter();
+        while let Some(arg) = options.next() {
+            if FILTER_FLAGS.contains(&arg.as_str()) {
+                filters.extend(options.next().cloned());
+            } else if let Some(filter) = inline_filter(arg) {
+                filters.push(filter.to_string());
+            } else {
+                args.push(arg.clone());
+            }
+        }
+        let filter = match filters.as_slice() {
+            [] => self.filter(),
+            [filter] => format!("({filter}) & {}", self.filter()),
+            filters => {
+                let union: Vec<String> = filters.iter().map(|f| format!("({f})")).collect();
+                format!("({}) & {}", union.join(" | "), self.filter())
+            }
+        };
+        args.extend(["-E".to_string(), filter]);
+        args.extend(binary_args.iter().cloned());
+        args
+    }
+}
+
+/// Returns the filterset of a filter option given with its value, such as
+/// `--filterset=test(a)` or `-Etest(a)`.
+fn inline_filter(arg: &str) -> Option<&str> {
+    arg.strip_prefix("--filterset=")
+        .or_else(|| arg.strip_prefix("--filter-expr="))
+        .or_else(|| {
+            arg.strip_prefix("-E")
+                .map(|value| value.strip_prefix('=').unwrap_or(value))
+        })
+}
+
+impl std::fmt::Display for TestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.binary_id, self.name)
+    }
+}
+
+/// Raw `cargo nextest list --message-format json` output; only the fields
+/// we need.
+#[derive(Deserialize)]
+struct NextestList {
+    #[serde(rename = "rust-suites")]
+    suites: BTreeMap<String, NextestSuite>,
+}
+
+#[derive(Deserialize)]
+struct NextestSuite {
+    #[serde(rename = "binary-id")]
+    binary_id: String,
+    testcases: BTreeMap<String, NextestCase>,
+}
+
+#[derive(Deserialize)]
+struct NextestCase {
+    ignored: bool,
+    #[serde(rename = "filter-match")]
+    filter_match: NextestFilterMatch,
+}
+
+#[derive(Deserialize)]
+struct NextestFilterMatch {
+    status: String,
+}
+
+/// Parses the JSON printed by `cargo nextest list --message-format json`
+/// into the tests that would run: those matching the filters and not
+/// ignored.
+///
+/// # Errors
+///
+/// Returns an error if the JSON is malformed.
+pub fn parse_nextest_list(json: &str) -> Result<Vec<TestId>, serde_json::Error> {
+    let list: NextestList = serde_json::from_str(json)?;
+    Ok(list
+        .suites
+        .into_values()
+        .flat_map(|suite| {
+            let binary_id = suite.binary_id;
+            suite
+                .testcases
+                .into_iter()
+                .filter(|(_, case)| !case.ignored && case.filter_match.status == "matches")
+                .map(move |(name, _)| TestId {
+                    binary_id: binary_id.clone(),
+                    name,
+                })
+        })
+        .collect())
+}
+
+/// Code covered by each test.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestIndex {
+    /// Test names, in the order they were added.
+    pub tests: Vec<String>,
+    /// Covered code per file.
+    pub files: BTreeMap<String, FileIndex>,
+}
+
+/// The code of one file covered by each test. Every entry maps to the
+/// indices in [`TestIndex::tests`] of the tests that executed it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileIndex {
+    /// Executed lines.
+    pub lines: BTreeMap<u64, Vec<usize>>,
+    /// Executed code regions, keyed by `line:col` of their start.
+    pub regions: BTreeMap<String, Vec<usize>>,
+    /// Taken branch directions, keyed by `line:col:true` or
+    /// `line:col:false`.
+    pub branches: BTreeMap<String, Vec<usize>>,
+}
+
+impl FileIndex {
+    /// Returns whether no test covered anything in the file.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.regions.is_empty() && self.branches.is_empty()
+    }
+}
+
+/// A test whose coverage is strictly contained in another test's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redundancy<'a> {
+    /// The redundant test.
+    pub test: &'a str,
+    /// The smallest test covering everything `test` covers and more.
+    pub covered_by: &'a str,
+}
+
+/// Tests that all cover exactly the same code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicates<'a> {
+    /// The first of the tests added, which is kept.
+    pub kept: &'a str,
+    /// The other tests, in the order they were added, which can go.
+    pub duplicates: Vec<&'a str>,
+}
+
+/// Lines of a file that only one test covers: the gaps the rest of the
+/// suite leaves, which that test closes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedGaps<'a> {
+    /// The test.
+    pub test: &'a str,
+    /// The file, as indexed.
+    pub filename: &'a str,
+    /// Runs of consecutive lines as `(start, end)`, inclusive.
+    pub lines: Vec<(u64, u64)>,
+}
+
+impl TestIndex {
+    /// Parses an index saved with [`TestIndex::to_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Serializes the index as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Records the lines, regions, and branch directions `test` executed,
+    /// given coverage from running it alone.
+    pub fn add(&mut self, test: &str, export: &CoverageExport) {
+        let index = self.tests.len();
+        self.tests.push(test.to_string());
+        for file in export.data.iter().flat_map(|d| &d.files) {
+            let mut covered = self.files.remove(&file.filename).unwrap_or_default();
+            for line in line_coverage(&file.segments) {
+                if line.count > 0 {
+                    record(&mut covered.lines, line.line, index);
+                }
+            }
+            for segment in &file.segments {
+                if segment.has_count
+                    && segment.is_region_entry
+                    && !segment.is_gap_region
+                    && segment.count > 0
+                {
+                    record(
+                        &mut covered.regions,
+                        format!("{}:{}", segment.line, segment.col),
+                        index,
+                    );
+                }
+            }
+            for branch in &file.branches {
+                let site = format!("{}:{}", branch.line_start, branch.col_start);
+                if branch.true_count > 0 {
+                    record(&mut covered.branches, format!("{site}:true"), index);
+                }
+                if branch.false_count > 0 {
+                    record(&mut covered.branches, format!("{site}:false"), index);
+                }
+            }
+            if !covered.is_empty() {
+                self.files.insert(file.filename.clone(), covered);
+            }
+        }
+    }
+
+    /// Returns the tests that executed `line` of `filename`, in the order
+    /// they were added.
+    ///
+    /// `filename` may be a suffix of the indexed path, such as
+    /// `src/lib.rs` or `./src/lib.rs`.
+    #[must_use]
+    pub fn who_covers(&self, filename: &str, line: u64) -> Vec<&str> {
+        let suffix = filename.strip_prefix("./").unwrap_or(filename);
+        let mut indices: Vec<usize> = self
+            .files
+            .iter()
+            .filter(|(indexed, _)| Path::new(indexed).ends_with(suffix))
+            .filter_map(|(_, file)| file.lines.get(&line))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.iter().map(|&i| self.tests[i].as_str()).collect()
+    }
+
+    /// Returns the tests that cover no line, region, or branch direction
+    /// that another test does not also cover, in the order they were added.
+    #[must_use]
+    pub fn without_unique_coverage(&self) -> Vec<&str> {
+        let mut unique = vec![false; self.tests.len()];
+        for tests in self.points() {
+            if let [only] = tests.as_slice() {
+                unique[*only] = true;
+            }
+        }
+        self.tests
+            .iter()
+            .zip(unique)
+            .filter(|(_, unique)| !unique)
+            .map(|(test, _)| test.as_str())
+            .collect()
+    }
+
+    /// Returns the lines each test alone covers, by test in the order they
+    /// were added and then by file.
+    #[must_use]
+    pub fn closed_gaps(&self) -> Vec<ClosedGaps<'_>> {
+        let mut closed = Vec::new();
+        for (index, test) in self.tests.iter().enumerate() {
+            for (filename, file) in &self.files {
+                let mut lines: Vec<(u64, u64)> = Vec::new();
+                for (&line, tests) in &file.lines {
+                    if tests.as_slice() != [index] {
+                        continue;
+                    }
+                    match lines.last_mut() {
+                        Some((_, end)) if *end + 1 == line => *end = line,
+                        _ => lines.push((line, line)),
+                    }
+                }
+                if !lines.is_empty() {
+                    closed.push(ClosedGaps {
+                        test,
+                        filename,
+                        lines,
+                    });
+                }
+            }
+        }
+        closed
+    }
+
+    /// Returns the tests whose coverage is a strict subset of another
+    /// test's, in the order they were added.
+    ///
+    /// Of several tests with identical coverage, only the one kept by
+    /// [`duplicates`](Self::duplicates) is considered.
+    #[must_use]
+    pub fn redundant(&self) -> Vec<Redundancy<'_>> {
+        let sets = self.coverage_sets();
+        (0..sets.len())
+            .filter(|&i| !sets[..i].contains(&sets[i]))
+            .filter_map(|i| {
+                (0..sets.len())
+                    .filter(|&j| sets[i].len() < sets[j].len() && sets[i].is_subset(&sets[j]))
+                    .min_by_key(|&j| sets[j].len())
+                    .map(|j| Redundancy {
+                        test: &self.tests[i],
+                        covered_by: &self.tests[j],
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the groups of tests with identical coverage, by the order in
+    /// which their first test was added.
+    #[must_use]
+    pub fn duplicates(&self) -> Vec<Duplicates<'_>> {
+        let sets = self.coverage_sets();
+        let mut groups: Vec<Duplicates<'_>> = Vec::new();
+        let mut group_of: BTreeMap<&BTreeSet<usize>, usize> = BTreeMap::new();
+        for (set, test) in sets.iter().zip(&self.tests) {
+            if let Some(&group) = group_of.get(set) {
+                groups[group].duplicates.push(test);
+            } else {
+                group_of.insert(set, groups.len());
+                groups.push(Duplicates {
+                    kept: test,
+                    duplicates: Vec::new(),
+                });
+            }
+        }
+        groups.retain(|group| !group.duplicates.is_empty());
+        groups
+    }
+
+    /// Returns a small set of tests that together cover everything the
+    /// whole suite covers, in the order they were added.
+    ///
+    /// Tests are picked greedily, each time taking the one that covers the
+    /// most code not yet covered.
+    #[must_use]
+    pub fn minimal_set(&self) -> Vec<&str> {
+        let sets = self.coverage_sets();
+        let mut uncovered: BTreeSet<usize> = sets.iter().flatten().copied().collect();
+        let mut chosen = Vec::new();
+        while !uncovered.is_empty() {
+            let Some(best) = (0..sets.len())
+                .max_by_key(|&i| (sets[i].intersection(&uncovered).count(), Reverse(i)))
+            else {
+                break;
+            };
+            uncovered.retain(|point| !sets[best].contains(point));
+            chosen.push(best);
+        }
+        chosen.sort_unstable();
+        chosen.iter().map(|&i| self.tests[i].as_str()).collect()
+    }
+
+    /// Returns the tests covering each line, region, and branch direction.
+    fn points(&self) -> impl Iterator<Item = &Vec<usize>> {
+        self.files.values().flat_map(|file| {
+            file.lines
+                .values()
+                .chain(file.regions.values())
+                .chain(file.branches.values())
+        })
+    }
+
+    /// Returns, for each test, the positions in [`TestIndex::points`] of
+    /// the code it covers.
+    fn coverage_sets(&self) -> Vec<BTreeSet<usize>> {
+        let mut sets = vec![BTreeSet::new(); self.tests.len()];
+        for (point, tests) in self.points().enumerate() {
+            for &test in tests {
+                sets[test].insert(point);
+            }
+        }
+        sets
+    }
+}
+
+/// Records that test `index` covered `key`.
+fn record<K: Ord>(covered: &mut BTreeMap<K, Vec<usize>>, key: K, index: usize) {
+    let tests = covered.entry(key).or_default();
+    if tests.last() != Some(&index) {
+        tests.push(index);
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    /// Builds a one-file export in which `lines` ran once and `missed`
+    /// never ran, with the given raw branch records.
+    fn export_with_branches(
+        filename: &str,
+        lines: &[u64],
+        missed: &[u64],
+        branches: &str,
+    ) -> CoverageExport {
+        let mut segments: Vec<(u64, u64)> = lines
+            .iter()
+            .map(|&l| (l, 1))
+            .chain(missed.iter().map(|&l| (l, 0)))
+            .collect();
+        segments.sort_unstable();
+        let segments: Vec<String> = segments
+            .iter()
+            .flat_map(|(line, count)| {
+                [
+                    format!("[{line}, 1, {count}, true, true, false]"),
+                    format!("[{line}, 9, 0, false, false, false]"),
+                ]
+            })
+            .collect();
+        crate::parse_json(&format!(
+            r#"{{"type": "llvm.coverage.json.export", "version": "2.0.1", "data": [{{
+                "files": [{{"filename": "{filename}", "segments": [{}], "branches": [{branches}], "summary": {{}}}}],
+                "totals": {{}}
+            }}]}}"#,
+            segments.join(", ")
+        ))
+        .unwrap()
+    }
+
+    fn export(filename: &str, lines: &[u64], missed: &[u64]) -> CoverageExport {
+        export_with_branches(filename, lines, missed, "")
+    }
+
+    fn index() -> TestIndex {
+        let mut index = TestIndex::default();
+        index.add("app tests::a", &export("/ws/src/lib.rs", &[1, 2], &[3]));
+        index.add("app tests::b", &export("/ws/src/lib.rs", &[2, 3], &[1]));
+        index.add("app tests::c", &export("/ws/src/lib.rs", &[2], &[]));
+        index
+    }
+
+    #[test]
+    fn test_who_covers() {
+        let index = index();
+        assert_eq!(index.who_covers("/ws/src/lib.rs", 1), vec!["app tests::a"]);
+        assert_eq!(
+            index.who_covers("./src/lib.rs", 2),
+            vec!["app tests::a", "app tests::b", "app tests::c"]
+        );
+        assert_eq!(index.who_covers("lib.rs", 3), vec!["app tests::b"]);
+        assert!(index.who_covers("src/lib.rs", 4).is_empty());
+        assert!(index.who_covers("b/lib.rs", 1).is_empty());
+    }
+
+    #[test]
+    fn test_without_unique_coverage() {
+        assert_eq!(index().without_unique_coverage(), vec!["app tests::c"]);
+    }
+
+    #[test]
+    fn test_redundant_tests() {
+        let mut index = index();
+        // Same lines as `c`, but takes a branch direction nobody else does.
+        index.add(
+            "app tests::d",
+            &export_with_branches("/ws/src/lib.rs", &[2], &[], "[2, 3, 2, 8, 1, 0, 0, 0, 4]"),
+        );
+        index.add("app tests::e", &export("/ws/src/lib.rs", &[2], &[]));
+        assert_eq!(
+            index.redundant(),
+            vec![Redundancy {
+                test: "app tests::c",
+                covered_by: "app tests::d",
+            }]
+        );
+        // `e` covers what `c` covers, so only one of them is listed as a
+        // subset, and `e` is reported as its duplicate.
+        assert_eq!(
+            index.duplicates(),
+            vec![Duplicates {
+                kept: "app tests::c",
+                duplicates: vec!["app tests::e"],
+            }]
+        );
+        assert_eq!(
+            index.minimal_set(),
+            vec!["app tests::a", "app tests::b", "app tests::d"]
+        );
+        assert!(TestIndex::default().minimal_set().is_empty());
+    }
+
+    #[test]
+    fn test_closed_gaps() {
+        let mut index = index();
+        index.add("app tests::d", &export("/ws/src/main.rs", &[4, 5, 7], &[]));
+        assert_eq!(
+            index.closed_gaps(),
+            vec![
+                ClosedGaps {
+                    test: "app tests::a",
+                    filename: "/ws/src/lib.rs",
+                    lines: vec![(1, 1)],
+                },
+                ClosedGaps {
+                    test: "app tests::b",
+                    filename: "/ws/src/lib.rs",
+                    lines: vec![(3, 3)],
+                },
+                ClosedGaps {
+                    test: "app tests::d",
+                    filename: "/ws/src/main.rs",
+                    lines: vec![(4, 5), (7, 7)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_args() {
+        let test = TestId {
+            binary_id: "app".to_string(),
+            name: "tests::a".to_string(),
+        };
+        let args = |user_args: &[&str]| {
+            let user_args: Vec<String> = user_args.iter().map(ToString::to_string).collect();
+            test.run_args(&user_args)
+        };
+        assert_eq!(
+            args(&["--workspace"]),
+            vec!["--workspace", "-E", "binary_id(=app) & test(=tests::a)"]
+        );
+        assert_eq!(
+            args(&["-E", "test(/a/)", "--", "--nocapture"]),
+            vec![
+                "-E",
+                "(test(/a/)) & binary_id(=app) & test(=tests::a)",
+                "--",
+                "--nocapture"
+            ]
+        );
+        assert_eq!(
+            args(&[
+                "--filterset=package(app)",
+                "-Ekind(lib)",
+                "--filter-expr",
+                "all()",
+                "--no-fail-fast",
+                "--",
+                "-E",
+                "x"
+            ]),
+            vec![
+                "--no-fail-fast",
+                "-E",
+                "((package(app)) | (kind(lib)) | (all())) & binary_id(=app) & test(=tests::a)",
+                "--",
+                "-E",
+                "x"
+            ]
+        );
+        assert_eq!(
+            args(&["-E=test(b)"])[1],
+            "(test(b)) & binary_id(=app) & test(=tests::a)"
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let index = index();
+        assert_eq!(
+            TestIndex::from_json(&index.to_json().unwrap()).unwrap(),
+            index
+        );
+    }
+
+    #[test]
+    fn test_parse_nextest_list() {
+        let json = r#"{
+            "test-count": 3,
+            "rust-suites": {
+                "app": {
+                    "binary-id": "app",
+                    "testcases": {
+                        "tests::a": {"ignored": false, "filter-match": {"status": "matches"}},
+                        "tests::slow": {"ignored": true, "filter-match": {"status": "matches"}},
+                        "tests::other": {"ignored": false, "filter-match": {"status": "mismatch", "reason": "expression"}}
+                    }
+                },
+                "app::smoke": {
+                    "binary-id": "app::smoke",
+                    "testcases": {
+                        "boots": {"ignored": false, "filter-match": {"status": "matches"}}
+                    }
+                }
+            }
+        }"#;
+        let tests = parse_nextest_list(json).unwrap();
+        let names: Vec<String> = tests.iter().map(ToString::to_string).collect();
+        assert_eq!(names, vec!["app tests::a", "app::smoke boots"]);
+        assert_eq!(tests[1].filter(), "binary_id(=app::smoke) & test(=boots)");
+    }
+}
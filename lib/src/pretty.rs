@@ -0,0 +1,289 @@
+//! Human-oriented terminal output.
+//!
+//! Renders the same report as [`format_result_with`](crate::format::format_result_with)
+//! for reading in a terminal: gaps are grouped under a header per file
+//! with aligned locations, gap kinds are colored, and the summary is a
+//! bar per metric. Colors are ANSI escapes and can be left out.
+
+use std::fmt::Write;
+
+use crate::analysis::{AnalysisResult, CoverageGap, CoverageSummary};
+use crate::format::{
+    FormatOptions, GapLine, GapOrder, format_exclusions, format_hot_lines, format_public_api,
+    format_rollups, gap_line,
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+const BOLD_UNDERLINE: &str = "\x1b[1;4m";
+
+/// Width of a summary bar in characters.
+const BAR_WIDTH: usize = 20;
+
+/// Applies ANSI styles if colors are enabled.
+#[derive(Debug, Clone, Copy)]
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    /// Returns `text` in `style`, or unchanged without colors or text.
+    fn paint(self, style: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Formats an analysis result for a terminal.
+///
+/// With `color`, gap kinds, file headers, and summary bars are styled
+/// with ANSI escapes.
+///
+/// # Output Format
+///
+/// ```text
+/// Code Coverage
+///
+/// ./src/lib.rs
+///   7          UNCOVERED
+///   42:3-42:18 REGION hits:0
+///   50:5       BRANCH `x > limit` never false
+///
+/// Coverage Summary
+/// Lines          ██████████████████░░  92.3%
+/// Regions        ██████████████████░░  88.1%
+/// Functions      ████████████████████ 100.0%
+/// ```
+#[must_use]
+pub fn format_pretty(result: &AnalysisResult, options: &FormatOptions, color: bool) -> String {
+    let painter = Painter { color };
+    let mut output = painter.paint(BOLD, "Code Coverage");
+    output.push('\n');
+
+    let gaps: Vec<(&str, &CoverageGap)> = match options.order {
+        GapOrder::File => result
+            .files
+            .iter()
+            .flat_map(|file| file.gaps.iter().map(|gap| (file.filename.as_str(), gap)))
+            .collect(),
+        GapOrder::Priority => result
+            .ranked_gaps(&options.score_context)
+            .into_iter()
+            .map(|ranked| (ranked.filename, ranked.gap))
+            .collect(),
+    };
+    if gaps.is_empty() {
+        output.push_str("\nNo coverage gaps.\n");
+    }
+    // Consecutive gaps in the same file share a header; in priority order
+    // a file may get several.
+    for group in gaps.chunk_by(|a, b| a.0 == b.0) {
+        output.push('\n');
+        output.push_str(&painter.paint(BOLD_UNDERLINE, group[0].0));
+        output.push('\n');
+        let lines: Vec<GapLine> = group.iter().map(|(_, gap)| gap_line(gap)).collect();
+        let width = lines.iter().map(|l| l.location.len()).max().unwrap_or(0);
+        for line in lines {
+            // writeln to a String is infallible.
+            write!(
+                output,
+                "  {:width$} {}",
+                line.location,
+                painter.paint(kind_style(line.kind), line.kind)
+            )
+            .unwrap();
+            if !line.detail.is_empty() {
+                write!(output, " {}", painter.paint(DIM, &line.detail)).unwrap();
+            }
+            output.push('\n');
+        }
+    }
+
+    if !result.hot_lines.is_empty() {
+        push_header(&mut output, painter, "Hottest Lines");
+        format_hot_lines(&mut output, &result.hot_lines);
+    }
+    if !result.rollups.is_empty() {
+        push_header(&mut output, painter, "Crate Coverage");
+        format_rollups(&mut output, &result.rollups);
+    }
+    if !result.exclusions.is_empty() {
+        push_header(&mut output, painter, "Coverage Exclusions");
+        format_exclusions(&mut output, &result.exclusions);
+    }
+    if !result.public_api.is_empty() {
+        push_header(&mut output, painter, "Public API");
+        format_public_api(&mut output, &result.public_api);
+    }
+
+    push_header(&mut output, painter, "Coverage Summary");
+    format_summary_bars(&mut output, &result.summary, painter);
+    output
+}
+
+/// Appends a blank line and a section header.
+fn push_header(output: &mut String, painter: Painter, title: &str) {
+    output.push('\n');
+    output.push_str(&painter.paint(BOLD, title));
+    output.push('\n');
+}
+
+/// Returns the style of a gap kind: red for uncovered code, yellow for
+/// branches, magenta for instantiations, and blue for weak coverage.
+fn kind_style(kind: &str) -> &'static str {
+    match kind {
+        "BRANCH" => YELLOW,
+        "INSTANTIATIONS" => MAGENTA,
+        kind if kind.starts_with("WEAK") => BLUE,
+        _ => RED,
+    }
+}
+
+/// Formats one bar per summary metric, colored green from 90%, yellow
+/// from 75%, and red below.
+fn format_summary_bars(output: &mut String, summary: &CoverageSummary, painter: Painter) {
+    let metrics = [
+        ("Lines", Some(summary.lines_percent)),
+        ("Regions", Some(summary.regions_percent)),
+        ("Branches", summary.branches_percent),
+        ("Functions", Some(summary.functions_percent)),
+        ("Instantiations", summary.instantiations_percent),
+    ];
+    for (name, percent) in metrics {
+        let Some(percent) = percent else {
+            continue;
+        };
+        let style = if percent >= 90.0 {
+            GREEN
+        } else if percent >= 75.0 {
+            YELLOW
+        } else {
+            RED
+        };
+        let filled = bar_cells(percent);
+        let bar = format!(
+            "{}{}",
+            painter.paint(style, &"█".repeat(filled)),
+            painter.paint(DIM, &"░".repeat(BAR_WIDTH - filled))
+        );
+        let value = painter.paint(style, &format!("{percent:5.1}%"));
+        // writeln to a String is infallible.
+        writeln!(output, "{name:<14} {bar} {value}").unwrap();
+    }
+}
+
+/// Returns the number of filled cells for `percent`, rounded to the
+/// nearest cell.
+fn bar_cells(percent: f64) -> usize {
+    ((percent.clamp(0.0, 100.0) / 100.0 * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::FileGaps;
+    use crate::totals::Totals;
+
+    fn result() -> AnalysisResult {
+        AnalysisResult {
+            files: vec![
+                FileGaps {
+                    filename: "src/a.rs".to_string(),
+                    gaps: vec![
+                        CoverageGap::UncoveredLines {
+                            start_line: 7,
+                            end_line: 7,
+                        },
+                        CoverageGap::UncoveredRegion {
+                            line_start: 42,
+                            col_start: 3,
+                            line_end: 42,
+                            col_end: 18,
+                        },
+                    ],
+                    functions: vec![],
+                },
+                FileGaps {
+                    filename: "src/b.rs".to_string(),
+                    gaps: vec![CoverageGap::WeakBranch {
+                        line: 3,
+                        col: 8,
+                        true_count: 1,
+                        false_count: 40,
+                    }],
+                    functions: vec![],
+                },
+            ],
+            summary: CoverageSummary {
+                lines_percent: 92.3,
+                regions_percent: 80.0,
+                branches_percent: None,
+                functions_percent: 50.0,
+                instantiations_percent: None,
+            },
+            ..AnalysisResult::default()
+        }
+    }
+
+    #[test]
+    fn test_format_pretty_without_color() {
+        assert_eq!(
+            format_pretty(&result(), &FormatOptions::default(), false),
+            "Code Coverage\n\
+             \nsrc/a.rs\n  \
+             7          UNCOVERED\n  \
+             42:3-42:18 REGION hits:0\n\
+             \nsrc/b.rs\n  \
+             3:8 WEAK BRANCH true:1 false:40\n\
+             \nCoverage Summary\n\
+             Lines          ██████████████████░░  92.3%\n\
+             Regions        ████████████████░░░░  80.0%\n\
+             Functions      ██████████░░░░░░░░░░  50.0%\n"
+        );
+    }
+
+    #[test]
+    fn test_format_pretty_with_color() {
+        let output = format_pretty(&result(), &FormatOptions::default(), true);
+        assert!(output.contains("\x1b[1;4msrc/a.rs\x1b[0m\n"));
+        assert!(output.contains("  7          \x1b[31mUNCOVERED\x1b[0m\n"));
+        assert!(output.contains("\x1b[34mWEAK BRANCH\x1b[0m \x1b[2mtrue:1 false:40\x1b[0m"));
+        assert!(output.contains("\x1b[32m██████████████████\x1b[0m"));
+        assert!(output.contains("\x1b[33m 80.0%\x1b[0m"));
+        assert!(output.contains("\x1b[31m 50.0%\x1b[0m"));
+    }
+
+    #[test]
+    fn test_format_pretty_sections() {
+        let mut result = result();
+        result.files.clear();
+        result.hot_lines = vec![crate::analysis::HotLine {
+            filename: "src/a.rs".to_string(),
+            line: 12,
+            count: 1043,
+        }];
+        result.summary = Totals::default().summary();
+        let output = format_pretty(&result, &FormatOptions::default(), false);
+        assert!(output.contains("\nNo coverage gaps.\n"));
+        assert!(output.contains("\nHottest Lines\nsrc/a.rs:12 hits:1043\n"));
+    }
+
+    #[test]
+    fn test_bar_cells() {
+        assert_eq!(bar_cells(0.0), 0);
+        assert_eq!(bar_cells(2.4), 0);
+        assert_eq!(bar_cells(2.5), 1);
+        assert_eq!(bar_cells(100.0), BAR_WIDTH);
+        assert_eq!(bar_cells(150.0), BAR_WIDTH);
+    }
+}
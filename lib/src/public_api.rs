@@ -0,0 +1,313 @@
+//! Public API coverage.
+//!
+//! Enumerates the `pub` functions and inherent methods reachable from a
+//! crate root through `pub mod` declarations, and looks up how often tests
+//! executed each one. Re-exports through `pub use` are not followed, and
+//! test-only and `coverage(off)` items are skipped.
+
+use std::path::{Path, PathBuf};
+
+use syn::{ImplItem, Item, Type, Visibility};
+
+use crate::analysis::{InstantiationKey, group_instantiations};
+use crate::exclusions::is_coverage_off;
+use crate::model::ExportData;
+use crate::source::SourceCache;
+use crate::test_code::{is_test_item, item_attrs, line_range, module_dir, path_attr};
+
+/// The root source file of a library crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateRoot {
+    /// Crate name as used in paths (`-` replaced by `_`).
+    pub name: String,
+    /// Path to the crate's root file, usually `src/lib.rs`.
+    pub path: PathBuf,
+}
+
+/// A public function or method and how often tests executed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiFunction {
+    /// File path of the definition.
+    pub filename: String,
+    /// Full path, e.g. `my_crate::parser::Parser::parse`.
+    pub path: String,
+    /// First line of the function, including its attributes.
+    pub line_start: u64,
+    /// Last line of the function.
+    pub line_end: u64,
+    /// Highest execution count of the function; 0 if it never ran or was
+    /// never instantiated.
+    pub count: u64,
+}
+
+/// Lists the public functions and methods of the crate at `root`, with
+/// counts of 0.
+///
+/// Modules whose source cannot be read or parsed are skipped.
+pub fn public_functions(root: &CrateRoot, sources: &mut SourceCache) -> Vec<ApiFunction> {
+    let mut walker = Walker {
+        sources,
+        found: Vec::new(),
+    };
+    walker.file(&root.path, &root.name);
+    walker.found
+}
+
+/// Lists the public API of every crate in `roots` with execution counts
+/// from the coverage data.
+pub(crate) fn report(
+    data: &ExportData,
+    roots: &[CrateRoot],
+    sources: &mut SourceCache,
+) -> Vec<ApiFunction> {
+    let groups = group_instantiations(&data.functions);
+    let mut functions: Vec<ApiFunction> = roots
+        .iter()
+        .flat_map(|root| public_functions(root, sources))
+        .collect();
+    for function in &mut functions {
+        function.count = groups
+            .iter()
+            .filter(|(InstantiationKey { filename, line, .. }, _)| {
+                *filename == function.filename
+                    && (function.line_start..=function.line_end).contains(line)
+            })
+            .flat_map(|(_, group)| group.iter().map(|f| f.count))
+            .max()
+            .unwrap_or(0);
+    }
+    functions
+}
+
+/// Walks modules and records public functions.
+struct Walker<'a> {
+    sources: &'a mut SourceCache,
+    found: Vec<ApiFunction>,
+}
+
+impl Walker<'_> {
+    /// Walks the module file at `path`, whose module path is `module`.
+    fn file(&mut self, path: &Path, module: &str) {
+        let filename = path.to_string_lossy();
+        let Some(file) = self
+            .sources
+            .get(&filename)
+            .and_then(|source| syn::parse_file(source).ok())
+        else {
+            return;
+        };
+        let file_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        self.items(&filename, &file.items, module, &module_dir(path), file_dir);
+    }
+
+    /// Records public functions among `items`. `dir` is where out-of-line
+    /// child modules live and `file_dir` is the directory of the file.
+    fn items(&mut self, filename: &str, items: &[Item], module: &str, dir: &Path, file_dir: &Path) {
+        for item in items {
+            let attrs = item_attrs(item);
+            if is_test_item(attrs) || is_coverage_off(attrs) {
+                continue;
+            }
+            match item {
+                Item::Fn(function) if is_pub(&function.vis) => {
+                    self.push(
+                        filename,
+                        format!("{module}::{}", function.sig.ident),
+                        function,
+                    );
+                }
+                Item::Mod(child) if is_pub(&child.vis) => {
+                    let name = child.ident.to_string();
+                    let path = format!("{module}::{name}");
+                    if let Some((_, content)) = &child.content {
+                        self.items(filename, content, &path, &dir.join(&name), file_dir);
+                    } else if let Some(child_file) = self.module_file(child, dir, file_dir) {
+                        self.file(&child_file, &path);
+                    }
+                }
+                Item::Impl(block) if block.trait_.is_none() => {
+                    let Some(ty) = type_name(&block.self_ty) else {
+                        continue;
+                    };
+                    if is_private_type(items, &ty) {
+                        continue;
+                    }
+                    for impl_item in &block.items {
+                        if let ImplItem::Fn(method) = impl_item
+                            && is_pub(&method.vis)
+                            && !is_test_item(&method.attrs)
+                            && !is_coverage_off(&method.attrs)
+                        {
+                            self.push(
+                                filename,
+                                format!("{module}::{ty}::{}", method.sig.ident),
+                                method,
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Finds the file of an out-of-line module, honoring `#[path]`.
+    fn module_file(
+        &mut self,
+        module: &syn::ItemMod,
+        dir: &Path,
+        file_dir: &Path,
+    ) -> Option<PathBuf> {
+        if let Some(path) = path_attr(&module.attrs) {
+            return Some(file_dir.join(path));
+        }
+        let name = module.ident.to_string();
+        [
+            dir.join(format!("{name}.rs")),
+            dir.join(&name).join("mod.rs"),
+        ]
+        .into_iter()
+        .find(|candidate| self.sources.get(&candidate.to_string_lossy()).is_some())
+    }
+
+    /// Records a public function.
+    fn push(&mut self, filename: &str, path: String, node: &impl syn::spanned::Spanned) {
+        let (line_start, line_end) = line_range(node);
+        self.found.push(ApiFunction {
+            filename: filename.to_string(),
+            path,
+            line_start,
+            line_end,
+            count: 0,
+        });
+    }
+}
+
+/// Returns whether `vis` is plain `pub`.
+const fn is_pub(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+/// Returns the name of an impl's self type, if it is a path.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns whether `items` declare a type named `name` that is not `pub`.
+///
+/// Types declared elsewhere are assumed to be public.
+fn is_private_type(items: &[Item], name: &str) -> bool {
+    items.iter().any(|item| {
+        let (ident, vis) = match item {
+            Item::Struct(s) => (&s.ident, &s.vis),
+            Item::Enum(e) => (&e.ident, &e.vis),
+            Item::Union(u) => (&u.ident, &u.vis),
+            Item::Type(t) => (&t.ident, &t.vis),
+            _ => return false,
+        };
+        ident == name && !is_pub(vis)
+    })
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn paths(root: &str, files: &[(&str, &str)]) -> Vec<String> {
+        let mut sources = SourceCache::new();
+        for (filename, contents) in files {
+            sources.insert(*filename, *contents);
+        }
+        let root = CrateRoot {
+            name: "my_crate".to_string(),
+            path: PathBuf::from(root),
+        };
+        public_functions(&root, &mut sources)
+            .into_iter()
+            .map(|f| f.path)
+            .collect()
+    }
+
+    #[test]
+    fn test_public_functions_and_methods() {
+        let lib = "pub fn a() {}\nfn b() {}\npub(crate) fn c() {}\n\
+                   pub struct S;\nimpl S {\n    pub fn new() -> Self { S }\n    fn private() {}\n}\n\
+                   struct Hidden;\nimpl Hidden {\n    pub fn h() {}\n}\n\
+                   impl Default for S {\n    fn default() -> Self { S }\n}\n\
+                   #[cfg(test)]\npub fn t() {}\n\
+                   #[cfg_attr(coverage_nightly, coverage(off))]\npub fn off() {}\n";
+        assert_eq!(
+            paths("/ws/src/lib.rs", &[("/ws/src/lib.rs", lib)]),
+            vec!["my_crate::a", "my_crate::S::new"]
+        );
+    }
+
+    #[test]
+    fn test_follows_only_public_modules() {
+        let files = [
+            (
+                "/ws/src/lib.rs",
+                "pub mod api;\nmod internal;\npub mod inline {\n    pub fn i() {}\n}\n",
+            ),
+            ("/ws/src/api/mod.rs", "pub mod nested;\npub fn api() {}\n"),
+            ("/ws/src/api/nested.rs", "pub fn deep() {}\n"),
+            ("/ws/src/internal.rs", "pub fn hidden() {}\n"),
+        ];
+        assert_eq!(
+            paths("/ws/src/lib.rs", &files),
+            vec![
+                "my_crate::api::nested::deep",
+                "my_crate::api::api",
+                "my_crate::inline::i",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_attribute_and_missing_files() {
+        let files = [
+            (
+                "/ws/src/lib.rs",
+                "#[path = \"other.rs\"]\npub mod renamed;\npub mod missing;\n",
+            ),
+            ("/ws/src/other.rs", "pub fn f() {}\n"),
+        ];
+        assert_eq!(
+            paths("/ws/src/lib.rs", &files),
+            vec!["my_crate::renamed::f"]
+        );
+    }
+
+    #[test]
+    fn test_report_counts_executions() {
+        let data = crate::parse_json(include_str!(
+            "../tests/fixtures/show-missing-lines-multi-missing.json"
+        ))
+        .unwrap()
+        .data
+        .remove(0);
+        let mut sources = SourceCache::new();
+        // `foo` spans lines 3-5 and ran; `baz` is on line 15 and never ran.
+        let source = format!(
+            "\n\npub fn foo() {{\n\n}}\n{}pub fn baz() {{}}\n",
+            "\n".repeat(9)
+        );
+        sources.insert("src/lib.rs", source);
+        let roots = [CrateRoot {
+            name: "t".to_string(),
+            path: PathBuf::from("src/lib.rs"),
+        }];
+        let report: Vec<(String, u64)> = report(&data, &roots, &mut sources)
+            .into_iter()
+            .map(|f| (f.path, f.count))
+            .collect();
+        assert_eq!(
+            report,
+            vec![("t::foo".to_string(), 1), ("t::baz".to_string(), 0)]
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Gap prioritization.
+//!
+//! Scores each [`CoverageGap`] so that the most valuable missing tests can
+//! be written first. The score grows with the size of the uncovered span,
+//! favors untaken branches over partial regions, doubles for functions that
+//! never ran at all, and is raised for functions in or near the public API
+//! (when it is checked) and for files that change often.
+
+use std::collections::BTreeMap;
+
+use crate::analysis::{AnalysisResult, CoverageGap, FileGaps, Visibility};
+
+/// Longest uncovered span, in lines, that still increases the score.
+const MAX_SCORED_LINES: u64 = 50;
+
+/// Extra score, in percent, for each recent commit touching the file.
+const CHURN_PERCENT_PER_COMMIT: u64 = 5;
+
+/// Largest extra score, in percent, from file churn.
+const MAX_CHURN_PERCENT: u64 = 100;
+
+/// Inputs to scoring that do not come from the coverage data.
+#[derive(Debug, Clone, Default)]
+pub struct ScoreContext {
+    /// Number of recent commits touching each file, keyed by filename as it
+    /// appears in the analysis result.
+    pub churn: BTreeMap<String, u64>,
+}
+
+/// A gap together with its file and priority score.
+#[derive(Debug, Clone, Copy)]
+pub struct RankedGap<'a> {
+    /// File path of the gap.
+    pub filename: &'a str,
+    /// The gap itself.
+    pub gap: &'a CoverageGap,
+    /// Priority score; higher means more valuable to test.
+    pub score: u64,
+}
+
+impl CoverageGap {
+    /// Scores this gap within `file`; higher scores should be tested first.
+    #[must_use]
+    pub fn score(&self, file: &FileGaps, context: &ScoreContext) -> u64 {
+        let (base, start_line) = match self {
+            Self::UncoveredLines {
+                start_line,
+                end_line,
+            } => {
+                let lines = (end_line - start_line + 1).min(MAX_SCORED_LINES);
+                (10 + 2 * lines, *start_line)
+            }
+            Self::UncoveredBranch { line, .. } => (8, *line),
+            Self::UncoveredRegion { line_start, .. } => (6, *line_start),
+            Self::UncoveredInstantiations { line, .. } => (4, *line),
+            Self::WeakLines { start_line, .. } => (2, *start_line),
+            Self::WeakRegion { line_start, .. }
+            | Self::WeakBranch {
+                line: line_start, ..
+            } => (1, *line_start),
+        };
+
+        // A gap that swallows a whole never-executed function means an
+        // entire behavior is untested.
+        let dead_function = match self {
+            Self::UncoveredLines {
+                start_line,
+                end_line,
+            } => file
+                .functions
+                .iter()
+                .any(|f| f.count == 0 && *start_line <= f.line_start && f.line_end <= *end_line),
+            _ => false,
+        };
+        let function = file.function_at(start_line);
+        let visibility_percent = match function.and_then(|f| f.visibility) {
+            Some(Visibility::Public) => 150,
+            Some(Visibility::Restricted) => 125,
+            Some(Visibility::Private) | None => 100,
+        };
+        let churn = context.churn.get(&file.filename).copied().unwrap_or(0);
+        let churn_percent = 100 + (churn * CHURN_PERCENT_PER_COMMIT).min(MAX_CHURN_PERCENT);
+
+        let mut score = base * visibility_percent * churn_percent / 10_000;
+        if dead_function {
+            score *= 2;
+        }
+        score.max(1)
+    }
+}
+
+impl AnalysisResult {
+    /// Returns every gap ordered by descending priority score.
+    ///
+    /// Gaps with equal scores keep their file and location order.
+    #[must_use]
+    pub fn ranked_gaps(&self, context: &ScoreContext) -> Vec<RankedGap<'_>> {
+        let mut ranked: Vec<RankedGap<'_>> = self
+            .files
+            .iter()
+            .flat_map(|file| {
+                file.gaps.iter().map(move |gap| RankedGap {
+                    filename: &file.filename,
+                    gap,
+                    score: gap.score(file, context),
+                })
+            })
+            .collect();
+        ranked.sort_by_key(|r| std::cmp::Reverse(r.score));
+        ranked
+    }
+}
+
+/// Counts how many commits touched each file in `git log` output.
+///
+/// Expects the output of `git log --format= --name-only --relative`, run
+/// from the directory that analysis paths are relative to. Keys use the
+/// `./` prefix produced by [`AnalysisResult::relativize_paths`].
+#[must_use]
+pub fn parse_git_churn(log: &str) -> BTreeMap<String, u64> {
+    let mut churn = BTreeMap::new();
+    for path in log.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        *churn.entry(format!("./{path}")).or_insert(0) += 1;
+    }
+    churn
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::{CoverageSummary, FunctionSpan};
+
+    fn function(line_start: u64, line_end: u64, count: u64, vis: Visibility) -> FunctionSpan {
+        FunctionSpan {
+            name: "f".to_string(),
+            line_start,
+            col_start: 1,
+            line_end,
+            col_end: 2,
+            count,
+            visibility: Some(vis),
+        }
+    }
+
+    fn file(gaps: Vec<CoverageGap>, functions: Vec<FunctionSpan>) -> FileGaps {
+        FileGaps {
+            filename: "./src/lib.rs".to_string(),
+            gaps,
+            functions,
+        }
+    }
+
+    fn lines(start_line: u64, end_line: u64) -> CoverageGap {
+        CoverageGap::UncoveredLines {
+            start_line,
+            end_line,
+        }
+    }
+
+    #[test]
+    fn test_larger_spans_score_higher() {
+        let file = file(vec![], vec![]);
+        let context = ScoreContext::default();
+        assert_eq!(lines(1, 1).score(&file, &context), 12);
+        assert_eq!(lines(1, 5).score(&file, &context), 20);
+        assert_eq!(lines(1, 500).score(&file, &context), 110);
+    }
+
+    #[test]
+    fn test_dead_function_doubles_score() {
+        let dead = file(vec![], vec![function(1, 5, 0, Visibility::Private)]);
+        let live = file(vec![], vec![function(1, 5, 3, Visibility::Private)]);
+        let context = ScoreContext::default();
+        assert_eq!(lines(1, 5).score(&dead, &context), 40);
+        assert_eq!(lines(1, 5).score(&live, &context), 20);
+    }
+
+    #[test]
+    fn test_public_functions_and_churn_raise_score() {
+        let public = file(vec![], vec![function(1, 9, 3, Visibility::Public)]);
+        let restricted = file(vec![], vec![function(1, 9, 3, Visibility::Restricted)]);
+        let mut context = ScoreContext::default();
+        assert_eq!(lines(2, 2).score(&public, &context), 18);
+        assert_eq!(lines(2, 2).score(&restricted, &context), 15);
+        context.churn.insert("./src/lib.rs".to_string(), 40);
+        assert_eq!(lines(2, 2).score(&public, &context), 36);
+    }
+
+    #[test]
+    fn test_kind_base_scores() {
+        let file = file(vec![], vec![]);
+        let context = ScoreContext::default();
+        let branch = CoverageGap::UncoveredBranch {
+            line: 1,
+            col: 1,
+            line_end: 1,
+            col_end: 2,
+            true_count: 1,
+            false_count: 0,
+            condition: None,
+        };
+        let region = CoverageGap::UncoveredRegion {
+            line_start: 1,
+            col_start: 1,
+            line_end: 1,
+            col_end: 2,
+        };
+        let instantiations = CoverageGap::UncoveredInstantiations {
+            line: 1,
+            col: 1,
+            executed: vec![],
+            unexecuted: vec![],
+        };
+        let weak_lines = CoverageGap::WeakLines {
+            start_line: 1,
+            end_line: 1,
+            max_count: 1,
+        };
+        let weak_branch = CoverageGap::WeakBranch {
+            line: 1,
+            col: 1,
+            true_count: 1,
+            false_count: 9,
+        };
+        let weak_region = CoverageGap::WeakRegion {
+            line_start: 1,
+            col_start: 1,
+            line_end: 1,
+            col_end: 2,
+            count: 1,
+        };
+        let scores: Vec<u64> = [
+            branch,
+            region,
+            instantiations,
+            weak_lines,
+            weak_branch,
+            weak_region,
+        ]
+        .iter()
+        .map(|g| g.score(&file, &context))
+        .collect();
+        assert_eq!(scores, vec![8, 6, 4, 2, 1, 1]);
+    }
+
+    #[test]
+    fn test_ranked_gaps_orders_across_files() {
+        let result = AnalysisResult {
+            files: vec![
+                FileGaps {
+                    filename: "./a.rs".to_string(),
+                    gaps: vec![lines(1, 1)],
+                    functions: vec![],
+                },
+                FileGaps {
+                    filename: "./b.rs".to_string(),
+                    gaps: vec![lines(1, 1), lines(3, 8)],
+                    functions: vec![],
+                },
+            ],
+            summary: CoverageSummary {
+                lines_percent: 0.0,
+                regions_percent: 0.0,
+                branches_percent: None,
+                functions_percent: 0.0,
+                instantiations_percent: None,
+            },
+            ..AnalysisResult::default()
+        };
+        let ranked: Vec<(&str, u64)> = result
+            .ranked_gaps(&ScoreContext::default())
+            .iter()
+            .map(|r| (r.filename, r.score))
+            .collect();
+        assert_eq!(ranked, vec![("./b.rs", 22), ("./a.rs", 12), ("./b.rs", 12)]);
+    }
+
+    #[test]
+    fn test_parse_git_churn() {
+        let log = "src/lib.rs\nsrc/main.rs\n\n\nsrc/lib.rs\n";
+        let churn = parse_git_churn(log);
+        assert_eq!(churn["./src/lib.rs"], 2);
+        assert_eq!(churn["./src/main.rs"], 1);
+    }
+}
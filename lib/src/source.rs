@@ -0,0 +1,170 @@
+//! Access to the source files referenced by coverage data.
+//!
+//! Source text is optional: every consumer falls back to location-only
+//! output when a file cannot be read.
+
+use std::collections::BTreeMap;
+
+/// Maximum length of a source snippet before it is truncated.
+const MAX_SNIPPET_CHARS: usize = 80;
+
+/// Lazily loaded source files, keyed by filename as it appears in the
+/// coverage data.
+///
+/// Each file is read from disk at most once; files that cannot be read are
+/// remembered as missing.
+#[derive(Debug, Default)]
+pub struct SourceCache {
+    files: BTreeMap<String, Option<String>>,
+}
+
+impl SourceCache {
+    /// Creates an empty cache that reads files from disk on demand.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers in-memory contents for `filename`, bypassing the disk.
+    pub fn insert(&mut self, filename: impl Into<String>, contents: impl Into<String>) {
+        self.files.insert(filename.into(), Some(contents.into()));
+    }
+
+    /// Returns the contents of `filename`, reading it on first access.
+    ///
+    /// Relative paths are resolved against the current directory.
+    pub fn get(&mut self, filename: &str) -> Option<&str> {
+        self.files
+            .entry(filename.to_string())
+            .or_insert_with(|| std::fs::read_to_string(filename).ok())
+            .as_deref()
+    }
+
+    /// Returns the source text between two 1-based positions, with runs of
+    /// whitespace collapsed to single spaces.
+    ///
+    /// Columns are byte offsets and the end column is exclusive, as in LLVM
+    /// coverage mappings. Returns `None` if the file is unavailable or the
+    /// positions do not fall on character boundaries within the file.
+    pub fn snippet(
+        &mut self,
+        filename: &str,
+        (line_start, col_start): (u64, u64),
+        (line_end, col_end): (u64, u64),
+    ) -> Option<String> {
+        let contents = self.get(filename)?;
+        let raw = extract(contents, (line_start, col_start), (line_end, col_end))?;
+        let collapsed = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+        if collapsed.is_empty() {
+            return None;
+        }
+        Some(truncate(&collapsed))
+    }
+}
+
+/// Slices `contents` between two 1-based (line, byte column) positions.
+fn extract(contents: &str, start: (u64, u64), end: (u64, u64)) -> Option<&str> {
+    let start = offset(contents, start)?;
+    let end = offset(contents, end)?;
+    contents.get(start..end)
+}
+
+/// Converts a 1-based (line, byte column) position into a byte offset.
+fn offset(contents: &str, (line, col): (u64, u64)) -> Option<usize> {
+    let line_index = usize::try_from(line.checked_sub(1)?).ok()?;
+    let col_index = usize::try_from(col.checked_sub(1)?).ok()?;
+    let line_start: usize = contents
+        .split_inclusive('\n')
+        .take(line_index)
+        .map(str::len)
+        .sum();
+    let line_text = contents[line_start..].lines().next().unwrap_or("");
+    (col_index <= line_text.len()).then_some(line_start + col_index)
+}
+
+/// Shortens `text` to at most [`MAX_SNIPPET_CHARS`] characters.
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_SNIPPET_CHARS {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(MAX_SNIPPET_CHARS - 3).collect();
+    short.push_str("...");
+    short
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn check(x: i32) -> bool {\n    if x > 0\n        && x < 10\n    {\n";
+
+    fn cache() -> SourceCache {
+        let mut cache = SourceCache::new();
+        cache.insert("src/lib.rs", SOURCE);
+        cache
+    }
+
+    #[test]
+    fn test_snippet_single_line() {
+        assert_eq!(
+            cache().snippet("src/lib.rs", (2, 8), (2, 13)),
+            Some("x > 0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_snippet_collapses_whitespace_across_lines() {
+        assert_eq!(
+            cache().snippet("src/lib.rs", (2, 8), (3, 18)),
+            Some("x > 0 && x < 10".to_string())
+        );
+    }
+
+    #[test]
+    fn test_snippet_out_of_range() {
+        let mut cache = cache();
+        assert_eq!(cache.snippet("src/lib.rs", (2, 8), (2, 40)), None);
+        assert_eq!(cache.snippet("src/lib.rs", (9, 1), (9, 2)), None);
+        assert_eq!(cache.snippet("src/lib.rs", (0, 1), (2, 2)), None);
+    }
+
+    #[test]
+    fn test_snippet_empty_is_none() {
+        assert_eq!(cache().snippet("src/lib.rs", (2, 1), (2, 5)), None);
+    }
+
+    #[test]
+    fn test_snippet_truncates_long_text() {
+        let mut cache = SourceCache::new();
+        cache.insert("long.rs", "a".repeat(100));
+        let snippet = cache.snippet("long.rs", (1, 1), (1, 101)).unwrap();
+        assert_eq!(snippet.len(), MAX_SNIPPET_CHARS);
+        assert!(snippet.ends_with("..."));
+    }
+
+    #[test]
+    fn test_missing_file_is_remembered() {
+        let mut cache = SourceCache::new();
+        assert!(cache.get("/nonexistent/llvm-cov-easy/file.rs").is_none());
+        assert!(
+            cache
+                .files
+                .contains_key("/nonexistent/llvm-cov-easy/file.rs")
+        );
+    }
+
+    #[test]
+    fn test_reads_file_from_disk() {
+        let path =
+            std::env::temp_dir().join(format!("llvm-cov-easy-{}-source.rs", std::process::id()));
+        std::fs::write(&path, "let a = 1;\n").unwrap();
+        let mut cache = SourceCache::new();
+        let filename = path.to_string_lossy();
+        assert_eq!(
+            cache.snippet(&filename, (1, 5), (1, 6)),
+            Some("a".to_string())
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,390 @@
+//! Detection of test-only code.
+//!
+//! Test code is found by path (files under a package's `tests` directory) and by
+//! parsing sources for items marked `#[cfg(test)]` or `#[test]` (including
+//! `#[tokio::test]` and similar). A `#[cfg(test)] mod helpers;` declaration
+//! makes the whole module file test code. Files that cannot be read or
+//! parsed are treated as containing no test code.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use syn::spanned::Spanned;
+use syn::{Attribute, ImplItem, Item, Meta};
+
+use crate::source::SourceCache;
+
+/// Inclusive line range covering a whole file.
+const WHOLE_FILE: (u64, u64) = (1, u64::MAX);
+
+/// Line ranges of test code, keyed by filename.
+#[derive(Debug, Clone, Default)]
+pub struct TestCode {
+    ranges: BTreeMap<String, Vec<(u64, u64)>>,
+}
+
+impl TestCode {
+    /// Detects test code in each of `filenames`.
+    pub fn detect<'a>(
+        filenames: impl IntoIterator<Item = &'a str> + Clone,
+        sources: &mut SourceCache,
+    ) -> Self {
+        let mut ranges = BTreeMap::new();
+        let mut test_modules = Vec::new();
+        for filename in filenames.clone() {
+            let Some(source) = sources.get(filename) else {
+                continue;
+            };
+            let path = Path::new(filename);
+            let mut scan = Scan {
+                ranges: Vec::new(),
+                file_dir: path.parent().unwrap_or_else(|| Path::new("")),
+                test_modules: &mut test_modules,
+            };
+            if let Ok(file) = syn::parse_file(source) {
+                scan.items(&file.items, &module_dir(path));
+            }
+            if !scan.ranges.is_empty() {
+                ranges.insert(filename.to_string(), scan.ranges);
+            }
+        }
+        for filename in filenames {
+            let path = Path::new(filename);
+            if is_test_path(filename) || test_modules.iter().any(|m| path.starts_with(m)) {
+                ranges.insert(filename.to_string(), vec![WHOLE_FILE]);
+            }
+        }
+        Self { ranges }
+    }
+
+    /// Returns whether `line` of `filename` is test code.
+    #[must_use]
+    pub fn contains(&self, filename: &str, line: u64) -> bool {
+        self.ranges
+            .get(filename)
+            .is_some_and(|r| r.iter().any(|(start, end)| (*start..=*end).contains(&line)))
+    }
+
+    /// Returns whether all of `filename` is test code.
+    #[must_use]
+    pub fn is_whole_file(&self, filename: &str) -> bool {
+        self.ranges
+            .get(filename)
+            .is_some_and(|r| r.contains(&WHOLE_FILE))
+    }
+
+    /// Detects test files by path alone, without reading sources.
+    pub fn detect_paths<'a>(filenames: impl IntoIterator<Item = &'a str>) -> Self {
+        let ranges = filenames
+            .into_iter()
+            .filter(|filename| is_test_path(filename))
+            .map(|filename| (filename.to_string(), vec![WHOLE_FILE]))
+            .collect();
+        Self { ranges }
+    }
+}
+
+/// Returns whether `filename` lies in the `tests` directory of its package.
+///
+/// The package root is the nearest directory above `filename` holding a
+/// `Cargo.toml`. Without one, a relative `filename` is taken as relative to
+/// the package root, and an absolute one is not test code.
+#[must_use]
+pub fn is_test_path(filename: &str) -> bool {
+    let path = Path::new(filename);
+    let relative = match package_root(path) {
+        Some(root) => path.strip_prefix(root).unwrap_or(path),
+        None if path.is_relative() => path,
+        None => return false,
+    };
+    relative
+        .components()
+        .find(|c| *c != Component::CurDir)
+        .is_some_and(|c| c == Component::Normal("tests".as_ref()))
+}
+
+/// Returns the nearest directory above `path` holding a `Cargo.toml`.
+///
+/// For a relative `path`, the last candidate is the empty path, meaning the
+/// current directory.
+pub(crate) fn package_root(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("Cargo.toml").is_file())
+}
+
+/// Returns the inclusive line ranges of test items in `source`, including
+/// their attributes.
+///
+/// Returns no ranges if the source does not parse.
+#[must_use]
+pub fn test_ranges(source: &str) -> Vec<(u64, u64)> {
+    let mut test_modules = Vec::new();
+    let mut scan = Scan {
+        ranges: Vec::new(),
+        file_dir: Path::new(""),
+        test_modules: &mut test_modules,
+    };
+    if let Ok(file) = syn::parse_file(source) {
+        scan.items(&file.items, Path::new(""));
+    }
+    scan.ranges
+}
+
+/// Returns the directory holding the files of modules declared in
+/// `filename`: its own directory for `lib.rs`, `main.rs`, and `mod.rs`, and
+/// a directory named after the file otherwise.
+pub(crate) fn module_dir(filename: &Path) -> PathBuf {
+    let dir = filename.parent().unwrap_or_else(|| Path::new(""));
+    match filename.file_stem().and_then(|s| s.to_str()) {
+        Some("lib" | "main" | "mod") | None => dir.to_path_buf(),
+        Some(stem) => dir.join(stem),
+    }
+}
+
+/// Test items found while walking one file.
+struct Scan<'a> {
+    /// Line ranges of inline test items.
+    ranges: Vec<(u64, u64)>,
+    /// Directory of the file, which `#[path]` attributes are relative to.
+    file_dir: &'a Path,
+    /// Paths of out-of-line test modules: each is either the module file
+    /// itself or a directory whose files all belong to the module.
+    test_modules: &'a mut Vec<PathBuf>,
+}
+
+impl Scan<'_> {
+    /// Records test items among `items`, descending into inline modules and
+    /// impl blocks. `dir` is where out-of-line child modules live.
+    fn items(&mut self, items: &[Item], dir: &Path) {
+        for item in items {
+            if is_test_item(item_attrs(item)) {
+                if let Item::Mod(module) = item
+                    && module.content.is_none()
+                {
+                    self.test_module(module, dir);
+                }
+                self.ranges.push(line_range(item));
+                continue;
+            }
+            match item {
+                Item::Mod(module) => {
+                    if let Some((_, content)) = &module.content {
+                        self.items(content, &dir.join(module.ident.to_string()));
+                    }
+                }
+                Item::Impl(block) => {
+                    for impl_item in &block.items {
+                        if let ImplItem::Fn(function) = impl_item
+                            && is_test_item(&function.attrs)
+                        {
+                            self.ranges.push(line_range(function));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Records the files of an out-of-line test module whose default
+    /// location is in `dir`.
+    fn test_module(&mut self, module: &syn::ItemMod, dir: &Path) {
+        if let Some(path) = path_attr(&module.attrs) {
+            self.test_modules.push(self.file_dir.join(path));
+        } else {
+            let name = module.ident.to_string();
+            self.test_modules.push(dir.join(format!("{name}.rs")));
+            self.test_modules.push(dir.join(name));
+        }
+    }
+}
+
+/// Returns the value of a `#[path = "..."]` attribute, if present.
+pub(crate) fn path_attr(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(nv) if nv.path.is_ident("path") => match &nv.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            }) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Returns the attributes of `item`.
+pub(crate) fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(i) => &i.attrs,
+        Item::Enum(i) => &i.attrs,
+        Item::Fn(i) => &i.attrs,
+        Item::Impl(i) => &i.attrs,
+        Item::Macro(i) => &i.attrs,
+        Item::Mod(i) => &i.attrs,
+        Item::Static(i) => &i.attrs,
+        Item::Struct(i) => &i.attrs,
+        Item::Trait(i) => &i.attrs,
+        Item::Type(i) => &i.attrs,
+        Item::Union(i) => &i.attrs,
+        Item::Use(i) => &i.attrs,
+        _ => &[],
+    }
+}
+
+/// Returns whether the attributes mark an item as test-only: a `cfg` that
+/// requires `test`, or a test attribute such as `#[test]` or
+/// `#[tokio::test]`.
+pub(crate) fn is_test_item(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        let path = attr.path();
+        if path.is_ident("cfg") {
+            attr.parse_args::<Meta>()
+                .is_ok_and(|meta| requires_test(&meta))
+        } else {
+            path.segments.last().is_some_and(|s| s.ident == "test")
+        }
+    })
+}
+
+/// Returns whether a `cfg` predicate can only hold in test builds.
+fn requires_test(meta: &Meta) -> bool {
+    match meta {
+        Meta::Path(path) => path.is_ident("test"),
+        Meta::List(list) if list.path.is_ident("all") => list
+            .parse_args_with(syn::punctuated::Punctuated::<Meta, syn::Token![,]>::parse_terminated)
+            .is_ok_and(|predicates| predicates.iter().any(requires_test)),
+        _ => false,
+    }
+}
+
+/// Returns the inclusive line range spanned by `node`.
+pub(crate) fn line_range(node: &impl Spanned) -> (u64, u64) {
+    let span = node.span();
+    let line = |l: usize| u64::try_from(l).unwrap_or(u64::MAX);
+    (line(span.start().line), line(span.end().line))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cfg_test_module() {
+        let source = "fn a() {}\n\n#[cfg(test)]\nmod tests {\n    fn helper() {}\n}\n";
+        assert_eq!(test_ranges(source), vec![(3, 6)]);
+    }
+
+    #[test]
+    fn test_test_functions() {
+        let source = "fn a() {}\n#[test]\nfn t() {}\n#[tokio::test]\nasync fn u() {}\n";
+        assert_eq!(test_ranges(source), vec![(2, 3), (4, 5)]);
+    }
+
+    #[test]
+    fn test_cfg_predicates() {
+        let source = "#[cfg(all(test, unix))]\nfn a() {}\n\
+                      #[cfg(not(test))]\nfn b() {}\n\
+                      #[cfg(any(test, unix))]\nfn c() {}\n";
+        assert_eq!(test_ranges(source), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_nested_modules_and_impls() {
+        let source = "mod a {\n    #[cfg(test)]\n    fn helper() {}\n}\n\
+                      impl S {\n    #[test]\n    fn t() {}\n}\n";
+        assert_eq!(test_ranges(source), vec![(2, 3), (6, 7)]);
+    }
+
+    #[test]
+    fn test_unparsable_source_has_no_ranges() {
+        assert!(test_ranges("fn (").is_empty());
+    }
+
+    #[test]
+    fn test_is_test_path() {
+        // Relative paths without a manifest are relative to the package.
+        assert!(is_test_path("tests/common/mod.rs"));
+        assert!(is_test_path("./tests/smoke.rs"));
+        assert!(!is_test_path("src/tests/mod.rs"));
+        assert!(!is_test_path("tests.rs"));
+        assert!(!is_test_path("/nonexistent/tests/smoke.rs"));
+
+        // A package checked out under a `tests` directory.
+        let dir = std::env::temp_dir().join(format!("llvm-cov-easy-{}-path", std::process::id()));
+        let root = dir.join("tests/pkg");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+        let path = |relative: &str| root.join(relative).to_string_lossy().into_owned();
+        assert!(is_test_path(&path("tests/smoke.rs")));
+        assert!(is_test_path(&path("tests/common/mod.rs")));
+        assert!(!is_test_path(&path("src/lib.rs")));
+        assert!(!is_test_path(&path("src/tests/mod.rs")));
+        assert!(!is_test_path(&path("src/tests.rs")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect_paths() {
+        let test_code = TestCode::detect_paths(["src/lib.rs", "tests/it.rs"]);
+        assert!(!test_code.contains("src/lib.rs", 1));
+        assert!(test_code.is_whole_file("tests/it.rs"));
+    }
+
+    #[test]
+    fn test_module_dir() {
+        assert_eq!(
+            module_dir(Path::new("/ws/src/lib.rs")),
+            Path::new("/ws/src")
+        );
+        assert_eq!(
+            module_dir(Path::new("/ws/src/a/mod.rs")),
+            Path::new("/ws/src/a")
+        );
+        assert_eq!(
+            module_dir(Path::new("/ws/src/a.rs")),
+            Path::new("/ws/src/a")
+        );
+    }
+
+    #[test]
+    fn test_out_of_line_test_modules() {
+        let mut sources = SourceCache::new();
+        sources.insert(
+            "/ws/src/lib.rs",
+            "#[cfg(test)]\nmod helpers;\nmod parse;\n#[cfg(test)]\n#[path = \"fixtures.rs\"]\nmod data;\n",
+        );
+        sources.insert("/ws/src/parse.rs", "#[cfg(test)]\nmod tests;\n");
+        let files = [
+            "/ws/src/lib.rs",
+            "/ws/src/parse.rs",
+            "/ws/src/helpers.rs",
+            "/ws/src/helpers/builders.rs",
+            "/ws/src/fixtures.rs",
+            "/ws/src/parse/tests.rs",
+        ];
+        let test_code = TestCode::detect(files, &mut sources);
+        let whole: Vec<&str> = files
+            .into_iter()
+            .filter(|f| test_code.is_whole_file(f))
+            .collect();
+        assert_eq!(whole, &files[2..]);
+        assert!(test_code.contains("/ws/src/lib.rs", 2));
+        assert!(!test_code.contains("/ws/src/lib.rs", 3));
+    }
+
+    #[test]
+    fn test_detect() {
+        let mut sources = SourceCache::new();
+        sources.insert("src/lib.rs", "fn a() {}\n#[test]\nfn t() {}\n");
+        sources.insert("tests/it.rs", "fn helper() {}\n");
+        let test_code = TestCode::detect(["src/lib.rs", "tests/it.rs"], &mut sources);
+        assert!(!test_code.contains("src/lib.rs", 1));
+        assert!(test_code.contains("src/lib.rs", 3));
+        assert!(!test_code.is_whole_file("src/lib.rs"));
+        assert!(test_code.is_whole_file("tests/it.rs"));
+        assert!(test_code.contains("tests/it.rs", 1));
+    }
+}
@@ -0,0 +1,289 @@
+//! Per-file coverage totals.
+//!
+//! Totals are normally taken from the summaries `llvm-cov` exports. When
+//! part of the code is excluded they are recomputed from segments, branch
+//! records, and function records using the same rules as `llvm-cov`.
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{
+    CoverageSummary, InstantiationKey, group_instantiations, line_coverage, merge_branches,
+};
+use crate::model::{CoverageCounts, ExportData, FunctionData, Summary};
+use crate::test_code::TestCode;
+
+/// Covered and total counts for one coverage metric.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counts {
+    /// Number of covered items.
+    pub covered: u64,
+    /// Total number of items.
+    pub count: u64,
+}
+
+impl Counts {
+    /// Returns the covered percentage, or 0 when there are no items, as
+    /// `llvm-cov` does.
+    #[must_use]
+    pub fn percent(self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.covered as f64 * 100.0 / self.count as f64
+        }
+    }
+}
+
+impl From<&CoverageCounts> for Counts {
+    fn from(counts: &CoverageCounts) -> Self {
+        Self {
+            covered: counts.covered,
+            count: counts.count,
+        }
+    }
+}
+
+impl AddAssign for Counts {
+    fn add_assign(&mut self, other: Self) {
+        self.covered += other.covered;
+        self.count += other.count;
+    }
+}
+
+/// Counts for every metric over a file or a group of files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Totals {
+    /// Line counts.
+    pub lines: Counts,
+    /// Code region counts.
+    pub regions: Counts,
+    /// Branch direction counts.
+    pub branches: Counts,
+    /// Function counts, with all instantiations of a generic function
+    /// counted once.
+    pub functions: Counts,
+    /// Function instantiation counts.
+    pub instantiations: Counts,
+}
+
+impl Totals {
+    /// Reads the counts from an exported summary.
+    #[must_use]
+    pub fn from_summary(summary: &Summary) -> Self {
+        let counts = |c: Option<&CoverageCounts>| c.map(Counts::from).unwrap_or_default();
+        Self {
+            lines: counts(summary.lines.as_ref()),
+            regions: counts(summary.regions.as_ref()),
+            branches: counts(summary.branches.as_ref()),
+            functions: counts(summary.functions.as_ref()),
+            instantiations: counts(summary.instantiations.as_ref()),
+        }
+    }
+
+    /// Converts the counts into percentages, leaving out branches when
+    /// there are none and always leaving out instantiations.
+    #[must_use]
+    pub fn summary(&self) -> CoverageSummary {
+        CoverageSummary {
+            lines_percent: self.lines.percent(),
+            regions_percent: self.regions.percent(),
+            branches_percent: (self.branches.count > 0).then(|| self.branches.percent()),
+            functions_percent: self.functions.percent(),
+            instantiations_percent: None,
+        }
+    }
+}
+
+impl AddAssign<&Self> for Totals {
+    fn add_assign(&mut self, other: &Self) {
+        self.lines += other.lines;
+        self.regions += other.regions;
+        self.branches += other.branches;
+        self.functions += other.functions;
+        self.instantiations += other.instantiations;
+    }
+}
+
+/// The totals of one source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTotals {
+    /// File path as it appears in the coverage data.
+    pub filename: String,
+    /// Counts over the file.
+    pub totals: Totals,
+}
+
+/// Adds up the totals of `files`.
+#[must_use]
+pub fn sum(files: &[FileTotals]) -> Totals {
+    let mut overall = Totals::default();
+    for file in files {
+        overall += &file.totals;
+    }
+    overall
+}
+
+/// Reads each file's totals from its exported summary.
+pub(crate) fn exported(data: &ExportData) -> Vec<FileTotals> {
+    data.files
+        .iter()
+        .map(|file| FileTotals {
+            filename: file.filename.clone(),
+            totals: Totals::from_summary(&file.summary),
+        })
+        .collect()
+}
+
+/// Recomputes each file's totals, leaving out `excluded` code.
+///
+/// Lines come from the file's segments and branches from its merged branch
+/// records. Regions, functions, and instantiations come from function
+/// records grouped, like `llvm-cov` does, by location alone, so functions
+/// expanded from one macro call count once. A group counts as one
+/// function, covered if any instantiation ran, and contributes the region
+/// counts of its best-covered instantiation. A function is excluded when it
+/// starts in excluded code. Files that are excluded entirely are left out.
+pub(crate) fn recompute(data: &ExportData, excluded: &TestCode) -> Vec<FileTotals> {
+    let mut locations: BTreeMap<(&str, u64, u64), Vec<&FunctionData>> = BTreeMap::new();
+    for (
+        InstantiationKey {
+            filename,
+            line,
+            col,
+            ..
+        },
+        group,
+    ) in group_instantiations(&data.functions)
+    {
+        locations
+            .entry((filename, line, col))
+            .or_default()
+            .extend(group);
+    }
+    let mut function_totals: BTreeMap<&str, Totals> = BTreeMap::new();
+    for ((filename, line, _), group) in locations {
+        if excluded.contains(filename, line) {
+            continue;
+        }
+        let totals = function_totals.entry(filename).or_default();
+        let executed = group.iter().filter(|f| f.count > 0).count() as u64;
+        totals.functions += Counts {
+            covered: u64::from(executed > 0),
+            count: 1,
+        };
+        totals.instantiations += Counts {
+            covered: executed,
+            count: group.len() as u64,
+        };
+        let regions: Vec<Counts> = group.iter().map(|f| code_regions(f)).collect();
+        totals.regions += Counts {
+            covered: regions.iter().map(|c| c.covered).max().unwrap_or(0),
+            count: regions.iter().map(|c| c.count).max().unwrap_or(0),
+        };
+    }
+
+    let mut files = Vec::new();
+    for file in &data.files {
+        if excluded.is_whole_file(&file.filename) {
+            continue;
+        }
+        let mut totals = function_totals
+            .remove(file.filename.as_str())
+            .unwrap_or_default();
+        for line in line_coverage(&file.segments) {
+            if !excluded.contains(&file.filename, line.line) {
+                totals.lines += Counts {
+                    covered: u64::from(line.count > 0),
+                    count: 1,
+                };
+            }
+        }
+        for ((line, _), (true_count, false_count)) in merge_branches(&file.branches) {
+            if !excluded.contains(&file.filename, line) {
+                totals.branches += Counts {
+                    covered: u64::from(true_count > 0) + u64::from(false_count > 0),
+                    count: 2,
+                };
+            }
+        }
+        files.push(FileTotals {
+            filename: file.filename.clone(),
+            totals,
+        });
+    }
+    files
+}
+
+/// Counts the code regions of one function instantiation.
+fn code_regions(function: &FunctionData) -> Counts {
+    let mut counts = Counts::default();
+    for region in function.regions.iter().filter(|r| r.kind.is_code()) {
+        counts += Counts {
+            covered: u64::from(region.execution_count > 0),
+            count: 1,
+        };
+    }
+    counts
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::source::SourceCache;
+
+    fn data(json: &str) -> ExportData {
+        crate::parse_json(json).unwrap().data.remove(0)
+    }
+
+    #[test]
+    fn test_recompute_matches_exported_totals() {
+        for json in [
+            include_str!("../tests/fixtures/show-missing-lines.json"),
+            include_str!("../tests/fixtures/show-missing-lines-complete.json"),
+            include_str!("../tests/fixtures/show-missing-lines-multi-missing.json"),
+            include_str!("../tests/fixtures/all-covered.json"),
+        ] {
+            let data = data(json);
+            let exported = exported(&data);
+            let recomputed = recompute(&data, &TestCode::default());
+            for (exported, recomputed) in exported.iter().zip(&recomputed) {
+                let mut expected = exported.totals;
+                // These fixtures were exported without branch coverage.
+                expected.branches = recomputed.totals.branches;
+                assert_eq!(recomputed.totals, expected, "{}", exported.filename);
+            }
+        }
+    }
+
+    #[test]
+    fn test_recompute_excludes_test_code() {
+        let mut data = data(include_str!(
+            "../tests/fixtures/show-missing-lines-multi-missing.json"
+        ));
+        let mut source = "fn f() {}\n".repeat(200);
+        source.insert_str(0, "#[cfg(test)]\nmod tests {\n");
+        source.push_str("}\n");
+        let mut sources = SourceCache::new();
+        sources.insert("src/lib.rs", source);
+        let excluded = TestCode::detect(["src/lib.rs"], &mut sources);
+        assert_eq!(recompute(&data, &excluded)[0].totals, Totals::default());
+
+        data.files[0].filename = "tests/it.rs".to_string();
+        let excluded = TestCode::detect(["tests/it.rs"], &mut sources);
+        assert!(recompute(&data, &excluded).is_empty());
+    }
+
+    #[test]
+    fn test_counts_percent() {
+        assert!(Counts::default().percent().abs() < f64::EPSILON);
+        let quarter = Counts {
+            covered: 1,
+            count: 4,
+        };
+        assert!((quarter.percent() - 25.0).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,397 @@
+//! Cargo workspace layout.
+//!
+//! Maps the files in coverage data to the workspace package and target
+//! that own them, using the output of
+//! `cargo metadata --format-version 1 --no-deps`, and rolls file totals up
+//! per package and per module directory.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::analysis::{AnalysisResult, CoverageSummary};
+use crate::public_api::CrateRoot;
+use crate::totals::{FileTotals, Totals};
+
+/// The member packages of a cargo workspace.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// Workspace member packages, in `cargo metadata` order.
+    pub packages: Vec<Package>,
+    /// Build output directory, if `cargo metadata` reported one.
+    pub target_directory: Option<PathBuf>,
+}
+
+/// A workspace member package.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    /// Package ID, as listed in `workspace_members`.
+    pub id: String,
+    /// Package name.
+    pub name: String,
+    /// Path to the package's `Cargo.toml`.
+    pub manifest_path: PathBuf,
+    /// Build targets of the package.
+    pub targets: Vec<Target>,
+}
+
+/// A build target (library, binary, test, bench, example, ...).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Target {
+    /// Target name.
+    pub name: String,
+    /// Target kinds, e.g. `["lib"]`, `["bin"]`, or `["test"]`.
+    pub kind: Vec<String>,
+    /// Path to the target's root source file.
+    pub src_path: PathBuf,
+}
+
+/// The package and target that own a source file.
+#[derive(Debug, Clone, Copy)]
+pub struct FileOwner<'a> {
+    /// Owning package.
+    pub package: &'a Package,
+    /// Owning target, if the file lies under a target's source directory.
+    pub target: Option<&'a Target>,
+}
+
+/// Coverage totals for a package or one of its module directories.
+#[derive(Debug, Clone)]
+pub struct Rollup {
+    /// Package name, followed by `/` and the module directory relative to
+    /// the package root for module rollups (e.g. `core/src/parser`).
+    pub name: String,
+    /// Coverage percentages over the files in the rollup.
+    pub summary: CoverageSummary,
+}
+
+/// Raw `cargo metadata` output; only the fields we need.
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+    target_directory: Option<PathBuf>,
+}
+
+impl Package {
+    /// Returns the package's root directory.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or_else(|| Path::new(""))
+    }
+}
+
+impl Workspace {
+    /// Parses the JSON printed by `cargo metadata --format-version 1`.
+    ///
+    /// Only workspace members are kept, so `--no-deps` is not required.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed.
+    pub fn from_metadata(json: &str) -> Result<Self, serde_json::Error> {
+        let metadata: Metadata = serde_json::from_str(json)?;
+        let packages = metadata
+            .packages
+            .into_iter()
+            .filter(|p| metadata.workspace_members.contains(&p.id))
+            .collect();
+        Ok(Self {
+            packages,
+            target_directory: metadata.target_directory,
+        })
+    }
+
+    /// Returns the package with the given name.
+    #[must_use]
+    pub fn package(&self, name: &str) -> Option<&Package> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+
+    /// Returns the root file of every library target, in package order.
+    #[must_use]
+    pub fn library_roots(&self) -> Vec<CrateRoot> {
+        self.packages
+            .iter()
+            .flat_map(|p| &p.targets)
+            .filter(|t| t.kind.iter().any(|k| k == "lib" || k == "proc-macro"))
+            .map(|t| CrateRoot {
+                name: t.name.replace('-', "_"),
+                path: t.src_path.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the package and target that own `filename`.
+    ///
+    /// Nested packages win over their parents. Within a package, a target
+    /// whose root file is `filename` wins; otherwise the target with the
+    /// deepest source directory containing the file is chosen, preferring
+    /// the library when several share a directory.
+    #[must_use]
+    pub fn owner(&self, filename: &str) -> Option<FileOwner<'_>> {
+        let path = Path::new(filename);
+        let package = self
+            .packages
+            .iter()
+            .filter(|p| path.starts_with(p.root()))
+            .max_by_key(|p| p.root().components().count())?;
+        let target = package
+            .targets
+            .iter()
+            .find(|t| t.src_path == path)
+            .or_else(|| {
+                package
+                    .targets
+                    .iter()
+                    .filter_map(|t| Some((t, t.src_path.parent()?)))
+                    .filter(|(_, dir)| path.starts_with(dir))
+                    .max_by_key(|(t, dir)| {
+                        (dir.components().count(), t.kind.iter().any(|k| k == "lib"))
+                    })
+                    .map(|(t, _)| t)
+            });
+        Some(FileOwner { package, target })
+    }
+
+    /// Computes coverage totals per package, in name order.
+    ///
+    /// With `modules`, each package is followed by one rollup per directory
+    /// containing covered files. Files outside every workspace package are
+    /// left out.
+    #[must_use]
+    pub fn rollup(&self, files: &[FileTotals], modules: bool) -> Vec<Rollup> {
+        let mut totals: BTreeMap<(&str, Option<String>), Totals> = BTreeMap::new();
+        for file in files {
+            let Some(owner) = self.owner(&file.filename) else {
+                continue;
+            };
+            let name = owner.package.name.as_str();
+            *totals.entry((name, None)).or_default() += &file.totals;
+            if modules {
+                let module = module_dir(owner.package, &file.filename);
+                *totals.entry((name, Some(module))).or_default() += &file.totals;
+            }
+        }
+        totals
+            .into_iter()
+            .map(|((package, module), totals)| Rollup {
+                name: module.map_or_else(|| package.to_string(), |m| format!("{package}/{m}")),
+                summary: totals.summary(),
+            })
+            .collect()
+    }
+
+    /// Restricts the gap listing, hot lines, exclusions, public API, and
+    /// rollups to `packages`.
+    ///
+    /// Must run before [`AnalysisResult::relativize_paths`], since package
+    /// roots are matched against the paths in the coverage data.
+    pub fn retain_packages(&self, result: &mut AnalysisResult, packages: &[String]) {
+        let selected = |filename: &str| {
+            self.owner(filename)
+                .is_some_and(|o| packages.contains(&o.package.name))
+        };
+        result.files.retain(|f| selected(&f.filename));
+        result.hot_lines.retain(|h| selected(&h.filename));
+        result.exclusions.retain(|e| selected(&e.filename));
+        result.public_api.retain(|f| selected(&f.filename));
+        result.rollups.retain(|r| {
+            packages.iter().any(|p| {
+                r.name
+                    .strip_prefix(p.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+        });
+    }
+}
+
+/// Returns the directory of `filename` relative to the package root, or
+/// `.` for files at the root.
+fn module_dir(package: &Package, filename: &str) -> String {
+    let path = Path::new(filename);
+    let relative = path.strip_prefix(package.root()).unwrap_or(path);
+    match relative.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::analysis::{FileGaps, HotLine};
+    use crate::totals::Counts;
+
+    const METADATA: &str = r#"{
+        "packages": [
+            {
+                "id": "app 0.1.0",
+                "name": "app",
+                "manifest_path": "/ws/Cargo.toml",
+                "targets": [
+                    {"name": "app", "kind": ["lib"], "src_path": "/ws/src/lib.rs"},
+                    {"name": "app", "kind": ["bin"], "src_path": "/ws/src/main.rs"},
+                    {"name": "cli", "kind": ["bin"], "src_path": "/ws/src/bin/cli.rs"},
+                    {"name": "smoke", "kind": ["test"], "src_path": "/ws/tests/smoke.rs"}
+                ]
+            },
+            {
+                "id": "core 0.1.0",
+                "name": "core",
+                "manifest_path": "/ws/crates/core/Cargo.toml",
+                "targets": [
+                    {"name": "core", "kind": ["lib"], "src_path": "/ws/crates/core/src/lib.rs"}
+                ]
+            },
+            {
+                "id": "serde 1.0.0",
+                "name": "serde",
+                "manifest_path": "/registry/serde/Cargo.toml",
+                "targets": []
+            }
+        ],
+        "workspace_members": ["app 0.1.0", "core 0.1.0"],
+        "target_directory": "/ws/target"
+    }"#;
+
+    fn workspace() -> Workspace {
+        Workspace::from_metadata(METADATA).unwrap()
+    }
+
+    fn owner(filename: &str) -> Option<(String, Option<String>)> {
+        let workspace = workspace();
+        let owner = workspace.owner(filename)?;
+        Some((
+            owner.package.name.clone(),
+            owner.target.map(|t| format!("{}:{}", t.kind[0], t.name)),
+        ))
+    }
+
+    #[test]
+    fn test_only_workspace_members_are_kept() {
+        let names: Vec<String> = workspace().packages.into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["app", "core"]);
+        assert_eq!(
+            workspace().target_directory,
+            Some(PathBuf::from("/ws/target"))
+        );
+    }
+
+    #[test]
+    fn test_owner_prefers_nested_packages() {
+        assert_eq!(
+            owner("/ws/crates/core/src/parse.rs"),
+            Some(("core".to_string(), Some("lib:core".to_string())))
+        );
+        assert_eq!(owner("/registry/serde/src/lib.rs"), None);
+    }
+
+    #[test]
+    fn test_owner_target_selection() {
+        let target = |filename| owner(filename).and_then(|(_, t)| t);
+        assert_eq!(target("/ws/src/main.rs").as_deref(), Some("bin:app"));
+        assert_eq!(target("/ws/src/model.rs").as_deref(), Some("lib:app"));
+        assert_eq!(target("/ws/src/bin/util.rs").as_deref(), Some("bin:cli"));
+        assert_eq!(
+            target("/ws/tests/common/mod.rs").as_deref(),
+            Some("test:smoke")
+        );
+        assert_eq!(target("/ws/build.rs"), None);
+    }
+
+    #[test]
+    fn test_module_dir() {
+        let workspace = workspace();
+        let app = workspace.package("app").unwrap();
+        assert_eq!(module_dir(app, "/ws/src/lib.rs"), "src");
+        assert_eq!(module_dir(app, "/ws/src/a/b.rs"), "src/a");
+        assert_eq!(module_dir(app, "/ws/build.rs"), ".");
+    }
+
+    #[test]
+    fn test_rollup() {
+        let file = |filename: &str, covered| FileTotals {
+            filename: filename.to_string(),
+            totals: Totals {
+                lines: Counts { covered, count: 4 },
+                ..Totals::default()
+            },
+        };
+        let files = [
+            file("/ws/src/lib.rs", 4),
+            file("/ws/src/a/b.rs", 0),
+            file("/ws/crates/core/src/lib.rs", 1),
+            file("/registry/serde/src/lib.rs", 0),
+        ];
+        let rollups: Vec<(String, f64)> = workspace()
+            .rollup(&files, true)
+            .into_iter()
+            .map(|r| (r.name, r.summary.lines_percent))
+            .collect();
+        assert_eq!(
+            rollups,
+            vec![
+                ("app".to_string(), 50.0),
+                ("app/src".to_string(), 100.0),
+                ("app/src/a".to_string(), 0.0),
+                ("core".to_string(), 25.0),
+                ("core/src".to_string(), 25.0),
+            ]
+        );
+        assert_eq!(workspace().rollup(&files, false).len(), 2);
+    }
+
+    #[test]
+    fn test_library_roots() {
+        assert_eq!(
+            workspace().library_roots(),
+            vec![
+                CrateRoot {
+                    name: "app".to_string(),
+                    path: PathBuf::from("/ws/src/lib.rs"),
+                },
+                CrateRoot {
+                    name: "core".to_string(),
+                    path: PathBuf::from("/ws/crates/core/src/lib.rs"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_retain_packages() {
+        let file = |filename: &str| FileGaps {
+            filename: filename.to_string(),
+            gaps: vec![],
+            functions: vec![],
+        };
+        let mut result = AnalysisResult {
+            files: vec![file("/ws/src/lib.rs"), file("/ws/crates/core/src/lib.rs")],
+            hot_lines: vec![HotLine {
+                filename: "/ws/src/lib.rs".to_string(),
+                line: 1,
+                count: 9,
+            }],
+            rollups: vec![
+                Rollup {
+                    name: "app".to_string(),
+                    summary: Totals::default().summary(),
+                },
+                Rollup {
+                    name: "core/src".to_string(),
+                    summary: Totals::default().summary(),
+                },
+            ],
+            ..AnalysisResult::default()
+        };
+        workspace().retain_packages(&mut result, &["core".to_string()]);
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].filename, "/ws/crates/core/src/lib.rs");
+        assert!(result.hot_lines.is_empty());
+        assert_eq!(result.rollups.len(), 1);
+        assert_eq!(result.rollups[0].name, "core/src");
+    }
+}
@@ -54,6 +54,116 @@ mod tests {
         assert_snapshot!(output);
     }
 
+    #[test]
+    fn test_instantiations() {
+        let json = include_str!("fixtures/show-missing-lines-multi-missing.json");
+        let options = llvm_cov_easy::analysis::AnalysisOptions {
+            instantiations: true,
+            ..Default::default()
+        };
+        let result = llvm_cov_easy::analyze_json_with_options(json, &options).unwrap();
+        assert_snapshot!(llvm_cov_easy::format::format_result(&result));
+    }
+
+    #[test]
+    fn test_weak_and_hot_lines() {
+        let json = include_str!("fixtures/with-branches.json");
+        let options = llvm_cov_easy::analysis::AnalysisOptions {
+            weak_threshold: Some(6),
+            hot_lines: Some(3),
+            ..Default::default()
+        };
+        let result = llvm_cov_easy::analyze_json_with_options(json, &options).unwrap();
+        assert_snapshot!(llvm_cov_easy::format::format_result(&result));
+    }
+
+    #[test]
+    fn test_grouped_weak() {
+        let json = include_str!("fixtures/with-branches.json");
+        let options = llvm_cov_easy::analysis::AnalysisOptions {
+            weak_threshold: Some(6),
+            ..Default::default()
+        };
+        let result = llvm_cov_easy::analyze_json_with_options(json, &options).unwrap();
+        let format_options = llvm_cov_easy::format::FormatOptions {
+            grouped: true,
+            ..Default::default()
+        };
+        assert_snapshot!(llvm_cov_easy::format::format_result_with(
+            &result,
+            &format_options
+        ));
+    }
+
+    #[test]
+    fn test_workspace_rollups() {
+        let json = include_str!("fixtures/real1-all.json");
+        let metadata = include_str!("fixtures/real1-metadata.json");
+        let workspace = llvm_cov_easy::workspace::Workspace::from_metadata(metadata).unwrap();
+        let mut result = llvm_cov_easy::analyze_json(json).unwrap();
+        result.rollups = workspace.rollup(&result.file_totals, true);
+        workspace.retain_packages(&mut result, &["member1".to_string(), "member2".to_string()]);
+        assert_snapshot!(llvm_cov_easy::format::format_result(&result));
+    }
+
+    #[test]
+    fn test_retain_packages_with_gaps() {
+        let json = include_str!("fixtures/two-packages.json");
+        let metadata = include_str!("fixtures/real1-metadata.json");
+        let workspace = llvm_cov_easy::workspace::Workspace::from_metadata(metadata).unwrap();
+        let mut result = llvm_cov_easy::analyze_json(json).unwrap();
+        assert_eq!(result.files.len(), 2);
+        workspace.retain_packages(&mut result, &["member1".to_string()]);
+        assert_snapshot!(llvm_cov_easy::format::format_result(&result));
+    }
+
+    /// Asserts that line counts recomputed from segments match the line
+    /// totals `llvm-cov` exported for every file in the fixture.
+    ///
+    /// `with-branches.json` is hand-written and its totals were not derived
+    /// from its segments, so it is not cross-checked.
+    fn assert_line_totals_match(json: &str) {
+        let export = llvm_cov_easy::parse_json(json).unwrap();
+        for file in &export.data[0].files {
+            let lines = llvm_cov_easy::analysis::line_coverage(&file.segments);
+            let expected = file.summary.lines.as_ref().unwrap();
+            let covered = lines.iter().filter(|l| l.count > 0).count() as u64;
+            assert_eq!(
+                (lines.len() as u64, covered),
+                (expected.count, expected.covered),
+                "line totals differ for {}",
+                file.filename
+            );
+        }
+    }
+
+    #[test]
+    fn test_line_totals_match_show_missing_lines() {
+        assert_line_totals_match(include_str!("fixtures/show-missing-lines.json"));
+    }
+
+    #[test]
+    fn test_line_totals_match_show_missing_lines_complete() {
+        assert_line_totals_match(include_str!("fixtures/show-missing-lines-complete.json"));
+    }
+
+    #[test]
+    fn test_line_totals_match_show_missing_lines_multi_missing() {
+        assert_line_totals_match(include_str!(
+            "fixtures/show-missing-lines-multi-missing.json"
+        ));
+    }
+
+    #[test]
+    fn test_line_totals_match_all_covered() {
+        assert_line_totals_match(include_str!("fixtures/all-covered.json"));
+    }
+
+    #[test]
+    fn test_line_totals_match_two_packages() {
+        assert_line_totals_match(include_str!("fixtures/two-packages.json"));
+    }
+
     #[test]
     fn test_malformed_json() {
         let result = llvm_cov_easy::analyze_and_format("not json");